@@ -7,7 +7,8 @@ pub struct EditorAudio {
     pub delete: Sound,
     pub space:  Sound,
     pub enter:  Sound,
-    pub nav:    Sound
+    pub nav:    Sound,
+    pub alert:  Sound
 }
 
 impl EditorAudio {
@@ -17,7 +18,8 @@ impl EditorAudio {
             delete: load_sound("assets/sound/del.wav"   ).await.unwrap(),
             enter:  load_sound("assets/sound/return.wav").await.unwrap(),
             space:  load_sound("assets/sound/space.wav" ).await.unwrap(),
-            nav:    load_sound("assets/sound/nav.wav"   ).await.unwrap()
+            nav:    load_sound("assets/sound/nav.wav"   ).await.unwrap(),
+            alert:  load_sound("assets/sound/alert.wav" ).await.unwrap()
         };
 
         editor_audio
@@ -42,4 +44,8 @@ impl EditorAudio {
     pub fn play_return(&self) {
         play_sound_once(&self.enter);
     }
+
+    pub fn play_alert(&self) {
+        play_sound_once(&self.alert);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,165 @@
+// Buffer manager module, holds every open file,
+// each with its own cursor, scroll and undo history.
+//
+// The open files are drawn as tabs above the text.
+// LCTRL + Tab / LCTRL + LSHIFT + Tab cycle through them,
+// LCTRL + W closes the current one (asking first if it is unsaved),
+// LCTRL + 6 swaps back to the one shown before (see editor_recent.rs).
+// These are the default keys of the 'buffer.*' commands (see editor_keymap.rs).
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use macroquad::prelude::*;
+
+use crate::editor_audio::EditorAudio;
+use crate::editor_commands::BufferCommand;
+use crate::editor_console::{ConsolePrompt, EditorConsole};
+use crate::editor_file::EditorFile;
+use crate::editor_palette::palette;
+use crate::editor_text::*;
+
+const TAB_FONT_SIZE: u16 = 16;
+const TAB_PADDING: f32 = 10.0;
+const TAB_HEIGHT: f32 = 22.0;
+
+pub struct EditorBuffers {
+    pub files: Vec<EditorFile>,
+    pub current: usize
+}
+
+impl EditorBuffers {
+    /// Buffers constructor, starts with a single untitled file
+    pub fn new() -> EditorBuffers {
+        EditorBuffers { files: vec![EditorFile::new()], current: 0 }
+    }
+
+    pub fn current(&self) -> &EditorFile {
+        &self.files[self.current]
+    }
+
+    pub fn current_mut(&mut self) -> &mut EditorFile {
+        &mut self.files[self.current]
+    }
+
+    /// Index of the buffer with the file id 'id'
+    pub fn index_of(&self, id: usize) -> Option<usize> {
+        self.files.iter().position(|f| f.id == id)
+    }
+
+    /// Index of the buffer holding 'path'
+    pub fn find(&self, path: &Path) -> Option<usize> {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+        self.files.iter().position(|f| {
+            f.path.as_ref().is_some_and(|p| fs::canonicalize(p).unwrap_or_else(|_| p.clone()) == path)
+        })
+    }
+
+    /// Switch to the buffer of 'path', reading it first if it is not open.
+    /// An untouched untitled buffer is replaced rather than kept around.
+    pub fn open(&mut self, path: &Path) -> io::Result<()> {
+        if let Some(index) = self.find(path) {
+            self.current = index;
+            return Ok(());
+        }
+
+        self.add(EditorFile::load(path)?);
+        Ok(())
+    }
+
+    /// Switch to a new buffer, in place of an untouched untitled one
+    pub fn add(&mut self, file: EditorFile) {
+        if self.current().is_pristine() {
+            self.files[self.current] = file;
+        } else {
+            self.files.push(file);
+            self.current = self.files.len() - 1;
+        }
+    }
+
+    pub fn next(&mut self) {
+        self.current = (self.current + 1) % self.files.len();
+    }
+
+    pub fn previous(&mut self) {
+        self.current = (self.current + self.files.len() - 1) % self.files.len();
+    }
+
+    /// Close a buffer without asking, there is always one left open
+    pub fn close(&mut self, index: usize) {
+        if index >= self.files.len() {
+            return;
+        }
+
+        self.files.remove(index);
+        if self.files.is_empty() {
+            self.files.push(EditorFile::new());
+        }
+
+        if self.current > index || self.current >= self.files.len() {
+            self.current = self.current.saturating_sub(1);
+        }
+    }
+
+    /// Buffer tabs, drawn above the text of an area starting at 'x'
+    pub fn draw(&self, x: f32, gts: &EditorGeneralTextStylizer) {
+        let mut x = x + FILE_TEXT_X_MARGIN;
+
+        for (i, file) in self.files.iter().enumerate() {
+            let mut label = if file.is_dirty() { format!("{}*", file.name()) } else { file.name() };
+            if file.readonly {
+                label = format!("{} [RO]", label);
+            }
+            if let Some(loader) = &file.loading {
+                label = format!("{} {}%", label, loader.percent());
+            }
+            let width = measure_text(&label, Some(&gts.font), TAB_FONT_SIZE, 1.0).width + 2.0 * TAB_PADDING;
+
+            if i == self.current {
+                draw_rectangle(x, 0.0, width, TAB_HEIGHT, palette().composite_type);
+            }
+
+            draw_text_ex(&label, x + TAB_PADDING, TAB_HEIGHT * 0.75,
+                TextParams { font: Some(&gts.font), font_size: TAB_FONT_SIZE, color: WHITE, ..Default::default() });
+
+            x += width;
+        }
+    }
+}
+
+/// Ask before closing a buffer with unsaved changes
+pub fn request_close_buffer(buffers: &mut EditorBuffers, index: usize, console: &mut EditorConsole) {
+    if buffers.files[index].is_dirty() {
+        let name = buffers.files[index].name();
+        console.ask(ConsolePrompt::CloseBuffer(buffers.files[index].id), &format!("{} has unsaved changes, close it? (y/n)", name));
+    } else {
+        buffers.close(index);
+    }
+}
+
+/// Buffer switching and closing, the 'buffer.*' commands
+pub fn buffer_navigation(command: BufferCommand, buffers: &mut EditorBuffers, audio: &EditorAudio, console: &mut EditorConsole) {
+    match command {
+        BufferCommand::Next => {
+            audio.play_nav();
+            buffers.next();
+        }
+        BufferCommand::Previous => {
+            audio.play_nav();
+            buffers.previous();
+        }
+        BufferCommand::Alternate => {
+            if console.recent.swap(buffers) {
+                audio.play_nav();
+            } else {
+                console.message("No other buffer to swap to");
+            }
+        }
+        BufferCommand::Close => {
+            audio.play_delete();
+            request_close_buffer(buffers, buffers.current, console);
+        }
+    }
+}
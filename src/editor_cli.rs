@@ -0,0 +1,234 @@
+// Command line module, the arguments Muse is started with.
+//
+// muse [options] [file[:line[:column]] | directory]...
+//
+// Every file is opened in its own buffer, the last one is shown.
+// A file that does not exist yet is created by the first ':w',
+// a directory becomes the working directory of the directives.
+
+use std::path::{Path, PathBuf};
+
+use crate::editor_config::CONFIG_FILE;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+const FONT_SIZE_MIN: u16 = 6;
+const FONT_SIZE_MAX: u16 = 200;
+
+pub const USAGE: &str = "\
+Usage: muse [options] [file[:line[:column]] | directory]...
+
+Open the files, at a line and column if given, in buffers of their own.
+A directory becomes the working directory of the console.
+
+Options:
+    --config <path>    Read the settings from 'path' instead of cal.conf
+    --palette <name>   Draw with the palette 'palletes/<name>.pal'
+    --font-size <N>    Text size in pixels (6-200)
+    --windowed         Start in a window rather than fullscreen
+    --readonly         Open the files just to read them (see ':ro')
+    --version          Print the version and exit
+    -h, --help         Print this help and exit
+";
+
+/// A file argument, 'line' and 'column' count from 1
+pub struct FileTarget {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>
+}
+
+pub struct CliOptions {
+    pub files: Vec<FileTarget>,
+    pub directory: Option<PathBuf>,
+    pub config: PathBuf,
+    pub palette: Option<String>,
+    pub font_size: Option<u16>,
+    pub windowed: bool,
+    pub readonly: bool
+}
+
+pub enum CliCommand {
+    Run(CliOptions),
+    Help,
+    Version
+}
+
+/// Split 'file:line:column' or 'file:line', a file whose name ends
+/// in such numbers is taken as it is
+fn parse_target(arg: &str) -> Result<FileTarget, String> {
+    if Path::new(arg).exists() {
+        return Ok(FileTarget { path: PathBuf::from(arg), line: None, column: None });
+    }
+
+    // The fewest numbers that leave an existing file, or all of them for a new one
+    let mut numbers = vec![];
+    let mut path = arg;
+    while numbers.len() < 2 {
+        match path.rsplit_once(':') {
+            Some((rest, number)) if !rest.is_empty() && !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) => {
+                numbers.insert(0, number.parse::<usize>().map_err(|_| format!("'{}' is not a line number", number))?);
+                path = rest;
+            }
+            _ => break
+        }
+
+        if Path::new(path).exists() {
+            break;
+        }
+    }
+
+    if numbers.contains(&0) {
+        return Err(format!("lines and columns count from 1 in '{}'", arg));
+    }
+
+    Ok(FileTarget { path: PathBuf::from(path), line: numbers.first().copied(), column: numbers.get(1).copied() })
+}
+
+/// The value of an option, given as '--name value' or '--name=value'
+fn option_value(name: &str, inline: Option<&str>, rest: &mut impl Iterator<Item = String>) -> Result<String, String> {
+    match inline {
+        Some(value) if !value.is_empty() => Ok(value.to_string()),
+        Some(_) => Err(format!("{} expects a value", name)),
+        None => rest.next().filter(|v| !v.starts_with("--")).ok_or_else(|| format!("{} expects a value", name))
+    }
+}
+
+/// Parse the arguments after the program name
+pub fn parse_args(args: Vec<String>) -> Result<CliCommand, String> {
+    let mut options = CliOptions {
+        files: vec![],
+        directory: None,
+        config: PathBuf::from(CONFIG_FILE),
+        palette: None,
+        font_size: None,
+        windowed: false,
+        readonly: false
+    };
+
+    let mut args = args.into_iter();
+    let mut only_files = false;
+
+    while let Some(arg) = args.next() {
+        if only_files || !arg.starts_with('-') {
+            let target = parse_target(&arg)?;
+            if target.path.is_dir() {
+                options.directory = Some(target.path);
+            } else {
+                options.files.push(target);
+            }
+            continue;
+        }
+
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None)
+        };
+
+        match name {
+            "--" => only_files = true,
+            "--windowed" | "--readonly" | "--version" | "--help" if inline.is_some() => return Err(format!("{} takes no value", name)),
+            "-h" | "--help" => return Ok(CliCommand::Help),
+            "--version" => return Ok(CliCommand::Version),
+            "--windowed" => options.windowed = true,
+            "--readonly" => options.readonly = true,
+            "--config" => options.config = PathBuf::from(option_value(name, inline, &mut args)?),
+            "--palette" => options.palette = Some(option_value(name, inline, &mut args)?),
+            "--font-size" => {
+                let value = option_value(name, inline, &mut args)?;
+                let size = value.parse::<u16>().ok().filter(|s| (FONT_SIZE_MIN..=FONT_SIZE_MAX).contains(s))
+                    .ok_or_else(|| format!("--font-size expects a number from {} to {}, not '{}'", FONT_SIZE_MIN, FONT_SIZE_MAX, value))?;
+                options.font_size = Some(size);
+            }
+            _ => return Err(format!("unknown option '{}'", name))
+        }
+    }
+
+    Ok(CliCommand::Run(options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn options(arguments: &[&str]) -> CliOptions {
+        match parse_args(args(arguments)) {
+            Ok(CliCommand::Run(options)) => options,
+            Ok(_) => panic!("{:?} did not run", arguments),
+            Err(e) => panic!("{:?}: {}", arguments, e)
+        }
+    }
+
+    #[test]
+    fn file_line_and_column() {
+        let target = parse_target("src/missing.rs:12:5").unwrap();
+        assert_eq!(target.path, PathBuf::from("src/missing.rs"));
+        assert_eq!((target.line, target.column), (Some(12), Some(5)));
+
+        let target = parse_target("missing.rs:12").unwrap();
+        assert_eq!((target.line, target.column), (Some(12), None));
+
+        assert!(parse_target("missing.rs:0").is_err());
+    }
+
+    #[test]
+    fn existing_name_ending_in_a_number_is_kept() {
+        let path = env::temp_dir().join(format!("muse-cli-{}-log:5", process::id()));
+        fs::write(&path, "").unwrap();
+
+        let target = parse_target(&path.display().to_string());
+        let with_line = parse_target(&format!("{}:3", path.display()));
+        fs::remove_file(&path).unwrap();
+
+        let target = target.unwrap();
+        assert_eq!(target.path, path);
+        assert_eq!(target.line, None);
+
+        let with_line = with_line.unwrap();
+        assert_eq!(with_line.path, path);
+        assert_eq!(with_line.line, Some(3));
+    }
+
+    #[test]
+    fn options_take_values_inline_or_after() {
+        let parsed = options(&["--palette=dark", "--font-size", "30", "--config=other.conf", "--readonly"]);
+        assert_eq!(parsed.palette.as_deref(), Some("dark"));
+        assert_eq!(parsed.font_size, Some(30));
+        assert_eq!(parsed.config, PathBuf::from("other.conf"));
+        assert!(parsed.readonly && !parsed.windowed);
+
+        assert!(parse_args(args(&["--palette="])).is_err());
+        assert!(parse_args(args(&["--palette", "--windowed"])).is_err());
+        assert!(parse_args(args(&["--windowed=yes"])).is_err());
+        assert!(parse_args(args(&["--unknown"])).is_err());
+        assert!(matches!(parse_args(args(&["-h"])), Ok(CliCommand::Help)));
+        assert!(matches!(parse_args(args(&["--version"])), Ok(CliCommand::Version)));
+    }
+
+    #[test]
+    fn files_after_double_dash() {
+        let parsed = options(&["--", "--windowed", "-notes.txt:4"]);
+        assert!(!parsed.windowed);
+
+        let paths: Vec<&Path> = parsed.files.iter().map(|f| f.path.as_path()).collect();
+        assert_eq!(paths, [Path::new("--windowed"), Path::new("-notes.txt")]);
+        assert_eq!(parsed.files[1].line, Some(4));
+    }
+
+    #[test]
+    fn font_size_is_checked() {
+        for size in ["0", "5", "201", "big", "-3"] {
+            let error = parse_args(args(&["--font-size", size])).err();
+            assert!(error.is_some_and(|e| e.contains("--font-size")), "{} was accepted", size);
+        }
+        assert_eq!(options(&["--font-size=6"]).font_size, Some(6));
+        assert_eq!(options(&["--font-size=200"]).font_size, Some(200));
+    }
+}
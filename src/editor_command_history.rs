@@ -0,0 +1,125 @@
+// Console history module, every executed directive is kept
+// and saved in the state directory so it survives restarts.
+//
+// Up/Down walk through the entries starting with what was typed,
+// LCTRL + R searches backwards for entries containing the typed text
+// ('console.up', 'console.down' and 'console.search_history', see editor_keymap.rs).
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::editor_config::{EditorConfig, state_dir};
+
+const HISTORY_FILE: &str = "history";
+const HISTORY_DEFAULT_SIZE: usize = 500;
+
+pub struct EditorCommandHistory {
+    pub entries: Vec<String>, // Oldest first
+    capacity: usize,
+    path: PathBuf,
+    index: Option<usize>,     // Entry shown while walking with Up/Down
+    prefix: String            // Input typed before walking
+}
+
+impl EditorCommandHistory {
+    /// Load the saved history, capped by 'history_size' in cal.conf
+    pub fn load(config: &EditorConfig) -> EditorCommandHistory {
+        let path = state_dir().join(HISTORY_FILE);
+        let capacity = config.get_usize("history_size", HISTORY_DEFAULT_SIZE);
+
+        let mut entries: Vec<String> = fs::read_to_string(&path)
+            .map(|content| content.lines().filter(|l| !l.is_empty()).map(|l| l.to_string()).collect())
+            .unwrap_or_default();
+
+        entries.dedup();
+        if entries.len() > capacity {
+            entries.drain(..entries.len() - capacity);
+        }
+
+        EditorCommandHistory { entries, capacity, path, index: None, prefix: String::new() }
+    }
+
+    fn save(&self) {
+        if let Some(dir) = self.path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+
+        let mut content = self.entries.join("\n");
+        content.push('\n');
+        let _ = fs::write(&self.path, content);
+    }
+
+    /// Remember an executed directive, a repeat of the last one is not kept twice
+    pub fn push(&mut self, command: &str) {
+        self.reset();
+
+        let command = command.trim();
+        if command.is_empty() || self.entries.last().is_some_and(|l| l == command) {
+            return;
+        }
+
+        self.entries.push(command.to_string());
+        if self.entries.len() > self.capacity {
+            self.entries.drain(..self.entries.len() - self.capacity);
+        }
+
+        self.save();
+    }
+
+    /// Take the entries of a restored session instead
+    pub fn replace(&mut self, mut entries: Vec<String>) {
+        self.reset();
+
+        if entries.len() > self.capacity {
+            entries.drain(..entries.len() - self.capacity);
+        }
+        self.entries = entries;
+        self.save();
+    }
+
+    /// Stop walking through the history
+    pub fn reset(&mut self) {
+        self.index = None;
+    }
+
+    /// Up/Down are walking through the entries
+    pub fn walking(&self) -> bool {
+        self.index.is_some()
+    }
+
+    /// Previous entry starting with the input typed before walking
+    pub fn older(&mut self, input: &str) -> Option<String> {
+        let end = match self.index {
+            Some(index) => index,
+            None => {
+                self.prefix = input.to_string();
+                self.entries.len()
+            }
+        };
+
+        let index = self.entries[..end].iter().rposition(|e| e.starts_with(&self.prefix))?;
+        self.index = Some(index);
+        Some(self.entries[index].clone())
+    }
+
+    /// Next entry, back to the typed input past the newest one
+    pub fn newer(&mut self) -> Option<String> {
+        let start = self.index? + 1;
+
+        match self.entries[start..].iter().position(|e| e.starts_with(&self.prefix)) {
+            Some(offset) => {
+                self.index = Some(start + offset);
+                Some(self.entries[start + offset].clone())
+            }
+            None => {
+                self.index = None;
+                Some(self.prefix.clone())
+            }
+        }
+    }
+
+    /// Index of the newest entry before 'before' containing 'query'
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        self.entries[..before.min(self.entries.len())].iter().rposition(|e| e.contains(query))
+    }
+}
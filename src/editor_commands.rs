@@ -0,0 +1,689 @@
+// Command registry module, every action of the editor under a name
+// like 'cursor.word_left', 'edit.newline', 'file.save' or 'view.toggle_console'.
+//
+// COMMANDS is the one list of them: the keys pressed (see editor_keymap.rs),
+// the directives typed in the console (see editor_directives.rs) and the macros
+// all become a call of one of these commands, queued on the console and run
+// once per frame with the whole editor at hand. The manuals and ':ekb' describe
+// the commands from the same list. The name is what cal.conf and the console
+// call a command by, its Command value what it does: run_command matches on
+// those, so a command without a handler does not build.
+//
+// A command takes at most one argument, the rest of the line after its name,
+// read into a typed value before the command runs: paths are resolved against
+// the working directory, lines against the cursor of the current file,
+// encodings and line endings checked by name.
+//
+// Macros are lists of commands in cal.conf, separated by ';', each one
+// a command name or a directive with its argument:
+//      macro.top = cursor.file_start; edit.newline; cursor.up
+//      macro.save_all = :w; buffer.next; :w
+// ':m <n>' runs the macro 'n', ':m' lists them. A step whose argument
+// cannot be read stops the rest of the macro.
+//
+// ':x <command> <args>' runs any command by name.
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use macroquad::prelude::*;
+
+use crate::editor_audio::EditorAudio;
+use crate::editor_buffers::{EditorBuffers, buffer_navigation};
+use crate::editor_completion::{find_font, resolve_path};
+use crate::editor_config::EditorConfig;
+use crate::editor_console::{EditorConsole, console_navigation};
+use crate::editor_directives::*;
+use crate::editor_encoding::{ENCODINGS, Encoding, LINE_ENDINGS, LineEnding};
+use crate::editor_file::EditorFile;
+use crate::editor_jumps::jump_list_navigation;
+use crate::editor_panes::{EditorPanes, pane_navigation};
+use crate::editor_sidebar::{EditorSidebar, sidebar_navigation, toggle_sidebar};
+use crate::editor_text::record_special_keys;
+
+const MAX_COMMANDS_PER_FRAME: usize = 1000; // A macro running itself stops there
+
+/// What the argument of a command is, for reading and completing it
+#[derive(Clone, Copy, PartialEq)]
+pub enum ArgKind {
+    File,      // Files and the directories leading to them
+    Directory,
+    Line,
+    Encoding,
+    LineEnding,
+    Session,
+    Command,
+    Palette,
+    Font,
+    Other
+}
+
+/// Manual a command is listed in, ':egman' lists them all
+#[derive(Clone, Copy, PartialEq)]
+pub enum Manual {
+    File,      // ':efman'
+    Directory, // ':edman'
+    Config,    // ':ecman'
+    Other      // ':eoman'
+}
+
+/// What a command does, run_command has a handler for each
+#[derive(Clone, Copy, PartialEq)]
+pub enum Command {
+    Cursor(CursorMove),
+    Select(CursorMove), // The same moves, selecting the text passed over
+    SelectAll,
+    GotoLine,
+    Find,
+    Edit(EditCommand),
+    FileOpen,
+    FileSave,
+    FileInfo,
+    FileEncoding,
+    FileLineEndings,
+    FileToggleReadonly,
+    FileCreate,
+    FileRemove,
+    FileRename,
+    Buffer(BufferCommand),
+    DirChange,
+    DirOpen,
+    DirCreate,
+    DirRemove,
+    DirRename,
+    DirList,
+    DirTree,
+    DirGrep,
+    SessionSave,
+    SessionOpen,
+    HelpKeys,
+    MacroRun,
+    RunByName,
+    Palette,
+    Font,
+    Manual(&'static [Manual]),
+    ToggleConsole,
+    ToggleSidebar,
+    Jump(JumpCommand),
+    Pane(PaneCommand),
+    Console(ConsoleCommand),
+    Sidebar(SidebarCommand)
+}
+
+/// Cursor moves, see editor_cursor.rs
+#[derive(Clone, Copy, PartialEq)]
+pub enum CursorMove {
+    Up,
+    Down,
+    Left,
+    Right,
+    WordLeft,
+    WordRight,
+    FileStart,
+    FileEnd
+}
+
+/// Edits at the cursor, see editor_text.rs
+#[derive(Clone, Copy, PartialEq)]
+pub enum EditCommand {
+    Backspace,
+    Indent,
+    Newline,
+    Undo,
+    Redo
+}
+
+/// See editor_buffers.rs
+#[derive(Clone, Copy, PartialEq)]
+pub enum BufferCommand {
+    Next,
+    Previous,
+    Close,
+    Alternate
+}
+
+/// See editor_jumps.rs
+#[derive(Clone, Copy, PartialEq)]
+pub enum JumpCommand {
+    Back,
+    Forward
+}
+
+/// See editor_panes.rs
+#[derive(Clone, Copy, PartialEq)]
+pub enum PaneCommand {
+    Split(bool), // Side by side if true
+    Close,
+    Focus(Direction),
+    Resize(Direction)
+}
+
+/// Keys of the open console, see editor_console.rs
+#[derive(Clone, Copy, PartialEq)]
+pub enum ConsoleCommand {
+    Accept,
+    Cancel,
+    Up,
+    Down,
+    Complete,
+    SearchHistory,
+    Expand,
+    Collapse
+}
+
+/// Keys of the sidebar while it has the keyboard, see editor_sidebar.rs
+#[derive(Clone, Copy, PartialEq)]
+pub enum SidebarCommand {
+    Up,
+    Down,
+    Expand,
+    Collapse,
+    Open,
+    Leave
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down
+}
+
+impl Direction {
+    /// The step towards the direction, x right and y down
+    pub fn delta(self) -> (f32, f32) {
+        match self {
+            Direction::Left => (-1.0, 0.0),
+            Direction::Right => (1.0, 0.0),
+            Direction::Up => (0.0, -1.0),
+            Direction::Down => (0.0, 1.0)
+        }
+    }
+}
+
+pub struct ArgSpec {
+    pub name: &'static str, // As shown in the syntax, '<f>'
+    pub what: &'static str, // As said when it is missing
+    pub kind: ArgKind,
+    pub required: bool
+}
+
+pub struct CommandInfo {
+    pub name: &'static str,
+    pub id: Command,
+    pub directive: Option<&'static str>,
+    pub keys: &'static str, // Default keys, see editor_keymap.rs
+    pub arg: Option<ArgSpec>,
+    pub manual: Manual,
+    pub description: &'static str
+}
+
+/// The argument of a command once read
+pub enum CommandArg {
+    Text(String),
+    Line(usize, Option<usize>), // 0-indexed line and column
+    Path(PathBuf),
+    Encoding(Encoding),
+    LineEnding(LineEnding)
+}
+
+/// A command waiting to run, with its argument as typed
+#[derive(Clone)]
+pub struct CommandCall {
+    pub info: &'static CommandInfo,
+    pub arg: Option<String>
+}
+
+/// The calls waiting to run and the macros of cal.conf
+pub struct EditorCommands {
+    pub queue: VecDeque<CommandCall>,
+    pub macros: Vec<(String, Vec<CommandCall>)>,
+    pub problems: Vec<String> // Macro steps that are not commands
+}
+
+/// The editor as the commands see it
+pub struct CommandContext<'a> {
+    pub console: &'a mut EditorConsole,
+    pub buffers: &'a mut EditorBuffers,
+    pub panes: &'a mut EditorPanes,
+    pub sidebar: &'a mut EditorSidebar,
+    pub audio: &'a EditorAudio,
+    pub area: Rect // Where the panes are drawn
+}
+
+const fn arg(name: &'static str, what: &'static str, kind: ArgKind, required: bool) -> Option<ArgSpec> {
+    Some(ArgSpec { name, what, kind, required })
+}
+
+const fn command(name: &'static str, id: Command, directive: Option<&'static str>, keys: &'static str, arg: Option<ArgSpec>,
+    manual: Manual, description: &'static str) -> CommandInfo {
+    CommandInfo { name, id, directive, keys, arg, manual, description }
+}
+
+/// Every command of the editor
+pub const COMMANDS: [CommandInfo; 84] = [
+    // File manual
+    command("cursor.up", Command::Cursor(CursorMove::Up), None, "up", None, Manual::File, "Move the cursor a line up"),
+    command("cursor.down", Command::Cursor(CursorMove::Down), None, "down", None, Manual::File, "Move the cursor a line down"),
+    command("cursor.left", Command::Cursor(CursorMove::Left), None, "left", None, Manual::File, "Move the cursor a character left"),
+    command("cursor.right", Command::Cursor(CursorMove::Right), None, "right", None, Manual::File, "Move the cursor a character right"),
+    command("cursor.word_left", Command::Cursor(CursorMove::WordLeft), None, "ctrl+left", None, Manual::File, "Move the cursor to the previous word"),
+    command("cursor.word_right", Command::Cursor(CursorMove::WordRight), None, "ctrl+right", None, Manual::File, "Move the cursor to the next word"),
+    command("cursor.file_start", Command::Cursor(CursorMove::FileStart), None, "ctrl+home", None, Manual::File, "Go to the start of the file"),
+    command("cursor.file_end", Command::Cursor(CursorMove::FileEnd), None, "ctrl+end", None, Manual::File, "Go to the end of the file"),
+    command("cursor.goto_line", Command::GotoLine, Some(":l"), "", arg("N | N:C | +N/-N | $", "a line", ArgKind::Line, true), Manual::File,
+        "Go to line N, column C, N lines down/up or the last line"),
+    command("cursor.find", Command::Find, Some(":f"), "", arg("f", "some text", ArgKind::Other, true), Manual::File,
+        "Go to the first line containing 'f'"),
+    command("select.up", Command::Select(CursorMove::Up), None, "shift+up", None, Manual::File, "Select up to the line above"),
+    command("select.down", Command::Select(CursorMove::Down), None, "shift+down", None, Manual::File, "Select down to the line below"),
+    command("select.left", Command::Select(CursorMove::Left), None, "shift+left", None, Manual::File, "Select the character to the left"),
+    command("select.right", Command::Select(CursorMove::Right), None, "shift+right", None, Manual::File, "Select the character to the right"),
+    command("select.word_left", Command::Select(CursorMove::WordLeft), None, "ctrl+shift+left", None, Manual::File, "Select to the previous word"),
+    command("select.word_right", Command::Select(CursorMove::WordRight), None, "ctrl+shift+right", None, Manual::File, "Select to the next word"),
+    command("select.all", Command::SelectAll, None, "ctrl+a", None, Manual::File, "Select the whole file"),
+    command("edit.backspace", Command::Edit(EditCommand::Backspace), None, "backspace", None, Manual::File, "Delete the character or indentation before the cursor"),
+    command("edit.indent", Command::Edit(EditCommand::Indent), None, "tab", None, Manual::File, "Insert an indentation"),
+    command("edit.newline", Command::Edit(EditCommand::Newline), None, "enter", None, Manual::File, "Break the line at the cursor"),
+    command("edit.undo", Command::Edit(EditCommand::Undo), None, "ctrl+z", None, Manual::File, "Undo the last edit"),
+    command("edit.redo", Command::Edit(EditCommand::Redo), None, "ctrl+y", None, Manual::File, "Redo the last undone edit"),
+    command("file.open", Command::FileOpen, None, "", arg("f", "a file name", ArgKind::Other, true), Manual::File,
+        "Switch to the open file 'f' or open it, what the console does with input without ':'"),
+    command("file.save", Command::FileSave, Some(":w"), "", arg("f", "a file name", ArgKind::File, false), Manual::File,
+        "Write the current file, to 'f' and keep editing it there if given"),
+    command("file.info", Command::FileInfo, Some(":i"), "", None, Manual::File, "Current file info display"),
+    command("file.encoding", Command::FileEncoding, Some(":en"), "", arg("e", "an encoding", ArgKind::Encoding, true), Manual::File,
+        "Convert the current file to the encoding 'e'"),
+    command("file.line_endings", Command::FileLineEndings, Some(":le"), "", arg("lf|crlf|cr", "a line ending", ArgKind::LineEnding, true), Manual::File,
+        "Convert the line endings of the current file"),
+    command("file.toggle_readonly", Command::FileToggleReadonly, Some(":ro"), "", None, Manual::File, "Read-only on/off switch of the current file"),
+    command("file.create", Command::FileCreate, Some(":c"), "", arg("f", "a file name", ArgKind::File, true), Manual::File,
+        "Create a new file with name 'f'"),
+    command("file.remove", Command::FileRemove, Some(":r"), "", arg("f", "a file name", ArgKind::File, true), Manual::File,
+        "Move the file 'f' to the trash"),
+    command("file.rename", Command::FileRename, Some(":b"), "", arg("f", "a file name", ArgKind::File, true), Manual::File,
+        "Change the name of the current open file to 'f'"),
+    command("buffer.next", Command::Buffer(BufferCommand::Next), None, "ctrl+tab", None, Manual::File, "Switch to the next buffer"),
+    command("buffer.previous", Command::Buffer(BufferCommand::Previous), None, "ctrl+shift+tab", None, Manual::File, "Switch to the previous buffer"),
+    command("buffer.close", Command::Buffer(BufferCommand::Close), None, "ctrl+w", None, Manual::File, "Close the current buffer, asks first if it has unsaved changes"),
+    command("buffer.alternate", Command::Buffer(BufferCommand::Alternate), None, "ctrl+6", None, Manual::File, "Swap to the buffer shown before the current one"),
+    // Directory manual
+    command("dir.change", Command::DirChange, Some(":cd"), "", arg("d", "a directory", ArgKind::Directory, false), Manual::Directory,
+        "Change directory, the home directory without 'd'"),
+    command("dir.open", Command::DirOpen, Some(":od"), "", arg("d", "a directory", ArgKind::Directory, false), Manual::Directory,
+        "Open the directory in the file explorer, the working directory without 'd'"),
+    command("dir.create", Command::DirCreate, Some(":md"), "", arg("f", "a directory name", ArgKind::Directory, true), Manual::Directory,
+        "Create a new directory with name 'f'"),
+    command("dir.remove", Command::DirRemove, Some(":rd"), "", arg("f", "a directory name", ArgKind::Directory, true), Manual::Directory,
+        "Move a directory with all its contents to the trash"),
+    command("dir.rename", Command::DirRename, Some(":bd"), "", arg("f", "a directory name", ArgKind::Other, true), Manual::Directory,
+        "Change the name of the working directory to 'f'"),
+    command("dir.list", Command::DirList, Some(":ld"), "", None, Manual::Directory, "List all files in the directory, with their sizes"),
+    command("dir.tree", Command::DirTree, Some(":td"), "", None, Manual::Directory, "Show the directory recursively"),
+    command("dir.grep", Command::DirGrep, Some(":g"), "", arg("re", "a regex", ArgKind::Other, true), Manual::Directory,
+        "Search the files under the directory for the regex 're'"),
+    command("session.save", Command::SessionSave, Some(":ss"), "", arg("n", "a session name", ArgKind::Session, false), Manual::Directory,
+        "Save the open files, cursors and splits as the session 'n'"),
+    command("session.open", Command::SessionOpen, Some(":os"), "", arg("n", "a session name", ArgKind::Session, false), Manual::Directory,
+        "Open the session 'n', list them without 'n'"),
+    // Config manual
+    command("help.keys", Command::HelpKeys, Some(":ekb"), "", arg("t", "some text", ArgKind::Other, false), Manual::Config,
+        "List the key bindings containing 't' and their conflicts"),
+    command("macro.run", Command::MacroRun, Some(":m"), "", arg("n", "a macro name", ArgKind::Other, false), Manual::Config,
+        "Run the macro 'n' of cal.conf, list them without 'n'"),
+    command("command.run", Command::RunByName, Some(":x"), "", arg("command args", "a command", ArgKind::Command, true), Manual::Config,
+        "Run any command by name"),
+    command("config.palette", Command::Palette, Some(":epa"), "", arg("p", "a palette name", ArgKind::Palette, true), Manual::Config,
+        "Change to the palette of name 'p', kept in cal.conf"),
+    command("config.font", Command::Font, Some(":efn"), "", arg("p", "a font name", ArgKind::Font, true), Manual::Config,
+        "Change to the font of name 'p', kept in cal.conf"),
+    // Other manual
+    command("view.toggle_console", Command::ToggleConsole, None, "ctrl+`", None, Manual::Other, "Switch between the console and the text"),
+    command("view.toggle_sidebar", Command::ToggleSidebar, None, "ctrl+e", None, Manual::Other, "Show and focus the sidebar, or hide it"),
+    command("jump.back", Command::Jump(JumpCommand::Back), None, "alt+left", None, Manual::Other, "Go back to where the cursor jumped from"),
+    command("jump.forward", Command::Jump(JumpCommand::Forward), None, "alt+right", None, Manual::Other, "Go forward again through the jumps"),
+    command("pane.split_vertical", Command::Pane(PaneCommand::Split(true)), None, "ctrl+alt+v", None, Manual::Other, "Split the pane side by side"),
+    command("pane.split_horizontal", Command::Pane(PaneCommand::Split(false)), None, "ctrl+alt+h", None, Manual::Other, "Split the pane one above the other"),
+    command("pane.close", Command::Pane(PaneCommand::Close), None, "ctrl+alt+w", None, Manual::Other, "Close the focused pane"),
+    command("pane.focus_left", Command::Pane(PaneCommand::Focus(Direction::Left)), None, "ctrl+alt+left", None, Manual::Other, "Focus the pane to the left"),
+    command("pane.focus_right", Command::Pane(PaneCommand::Focus(Direction::Right)), None, "ctrl+alt+right", None, Manual::Other, "Focus the pane to the right"),
+    command("pane.focus_up", Command::Pane(PaneCommand::Focus(Direction::Up)), None, "ctrl+alt+up", None, Manual::Other, "Focus the pane above"),
+    command("pane.focus_down", Command::Pane(PaneCommand::Focus(Direction::Down)), None, "ctrl+alt+down", None, Manual::Other, "Focus the pane below"),
+    command("pane.resize_left", Command::Pane(PaneCommand::Resize(Direction::Left)), None, "ctrl+alt+shift+left", None, Manual::Other, "Move the split of the pane left"),
+    command("pane.resize_right", Command::Pane(PaneCommand::Resize(Direction::Right)), None, "ctrl+alt+shift+right", None, Manual::Other, "Move the split of the pane right"),
+    command("pane.resize_up", Command::Pane(PaneCommand::Resize(Direction::Up)), None, "ctrl+alt+shift+up", None, Manual::Other, "Move the split of the pane up"),
+    command("pane.resize_down", Command::Pane(PaneCommand::Resize(Direction::Down)), None, "ctrl+alt+shift+down", None, Manual::Other, "Move the split of the pane down"),
+    command("console.accept", Command::Console(ConsoleCommand::Accept), None, "enter", None, Manual::Other,
+        "Run the input of the console, or open the selected entry when it is empty"),
+    command("console.cancel", Command::Console(ConsoleCommand::Cancel), None, "escape", None, Manual::Other,
+        "Close the console, cancelling a question or a history search"),
+    command("console.up", Command::Console(ConsoleCommand::Up), None, "up", None, Manual::Other,
+        "Select the entry above, or the older input of the history"),
+    command("console.down", Command::Console(ConsoleCommand::Down), None, "down", None, Manual::Other,
+        "Select the entry below, or the newer input of the history"),
+    command("console.complete", Command::Console(ConsoleCommand::Complete), None, "tab", None, Manual::Other,
+        "Complete the input with the selected candidate"),
+    command("console.search_history", Command::Console(ConsoleCommand::SearchHistory), None, "ctrl+r", None, Manual::Other,
+        "Search the history backwards, again for an older match"),
+    command("console.expand", Command::Console(ConsoleCommand::Expand), None, "right", None, Manual::Other,
+        "Expand the selected directory of ':td'"),
+    command("console.collapse", Command::Console(ConsoleCommand::Collapse), None, "left", None, Manual::Other,
+        "Collapse the selected directory of ':td', or select its parent"),
+    command("sidebar.up", Command::Sidebar(SidebarCommand::Up), None, "up", None, Manual::Other, "Select the entry above"),
+    command("sidebar.down", Command::Sidebar(SidebarCommand::Down), None, "down", None, Manual::Other, "Select the entry below"),
+    command("sidebar.expand", Command::Sidebar(SidebarCommand::Expand), None, "right", None, Manual::Other,
+        "Expand the selected directory"),
+    command("sidebar.collapse", Command::Sidebar(SidebarCommand::Collapse), None, "left", None, Manual::Other,
+        "Collapse the selected directory, or select its parent"),
+    command("sidebar.open", Command::Sidebar(SidebarCommand::Open), None, "enter", None, Manual::Other,
+        "Open the selected file, or expand/collapse the selected directory"),
+    command("sidebar.leave", Command::Sidebar(SidebarCommand::Leave), None, "escape", None, Manual::Other,
+        "Give the keyboard back to the text"),
+    command("help.manual", Command::Manual(&[Manual::File, Manual::Directory, Manual::Config, Manual::Other]), Some(":egman"), "", None, Manual::Other, "Editor general manual, every command"),
+    command("help.file_manual", Command::Manual(&[Manual::File]), Some(":efman"), "", None, Manual::Other, "Editor file manual"),
+    command("help.directory_manual", Command::Manual(&[Manual::Directory]), Some(":edman"), "", None, Manual::Other, "Editor directory manual"),
+    command("help.config_manual", Command::Manual(&[Manual::Config]), Some(":ecman"), "", None, Manual::Other, "Editor config manual"),
+    command("help.other_manual", Command::Manual(&[Manual::Other]), Some(":eoman"), "", None, Manual::Other, "Editor others manual")
+];
+
+pub fn command_info(name: &str) -> Option<&'static CommandInfo> {
+    COMMANDS.iter().find(|c| c.name == name)
+}
+
+pub fn directive_command(directive: &str) -> Option<&'static CommandInfo> {
+    COMMANDS.iter().find(|c| c.directive == Some(directive))
+}
+
+impl CommandInfo {
+    /// The directive of the command, or its name if it has none
+    pub fn shown(&self) -> &'static str {
+        self.directive.unwrap_or(self.name)
+    }
+
+    /// Syntax of the command as typed, ':l <N>', 'file.open <f>'
+    pub fn usage(&self, name: &str) -> String {
+        match &self.arg {
+            Some(arg) => format!("{} <{}>", name, arg.name),
+            None => name.to_string()
+        }
+    }
+
+    /// Read the typed argument into its value, None if there is none,
+    /// lines are resolved against the cursor of the current file
+    fn read_arg(&self, typed: Option<&str>, console: &EditorConsole, file: &EditorFile) -> Result<Option<CommandArg>, String> {
+        let typed = typed.map(str::trim).filter(|t| !t.is_empty());
+
+        let (spec, typed) = match (&self.arg, typed) {
+            (None, None) => return Ok(None),
+            (None, Some(_)) => return Err(format!("{} takes no argument, '{}'", self.shown(), self.shown())),
+            (Some(spec), None) if spec.required =>
+                return Err(format!("{} expects {}, '{}'", self.shown(), spec.what, self.usage(self.shown()))),
+            (Some(_), None) => return Ok(None),
+            (Some(spec), Some(typed)) => (spec, typed)
+        };
+
+        let value = match spec.kind {
+            ArgKind::File | ArgKind::Directory => CommandArg::Path(resolve_path(&console.cwd, typed)),
+            ArgKind::Line => match parse_goto_target(typed, file.cursor.xy.1, file.text.len().max(1)) {
+                Ok((line, column)) => CommandArg::Line(line, column),
+                Err(e) if file.loading.is_some() => return Err(format!("{}, the file is still loading", e)),
+                Err(e) => return Err(e)
+            },
+            ArgKind::Font => match find_font(typed) {
+                Some(path) => CommandArg::Path(path),
+                None => return Err(format!("No font named '{}', the fonts of 'assets/font/' and the system complete", typed))
+            },
+            ArgKind::Encoding => match Encoding::from_name(typed) {
+                Some(encoding) => CommandArg::Encoding(encoding),
+                None => {
+                    let names: Vec<&str> = ENCODINGS.iter().map(|e| e.name()).collect();
+                    return Err(format!("Usage: {} <{}>", self.shown(), names.join("|")));
+                }
+            },
+            ArgKind::LineEnding => match LineEnding::from_name(typed) {
+                Some(line_ending) => CommandArg::LineEnding(line_ending),
+                None => {
+                    let names: Vec<&str> = LINE_ENDINGS.iter().map(|l| l.name()).collect();
+                    return Err(format!("Usage: {} <{}>", self.shown(), names.join("|")));
+                }
+            },
+            _ => CommandArg::Text(typed.to_string())
+        };
+        Ok(Some(value))
+    }
+}
+
+/// Read a call written as 'name <arg>' or ':directive <arg>'
+pub fn parse_call(line: &str) -> Result<CommandCall, String> {
+    let line = line.trim();
+    let (name, arg) = match line.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, Some(arg.trim().to_string())),
+        None => (line, None)
+    };
+
+    let info = if name.starts_with(':') { directive_command(name) } else { command_info(name) };
+    match info {
+        Some(info) => Ok(CommandCall { info, arg }),
+        None if name.starts_with(':') => Err(format!("Unknown directive {}", name)),
+        None => Err(format!("No command '{}', ':egman' lists them", name))
+    }
+}
+
+impl EditorCommands {
+    /// The 'macro.<name>' lines of cal.conf, steps that are not commands are left out
+    pub fn load(config: &EditorConfig) -> EditorCommands {
+        let mut macros = vec![];
+        let mut problems = vec![];
+
+        for (name, value) in config.with_prefix("macro.") {
+            let mut steps = vec![];
+            for step in value.split(';').filter(|s| !s.trim().is_empty()) {
+                match parse_call(step) {
+                    Ok(call) => steps.push(call),
+                    Err(e) => problems.push(format!("macro.{}: {}", name, e))
+                }
+            }
+            macros.push((name.to_string(), steps));
+        }
+
+        EditorCommands { queue: VecDeque::new(), macros, problems }
+    }
+
+    /// Queue 'name', a command of the registry, to run this frame
+    pub fn push(&mut self, name: &str, arg: Option<&str>) {
+        if let Some(info) = command_info(name) {
+            self.queue.push_back(CommandCall { info, arg: arg.map(|a| a.to_string()) });
+        }
+    }
+}
+
+/// Run the calls queued this frame, the keys pressed and directives typed,
+/// a call whose argument cannot be read drops the rest of the queue
+pub fn run_queued_commands(ctx: &mut CommandContext) {
+    let mut ran = 0;
+
+    while let Some(call) = ctx.console.commands.queue.pop_front() {
+        ran += 1;
+        if ran > MAX_COMMANDS_PER_FRAME {
+            ctx.console.commands.queue.clear();
+            ctx.console.message(&format!("Stopped after {} commands, does a macro run itself?", MAX_COMMANDS_PER_FRAME));
+            return;
+        }
+
+        match call.info.read_arg(call.arg.as_deref(), ctx.console, ctx.buffers.current()) {
+            Ok(arg) => run_command(call.info, arg, ctx),
+            Err(e) => {
+                ctx.console.commands.queue.clear();
+                ctx.console.message(&e);
+            }
+        }
+    }
+}
+
+/// Run one command with its argument read
+fn run_command(info: &CommandInfo, arg: Option<CommandArg>, ctx: &mut CommandContext) {
+    let CommandContext { console, buffers, panes, sidebar, audio, area } = ctx;
+    let jumps = &mut panes.focused_mut().jumps;
+
+    let text = match &arg {
+        Some(CommandArg::Text(text)) => Some(text.as_str()),
+        _ => None
+    };
+    let path = match &arg {
+        Some(CommandArg::Path(path)) => Some(path.clone()),
+        _ => None
+    };
+
+    match info.id {
+        Command::GotoLine => if let Some(CommandArg::Line(line, column)) = arg {
+            directive_goto_line(console, buffers.current_mut(), jumps, (line, column))
+        },
+        Command::Find => directive_find(console, buffers.current_mut(), jumps, text.unwrap_or_default()),
+        Command::FileOpen => switch_to_file(console, buffers, jumps, text.unwrap_or_default()),
+        Command::FileSave => directive_write(console, buffers, path),
+        Command::FileInfo => directive_file_info(console, buffers.current()),
+        Command::FileEncoding => if let Some(CommandArg::Encoding(encoding)) = &arg {
+            directive_convert_encoding(console, buffers.current_mut(), *encoding)
+        },
+        Command::FileLineEndings => if let Some(CommandArg::LineEnding(line_ending)) = &arg {
+            directive_convert_line_endings(console, buffers.current_mut(), *line_ending)
+        },
+        Command::FileToggleReadonly => directive_toggle_readonly(console, buffers.current_mut()),
+        Command::FileCreate => if let Some(path) = path {
+            directive_create_file(console, buffers, jumps, path)
+        },
+        Command::FileRemove => if let Some(path) = path {
+            directive_remove_file(console, buffers, path)
+        },
+        Command::FileRename => if let Some(path) = path {
+            directive_rename_file(console, buffers, path)
+        },
+        Command::DirChange => directive_change_directory(console, path),
+        Command::DirOpen => directive_open_directory(console, path),
+        Command::DirCreate => if let Some(path) = path {
+            directive_make_directory(console, path)
+        },
+        Command::DirRemove => if let Some(path) = path {
+            directive_remove_directory(console, buffers, path)
+        },
+        Command::DirRename => directive_rename_directory(console, buffers, text.unwrap_or_default()),
+        Command::DirList => directive_list_directory(console),
+        Command::DirTree => directive_tree_directory(console),
+        Command::DirGrep => directive_grep(console, text.unwrap_or_default()),
+        Command::SessionSave => directive_save_session(console, text),
+        Command::SessionOpen => directive_open_session(console, text),
+        Command::HelpKeys => directive_key_bindings(console, text),
+        Command::MacroRun => run_macro(console, text),
+        Command::Palette => directive_palette(console, text.unwrap_or_default()),
+        Command::Font => if let Some(path) = path {
+            directive_font(console, path)
+        },
+        Command::RunByName => match parse_call(text.unwrap_or_default()) {
+            Ok(call) => console.commands.queue.push_front(call),
+            Err(e) => console.message(&e)
+        },
+        Command::Manual(manuals) => show_manual(console, manuals),
+        Command::ToggleConsole => console.toggle(),
+        Command::ToggleSidebar => toggle_sidebar(sidebar, buffers, audio, console),
+        Command::Cursor(_) | Command::Select(_) | Command::SelectAll | Command::Edit(_) => {
+            let file = buffers.current_mut();
+            if file.text.is_empty() {
+                file.text.push(String::new());
+            }
+            record_special_keys(info.id, file, jumps, audio, console);
+        }
+        Command::Buffer(command) => buffer_navigation(command, buffers, audio, console),
+        Command::Jump(command) => jump_list_navigation(command, jumps, buffers, audio, console),
+        Command::Pane(command) => pane_navigation(command, panes, buffers, *area, audio),
+        Command::Console(command) => console_navigation(command, console, buffers, jumps, audio),
+        Command::Sidebar(command) => sidebar_navigation(command, sidebar, buffers, jumps, audio, console)
+    }
+}
+
+/// :m, :m <n>, the steps go before the rest of the queue
+fn run_macro(console: &mut EditorConsole, name: Option<&str>) {
+    let name = match name {
+        Some(name) => name,
+        None => {
+            if console.commands.macros.is_empty() && console.commands.problems.is_empty() {
+                console.message("No macros, 'macro.<n> = <command>; <command>' in cal.conf adds one");
+                return;
+            }
+
+            let mut lines: Vec<String> = console.commands.macros.iter()
+                .map(|(name, steps)| format!("{}  {} step{}", name, steps.len(), if steps.len() == 1 { "" } else { "s" }))
+                .collect();
+            if !console.commands.problems.is_empty() {
+                lines.push(String::from("Problems:"));
+                lines.extend(console.commands.problems.iter().cloned());
+            }
+
+            console.message("Macros of cal.conf, ':m <n>' runs one:");
+            console.output.extend(lines);
+            return;
+        }
+    };
+
+    let steps = match console.commands.macros.iter().find(|(n, _)| n == name) {
+        Some((_, steps)) => steps.clone(),
+        None => {
+            console.message(&format!("No macro '{}', ':m' lists them", name));
+            return;
+        }
+    };
+
+    for step in steps.into_iter().rev() {
+        console.commands.queue.push_front(step);
+    }
+}
+
+/// One line of a manual, 'name <arg>  :directive  keys : description'
+fn manual_line(console: &EditorConsole, info: &CommandInfo) -> String {
+    let mut line = format!("  {}", info.usage(info.name));
+    if let Some(directive) = info.directive {
+        line.push_str(&format!("  {}", directive));
+    }
+
+    let keys = console.keymap.keys_of(info.name);
+    if !keys.is_empty() {
+        line.push_str(&format!("  {}", keys.join(", ")));
+    }
+
+    format!("{} : {}", line, info.description)
+}
+
+/// :egman, :efman, :edman, :ecman and :eoman
+fn show_manual(console: &mut EditorConsole, manuals: &[Manual]) {
+    let mut lines = vec![];
+
+    for manual in manuals {
+        let title = match manual {
+            Manual::File => "File",
+            Manual::Directory => "Directory",
+            Manual::Config => "Config, 'key.<command>' and 'macro.<n>' in cal.conf",
+            Manual::Other => "Other"
+        };
+        lines.push(format!("{}:", title));
+        lines.extend(COMMANDS.iter().filter(|c| c.manual == *manual).map(|c| manual_line(console, c)));
+    }
+
+    console.message("Commands, ':x <command>' runs any of them:");
+    console.output.extend(lines);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_commands_are_unique() {
+        for (index, info) in COMMANDS.iter().enumerate() {
+            for other in &COMMANDS[index + 1..] {
+                assert!(info.name != other.name, "{} twice", info.name);
+                assert!(info.id != other.id, "{} and {} do the same", info.name, other.name);
+                assert!(info.directive.is_none() || info.directive != other.directive, "{} twice", info.shown());
+            }
+        }
+    }
+}
@@ -0,0 +1,242 @@
+// Directive completion module, completes directive names
+// and their arguments while they are typed in the console,
+// and hints at the syntax of the directive being typed.
+//
+// Arguments complete from:
+//      paths       : files and directories relative to the working directory
+//      lines       : the last line '$' of the ':l' forms, the lines around the cursor
+//                    and the line numbers starting with the digits typed
+//      encodings   : the encodings and line endings of ':en' and ':le'
+//      sessions    : the sessions saved in the working directory for ':ss' and ':os'
+//      commands    : the names of the commands for ':x'
+//      palettes    : the names of the 'palletes/*.pal' files
+//      fonts       : the fonts in 'assets/font/' and the system font directories
+//
+// The directives and their arguments are those of the command registry (see editor_commands.rs).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::editor_commands::{ArgKind, COMMANDS, directive_command};
+use crate::editor_config::home_dir;
+use crate::editor_encoding::{ENCODINGS, LINE_ENDINGS};
+use crate::editor_palette::palette_names;
+use crate::editor_session::session_names;
+
+const LINES_AROUND_CURSOR: usize = 2;   // Lines above and below the cursor ':l' offers
+const MAX_LINE_COMPLETIONS: usize = 20;
+const FONT_DIR: &str = "assets/font";
+const FONT_EXTENSIONS: [&str; 2] = ["ttf", "otf"];
+const SYSTEM_FONT_DIRS: [&str; 2] = ["/usr/share/fonts", "/usr/local/share/fonts"];
+const USER_FONT_DIRS: [&str; 2] = [".local/share/fonts", ".fonts"];
+const MAX_FONT_DEPTH: usize = 4;
+
+/// Syntax hint of the directive being typed
+pub fn directive_hint(input: &str) -> Option<String> {
+    let name = input.split_whitespace().next()?;
+    let info = directive_command(name)?;
+
+    Some(format!("{} : {}", info.usage(name), info.description))
+}
+
+/// The files in 'dir' with one of 'extensions'
+fn files_with_extensions(dir: &Path, extensions: &[&str], depth: usize, out: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+
+        if path.is_dir() {
+            if depth > 0 {
+                files_with_extensions(&path, extensions, depth - 1, out);
+            }
+            continue;
+        }
+
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+        if extension.is_some_and(|e| extensions.contains(&e.as_str())) {
+            out.push(path);
+        }
+    }
+}
+
+/// The fonts of 'assets/font/' first, then those of the system and the user
+fn font_files() -> Vec<PathBuf> {
+    let mut files = vec![];
+    files_with_extensions(Path::new(FONT_DIR), &FONT_EXTENSIONS, 0, &mut files);
+
+    for dir in SYSTEM_FONT_DIRS {
+        files_with_extensions(Path::new(dir), &FONT_EXTENSIONS, MAX_FONT_DEPTH, &mut files);
+    }
+
+    let home = home_dir();
+    for dir in USER_FONT_DIRS {
+        files_with_extensions(&home.join(dir), &FONT_EXTENSIONS, MAX_FONT_DEPTH, &mut files);
+    }
+
+    files
+}
+
+/// The font file named 'name', without its extension, as ':efn' completes it
+pub fn find_font(name: &str) -> Option<PathBuf> {
+    font_files().into_iter().find(|f| f.file_stem().is_some_and(|s| s.to_string_lossy().eq_ignore_ascii_case(name)))
+}
+
+fn complete_palettes(partial: &str) -> Vec<String> {
+    let mut names = palette_names();
+    names.retain(|n| n.starts_with(partial));
+    names
+}
+
+fn complete_fonts(partial: &str) -> Vec<String> {
+    let partial = partial.to_lowercase();
+    let mut names: Vec<String> = font_files().iter()
+        .filter_map(|f| f.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .filter(|n| n.to_lowercase().starts_with(&partial))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Resolve a typed path against the working directory, '~' is the home directory
+pub fn resolve_path(cwd: &Path, typed: &str) -> PathBuf {
+    if typed == "~" {
+        return home_dir();
+    }
+
+    match typed.strip_prefix("~/") {
+        Some(rest) => home_dir().join(rest),
+        None => cwd.join(typed)
+    }
+}
+
+fn complete_paths(cwd: &Path, partial: &str, directories_only: bool) -> Vec<String> {
+    // Split the typed text into the directory part, kept as typed, and the name being completed
+    let (dir_part, name_part) = match partial.rfind('/') {
+        Some(i) => (&partial[..=i], &partial[i + 1..]),
+        None => ("", partial)
+    };
+
+    let entries = match fs::read_dir(resolve_path(cwd, dir_part)) {
+        Ok(entries) => entries,
+        Err(_) => return vec![]
+    };
+
+    let mut paths: Vec<String> = entries.filter_map(|e| e.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let is_dir = entry.path().is_dir();
+
+            // Hidden entries only when asked for
+            if !name.starts_with(name_part) || (name.starts_with('.') && !name_part.starts_with('.')) {
+                return None;
+            }
+
+            if is_dir {
+                Some(format!("{}{}/", dir_part, name))
+            } else if !directories_only {
+                Some(format!("{}{}", dir_part, name))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    paths.sort();
+    paths
+}
+
+/// '$', then the lines around the cursor and the last line while nothing is typed,
+/// else the line numbers starting with the digits typed, 'lines' is the cursor line and line count
+fn complete_lines(partial: &str, lines: (usize, usize)) -> Vec<String> {
+    let (cursor, count) = lines;
+    let mut numbers: Vec<usize> = vec![];
+
+    if partial.is_empty() {
+        let first = cursor.saturating_sub(LINES_AROUND_CURSOR) + 1;
+        let last = (cursor + 1 + LINES_AROUND_CURSOR).min(count);
+        numbers.extend(first..=last);
+        numbers.push(count);
+    } else if let Ok(start) = partial.parse::<usize>() && start > 0 {
+        // 'start', then the numbers one digit longer, and so on while they are lines
+        let mut width = 1;
+        let mut first = start;
+        while first <= count && numbers.len() < MAX_LINE_COMPLETIONS {
+            numbers.extend((first..first + width).take_while(|n| *n <= count));
+            first *= 10;
+            width *= 10;
+        }
+        numbers.truncate(MAX_LINE_COMPLETIONS);
+    }
+    numbers.dedup();
+
+    let mut completions: Vec<String> = ["$"].iter().filter(|l| l.starts_with(partial)).map(|l| l.to_string()).collect();
+    completions.extend(numbers.into_iter().filter(|n| *n > 0).map(|n| n.to_string()));
+    completions
+}
+
+fn complete_names(names: impl Iterator<Item = &'static str>, partial: &str) -> Vec<String> {
+    names.filter(|n| n.starts_with(partial)).map(|n| n.to_string()).collect()
+}
+
+/// Completions for a directive input, each one is a whole input line,
+/// 'lines' is the cursor line and line count of the current file
+pub fn complete_directive(input: &str, cwd: &Path, lines: (usize, usize)) -> Vec<String> {
+    let (name, partial) = match input.split_once(' ') {
+        Some((name, partial)) => (name, partial.trim_start()),
+        None => {
+            return COMMANDS.iter()
+                .filter_map(|c| c.directive.filter(|d| d.starts_with(input)).map(|d| (d, c)))
+                .map(|(d, c)| if c.arg.is_none() { d.to_string() } else { format!("{} ", d) })
+                .collect();
+        }
+    };
+
+    let kind = match directive_command(name).and_then(|info| info.arg.as_ref()) {
+        Some(arg) => arg.kind,
+        None => return vec![]
+    };
+
+    let args = match kind {
+        ArgKind::File => complete_paths(cwd, partial, false),
+        ArgKind::Directory => complete_paths(cwd, partial, true),
+        ArgKind::Line => complete_lines(partial, lines),
+        ArgKind::Encoding => complete_names(ENCODINGS.iter().map(|e| e.name()), partial),
+        ArgKind::LineEnding => complete_names(LINE_ENDINGS.iter().map(|l| l.name()), partial),
+        ArgKind::Session => session_names(cwd).into_iter().filter(|n| n.starts_with(partial)).collect(),
+        ArgKind::Command => complete_names(COMMANDS.iter().map(|c| c.name), partial),
+        ArgKind::Palette => complete_palettes(partial),
+        ArgKind::Font => complete_fonts(partial),
+        ArgKind::Other => vec![]
+    };
+
+    args.into_iter().map(|arg| format!("{} {}", name, arg)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_around_the_cursor_and_the_last_line() {
+        assert_eq!(complete_lines("", (10, 100)), ["$", "9", "10", "11", "12", "13", "100"]);
+        assert_eq!(complete_lines("", (0, 1)), ["$", "1"]);
+    }
+
+    #[test]
+    fn line_numbers_starting_with_the_digits_typed() {
+        assert_eq!(complete_lines("1", (0, 120)).len(), MAX_LINE_COMPLETIONS);
+        assert_eq!(complete_lines("1", (0, 12)), ["1", "10", "11", "12"]);
+        assert_eq!(complete_lines("5", (0, 4)), Vec::<String>::new());
+        assert_eq!(complete_lines("$", (0, 4)), ["$"]);
+    }
+
+    #[test]
+    fn directive_line_completion() {
+        assert_eq!(complete_directive(":l 2", Path::new("."), (0, 25)), [":l 2", ":l 20", ":l 21", ":l 22", ":l 23", ":l 24", ":l 25"]);
+    }
+}
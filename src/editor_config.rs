@@ -0,0 +1,144 @@
+// Config module, reads the editor settings from cal.conf.
+//
+// Each line is a 'key = value' pair, lines starting
+// with '#' are comments. Unknown keys are kept but ignored.
+//
+// Settings:
+//      history_size = <N>      : Console history entries kept between sessions (500)
+//      recent_files_size = <N> : Recently opened files remembered (100)
+//      file_manager = <cmd>    : Command ':od' opens directories with, '{}' is
+//                                replaced by the directory (xdg-open)
+//      backup = <mode>         : Copy ':w' keeps of the previous content,
+//                                none, tilde ('name~') or timestamp (none)
+//      autosave = <N>          : Write named files after N idle seconds, 0 is off (0)
+//      fallback_encoding = <e> : Encoding of files that are not UTF-8 or UTF-16,
+//                                latin1, windows-1252 or windows-1253 (latin1)
+//      large_file_size = <N>   : Files of N megabytes and more load in the background,
+//                                read-only and without highlighting (64)
+//      palette = <name>        : Palette of 'palletes/', '--palette' overrides it (melpomene)
+//      font = <name>           : Font of 'assets/font/' or the system fonts (default)
+//      font_size = <N>         : Text size in pixels, '--font-size' overrides it (25)
+//      key.<command> = <keys>  : Keys running a command instead of its default ones,
+//                                'none' unbinds it (see editor_keymap.rs)
+//      macro.<n> = <commands>  : Commands ':m <n>' runs, separated by ';' (see editor_commands.rs)
+//
+// 'muse --config <path>' reads another file instead (see editor_cli.rs).
+// ':epa' and ':efn' write the palette and font they change to back to it.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::editor_fs::{BackupMode, write_atomic};
+
+pub const CONFIG_FILE: &str = "cal.conf";
+
+pub struct EditorConfig {
+    pub path: PathBuf,
+    values: HashMap<String, String>
+}
+
+impl EditorConfig {
+    /// Read the config at 'path', a missing file gives the defaults
+    pub fn load(path: &Path) -> EditorConfig {
+        let mut values = HashMap::new();
+
+        if let Ok(content) = fs::read_to_string(path) {
+            for line in content.lines() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+
+                if let Some((key, value)) = line.split_once('=') {
+                    values.insert(key.trim().to_string(), value.trim().to_string());
+                }
+            }
+        }
+
+        EditorConfig { path: path.to_path_buf(), values }
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.as_str())
+    }
+
+    pub fn get_usize(&self, key: &str, default: usize) -> usize {
+        self.get_str(key).and_then(|v| v.parse().ok()).unwrap_or(default)
+    }
+
+    /// Every 'key = value' whose key starts with 'prefix', the prefix
+    /// cut off and sorted by key
+    pub fn with_prefix(&self, prefix: &str) -> Vec<(&str, &str)> {
+        let mut entries: Vec<(&str, &str)> = self.values.iter()
+            .filter_map(|(k, v)| k.strip_prefix(prefix).map(|k| (k, v.as_str())))
+            .collect();
+        entries.sort();
+        entries
+    }
+}
+
+/// Directory for files the editor keeps between sessions,
+/// Set 'key = value' in the config at 'path', replacing the line of the key
+/// if there is one, the other lines and comments are kept
+pub fn save_value(path: &Path, key: &str, value: &str) -> io::Result<()> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e)
+    };
+
+    let line = format!("{} = {}", key, value);
+    let mut lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
+    let existing = lines.iter().position(|l| !l.trim_start().starts_with('#')
+        && l.split_once('=').is_some_and(|(k, _)| k.trim() == key));
+
+    match existing {
+        Some(index) => lines[index] = line,
+        None => lines.push(line)
+    }
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+    write_atomic(path, content.as_bytes(), BackupMode::None).map(|_| ())
+}
+
+/// $XDG_STATE_HOME/muse or ~/.local/state/muse
+pub fn state_dir() -> PathBuf {
+    let base = match env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir().join(".local").join("state")
+    };
+
+    base.join("muse")
+}
+
+/// The user's home directory, or the working directory if unknown
+pub fn home_dir() -> PathBuf {
+    env::var_os("HOME").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process;
+
+    #[test]
+    fn saved_value_replaces_its_line() {
+        let path = env::temp_dir().join(format!("muse-config-{}.conf", process::id()));
+        fs::write(&path, "# palette = old\npalette = melpomene\nfont_size = 20\n").unwrap();
+
+        save_value(&path, "palette", "clio").unwrap();
+        save_value(&path, "font", "mono").unwrap();
+        let config = EditorConfig::load(&path);
+        let content = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(content, "# palette = old\npalette = clio\nfont_size = 20\nfont = mono\n");
+        assert_eq!(config.get_str("palette"), Some("clio"));
+        assert_eq!(config.get_usize("font_size", 0), 20);
+    }
+}
@@ -1,40 +1,578 @@
-// Console module, see editor_directives.rs 
+// Console module, see editor_directives.rs
 // for more info.
 
+use std::path::{Path, PathBuf};
+
 use macroquad::prelude::*;
 
+use crate::editor_audio::EditorAudio;
+use crate::editor_buffers::EditorBuffers;
+use crate::editor_command_history::EditorCommandHistory;
+use crate::editor_commands::{ConsoleCommand, EditorCommands};
+use crate::editor_completion::{complete_directive, directive_hint};
+use crate::editor_config::EditorConfig;
+use crate::editor_directives::{answer_prompt, execute_directive};
+use crate::editor_fs::BackupMode;
+use crate::editor_fuzzy::{fuzzy_rank, list_files};
+use crate::editor_grep::{GrepMatch, GrepSearch};
+use crate::editor_jumps::EditorJumpList;
+use crate::editor_keymap::EditorKeymap;
+use crate::editor_open::{DirectoryOpener, SystemSpawner};
+use crate::editor_palette::palette;
+use crate::editor_recent::EditorRecentFiles;
+use crate::editor_session::SessionRequest;
+use crate::editor_tree::DirectoryTree;
 use crate::editor_text::*;
 
+/// A question waiting for the user's answer in the console
+pub enum ConsolePrompt {
+    CloseBuffer(usize),    // Buffer with the file id
+    RemoveFile(PathBuf),
+    RemoveDirectory(PathBuf),
+    RenameFile { id: usize, to: PathBuf }, // Buffer with the file id 'id'
+    WriteFile { id: usize, to: PathBuf },
+    RecoverSwap(PathBuf),
+    ExternalChange(usize),  // Buffer with the file id, changed on the disk
+    OverwriteChanged(usize),
+    RestoreSession(PathBuf),
+    Quit                    // The window was closed with unsaved changes
+}
+
+/// Reverse incremental search through the history
+pub struct ConsoleSearch {
+    pub query: String,
+    pub found: Option<usize>, // History entry matching the query
+    saved_input: String       // Put back if the search is cancelled
+}
+
 pub struct EditorConsole {
     pub mode: bool,
     pub directive: Option<String>,
-    pub directive_param: Option<String>
+    pub directive_param: Option<String>,
+    pub input: String,
+    pub output: Vec<String>,         // Messages of the last executed directive
+    pub message_at: f64,             // When the last message was given, the status bar shows it for a while
+    pub grep_results: Vec<GrepMatch>,
+    pub grep: Option<GrepSearch>,    // ':g' still searching, adding to 'grep_results'
+    pub tree: Option<DirectoryTree>, // Tree shown by ':td'
+    pub candidates: Vec<String>,     // Files with names close to the input, or directive completions
+    pub hint: Option<String>,        // Syntax of the directive being typed
+    pub selected: usize,             // Selected grep result or candidate
+    pub lines: (usize, usize),       // Cursor line and line count of the current file, for ':l' completion
+    pub cwd: PathBuf,                // Working directory of the directives
+    pub prompt: Option<ConsolePrompt>,
+    pub history: EditorCommandHistory,
+    pub recent: EditorRecentFiles,   // Listed while the input is empty
+    pub keymap: EditorKeymap,        // Commands of the keys pressed, listed by ':ekb'
+    pub commands: EditorCommands,    // Calls of the keys and directives waiting to run, and the macros
+    pub search: Option<ConsoleSearch>,
+    pub opener: DirectoryOpener,     // File manager of ':od'
+    pub backup: BackupMode,          // Backups ':w' keeps
+    pub session_request: Option<SessionRequest>, // ':ss' or ':os' waiting for the panes
+    pub font_request: Option<PathBuf>, // Font of ':efn', loaded before the next frame is drawn
+    pub config_path: PathBuf,        // Where ':epa' and ':efn' keep their settings
+    pub quit_request: bool,          // The window was closed and nothing unsaved is in the way
+    files: Vec<String>,              // Every file under 'files_root', for the candidates
+    files_root: Option<PathBuf>
 }
 
 const CONSOLE_WIDTH: f32 = 255.0;
+const CONSOLE_MARGIN: f32 = 8.0;
+const CONSOLE_FONT_SIZE: u16 = 16;
+const CONSOLE_PROMPT: &str = "> ";
+const CONSOLE_MAX_CANDIDATES: usize = 50;
 
 impl EditorConsole {
     /// Console constructor
-    pub fn new() -> EditorConsole {
-        EditorConsole { mode: false, directive: None, directive_param: None }
+    pub fn new(config: &EditorConfig) -> EditorConsole {
+        EditorConsole {
+            mode: false,
+            directive: None,
+            directive_param: None,
+            input: String::new(),
+            output: vec![],
+            message_at: 0.0,
+            grep_results: vec![],
+            grep: None,
+            tree: None,
+            candidates: vec![],
+            hint: None,
+            selected: 0,
+            lines: (0, 0),
+            cwd: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            prompt: None,
+            history: EditorCommandHistory::load(config),
+            recent: EditorRecentFiles::load(config),
+            keymap: EditorKeymap::load(config),
+            commands: EditorCommands::load(config),
+            search: None,
+            opener: DirectoryOpener::new(config, Box::new(SystemSpawner)),
+            backup: BackupMode::from_config(config),
+            session_request: None,
+            font_request: None,
+            config_path: config.path.clone(),
+            quit_request: false,
+            files: vec![],
+            files_root: None
+        }
+    }
+
+    /// Switch between the console and insert mode
+    pub fn toggle(&mut self) {
+        self.mode = !self.mode;
+
+        // The files may have changed while the console was closed
+        self.files_root = None;
+        clear_input_queue();
+    }
+
+    /// Something is being typed, so the closest files or the directive
+    /// completions are listed instead of the grep results
+    pub fn showing_candidates(&self) -> bool {
+        self.prompt.is_none() && !self.input.is_empty()
+    }
+
+    /// Nothing is typed, asked, searched for or shown by ':td' or ':g',
+    /// so the recent files are listed
+    fn showing_recent(&self) -> bool {
+        self.prompt.is_none() && self.input.is_empty() && self.search.is_none() && self.tree.is_none() && self.grep_results.is_empty()
+    }
+
+    /// The input is a directive rather than a file name
+    fn typing_directive(&self) -> bool {
+        self.input.starts_with(':')
+    }
+
+    /// The list Up/Down select from, as displayed:
+    /// the candidates, the recent files, the ':td' tree or the grep results
+    fn list(&self) -> Vec<String> {
+        if self.showing_candidates() {
+            return self.candidates.clone();
+        }
+        if self.showing_recent() {
+            return self.recent.display(&self.cwd);
+        }
+
+        match &self.tree {
+            Some(tree) => tree.rows().iter().map(|row| {
+                let marker = match (row.is_dir, row.expanded) {
+                    (true, true) => "- ",
+                    (true, false) => "+ ",
+                    (false, _) => "  "
+                };
+                let suffix = if row.is_dir { "/" } else { "" };
+                format!("{}{}{}{}", "  ".repeat(row.depth), marker, row.name, suffix)
+            }).collect(),
+            None => self.grep_results.iter().map(|r| self.format_grep_match(r)).collect()
+        }
+    }
+
+    fn list_len(&self) -> usize {
+        if self.showing_candidates() {
+            self.candidates.len()
+        } else if self.showing_recent() {
+            self.recent.paths.len()
+        } else if let Some(tree) = &self.tree {
+            tree.rows().len()
+        } else {
+            self.grep_results.len()
+        }
+    }
+
+    /// Rank the files under the working directory against the input,
+    /// or complete the directive being typed
+    pub fn update_candidates(&mut self) {
+        self.selected = 0;
+        self.hint = None;
+
+        if !self.showing_candidates() {
+            self.candidates.clear();
+            return;
+        }
+
+        if self.typing_directive() {
+            self.candidates = complete_directive(&self.input, &self.cwd, self.lines);
+            self.candidates.truncate(CONSOLE_MAX_CANDIDATES);
+            self.hint = directive_hint(&self.input);
+            return;
+        }
+
+        if self.files_root.as_ref() != Some(&self.cwd) {
+            self.files = list_files(&self.cwd);
+            self.files_root = Some(self.cwd.clone());
+        }
+
+        self.candidates = fuzzy_rank(&self.input, &self.files, &self.recent.display(&self.cwd), CONSOLE_MAX_CANDIDATES);
+    }
+
+    /// Replace the console output with a single message
+    pub fn message(&mut self, msg: &str) {
+        self.output = vec![msg.to_string()];
+        self.message_at = get_time();
+    }
+
+    /// Open the console with a question, the next input answers it
+    pub fn ask(&mut self, prompt: ConsolePrompt, question: &str) {
+        self.prompt = Some(prompt);
+        self.message(question);
+        self.input.clear();
+        self.mode = true;
+    }
+
+    /// Grep result as displayed in the console, 'file:line: text'
+    fn format_grep_match(&self, result: &GrepMatch) -> String {
+        let path = result.path.strip_prefix(&self.cwd).unwrap_or(&result.path);
+        format!("{}:{}: {}", path.display(), result.line + 1, result.text)
+    }
+
+    /// Open the selected recent file, the selected grep result at its line,
+    /// or the selected file of the tree, a selected directory is expanded or collapsed
+    fn open_selected(&mut self, buffers: &mut EditorBuffers, jumps: &mut EditorJumpList) {
+        if self.showing_recent() {
+            if let Some(path) = self.recent.paths.get(self.selected).cloned() {
+                self.open_path(&path, None, buffers, jumps);
+            }
+            return;
+        }
+
+        if let Some(tree) = self.tree.as_mut() {
+            let row = match tree.rows().into_iter().nth(self.selected) {
+                Some(row) => row,
+                None => return
+            };
+
+            if row.is_dir {
+                tree.toggle(&row.path);
+            } else {
+                self.open_path(&row.path, None, buffers, jumps);
+            }
+            return;
+        }
+
+        if let Some(result) = self.grep_results.get(self.selected) {
+            let (path, line) = (result.path.clone(), result.line);
+            self.open_path(&path, Some(line), buffers, jumps);
+        }
+    }
+
+    /// Open a file, at 'line' if given, and close the console
+    fn open_path(&mut self, path: &Path, line: Option<usize>, buffers: &mut EditorBuffers, jumps: &mut EditorJumpList) {
+        let from = (buffers.current().path.clone(), buffers.current().cursor.xy);
+
+        match buffers.open(path) {
+            Ok(()) => {
+                jumps.push(&from.0, from.1);
+
+                if let Some(line) = line {
+                    buffers.current_mut().goto(line, 0);
+                }
+                self.mode = false;
+            }
+            Err(e) => self.message(&format!("Cannot open {}: {}", path.display(), e))
+        }
+    }
+
+    /// Expand or collapse the selected directory of the tree,
+    /// collapsing a file or a collapsed directory selects its parent
+    fn expand_selected(&mut self, expanded: bool) {
+        let tree = match self.tree.as_mut() {
+            Some(tree) => tree,
+            None => return
+        };
+
+        let rows = tree.rows();
+        let row = match rows.get(self.selected) {
+            Some(row) => row,
+            None => return
+        };
+
+        if row.is_dir && row.expanded != expanded {
+            tree.set_expanded(&row.path, expanded);
+        } else if !expanded && let Some(parent) = rows[..self.selected].iter().rposition(|r| r.depth + 1 == row.depth) {
+            self.selected = parent;
+        }
     }
 
     /// Console will be drawn to the right of the screen
-    pub fn draw(&self) {
+    pub fn draw(&self, gts: &EditorGeneralTextStylizer) {
+        let x = screen_width() - CONSOLE_WIDTH;
+
         // Console background
-        draw_rectangle(screen_width() - CONSOLE_WIDTH,
+        draw_rectangle(x,
             0.0,
             CONSOLE_WIDTH,
             screen_height(),
-            COMPOSITE_TYPE_COLOR
+            palette().composite_type
         );
 
         // Console foreground
-        draw_rectangle(screen_width() - CONSOLE_WIDTH + 1.5,
+        draw_rectangle(x + 1.5,
             0.0,
             CONSOLE_WIDTH,
             screen_height(),
-            BACKGROUND_COLOR
+            palette().background
         );
+
+        let line_height = CONSOLE_FONT_SIZE as f32 * 1.25;
+        let max_width = CONSOLE_WIDTH - 2.0 * CONSOLE_MARGIN;
+        let mut y = CONSOLE_MARGIN + CONSOLE_FONT_SIZE as f32;
+
+        // Input line, or the search and its match
+        let input = match &self.search {
+            Some(search) => {
+                let found = search.found.map_or("", |i| self.history.entries[i].as_str());
+                format!("(search) '{}': {}", search.query, found)
+            }
+            None => format!("{}{}", CONSOLE_PROMPT, self.input)
+        };
+        draw_console_text(&input, x + CONSOLE_MARGIN, y, max_width, WHITE, gts);
+
+        if let Some(hint) = &self.hint && self.search.is_none() {
+            y += line_height;
+            draw_console_text(hint, x + CONSOLE_MARGIN, y, max_width, GRAY, gts);
+        }
+        y += line_height * 1.5;
+
+        for msg in &self.output {
+            draw_console_text(msg, x + CONSOLE_MARGIN, y, max_width, WHITE, gts);
+            y += line_height;
+        }
+
+        let list = self.list();
+        if self.showing_recent() && !list.is_empty() {
+            draw_console_text("Recent files", x + CONSOLE_MARGIN, y, max_width, GRAY, gts);
+            y += line_height;
+        }
+
+        // Keep the selected entry on screen
+        let rows = ((screen_height() - y) / line_height).max(1.0) as usize;
+        let first = self.selected.saturating_sub(rows - 1);
+
+        for (i, entry) in list.iter().enumerate().skip(first).take(rows) {
+            if i == self.selected {
+                draw_rectangle(x + 1.5, y - CONSOLE_FONT_SIZE as f32 * 0.8, CONSOLE_WIDTH, line_height, palette().composite_type);
+            }
+
+            draw_console_text(entry, x + CONSOLE_MARGIN, y, max_width, WHITE, gts);
+            y += line_height;
+        }
+    }
+}
+
+/// Draw a single line of console text, cut to fit the console width
+fn draw_console_text(text: &str, x: f32, y: f32, max_width: f32, color: Color, gts: &EditorGeneralTextStylizer) {
+    let mut end = text.len();
+    while end > 0 && measure_text(&text[..end], Some(&gts.font), CONSOLE_FONT_SIZE, 1.0).width > max_width {
+        end = text[..end].char_indices().last().map(|(b, _)| b).unwrap_or(0);
+    }
+
+    draw_text_ex(&text[..end], x, y,
+        TextParams { font: Some(&gts.font), font_size: CONSOLE_FONT_SIZE, color, ..Default::default() });
+}
+
+/// Characters typed since the last frame, in order
+fn typed_chars() -> Vec<char> {
+    // The input queue is a stack, so the order is restored before inserting
+    let mut chars = vec![];
+    while let Some(c) = get_char_pressed() {
+        chars.push(c);
+    }
+
+    chars.into_iter().rev().filter(|c| !c.is_control()).collect()
+}
+
+/// Key recording during a reverse history search, the typed characters refine the query
+fn record_keyboard_to_search(console: &mut EditorConsole, audio: &EditorAudio) {
+    let search = match console.search.as_mut() {
+        Some(search) => search,
+        None => return
+    };
+
+    let mut changed = false;
+
+    if is_key_pressed(KeyCode::Backspace) {
+        audio.play_delete();
+        search.query.pop();
+        changed = true;
+    }
+
+    for c in typed_chars() {
+        audio.play_insert();
+        search.query.push(c);
+        changed = true;
+    }
+
+    if changed {
+        search.found = console.history.search(&search.query, console.history.entries.len());
+    }
+}
+
+/// Key recording while the console is open, 'command' is what the keymap made of the keys pressed
+pub fn record_keyboard_to_console(console: &mut EditorConsole, buffers: &EditorBuffers, audio: &EditorAudio, command: Option<&str>) {
+    console.lines = (buffers.current().cursor.xy.1, buffers.current().text.len());
+
+    // The console keys, the console switch or another command of the keys
+    if let Some(command) = command {
+        console.commands.push(command, None);
+        return;
+    }
+
+    if console.search.is_some() {
+        record_keyboard_to_search(console, audio);
+        return;
+    }
+
+    // Chords without a command are not typed
+    if is_key_down(KeyCode::LeftControl) {
+        clear_input_queue();
+        return;
+    }
+
+    if is_key_pressed(KeyCode::Backspace) {
+        audio.play_delete();
+        console.input.pop();
+        console.history.reset();
+        console.update_candidates();
+    }
+
+    let chars = typed_chars();
+    if chars.is_empty() {
+        return;
+    }
+
+    for c in chars {
+        if c != ' ' {
+            audio.play_insert();
+        } else {
+            audio.play_space();
+        }
+
+        console.input.push(c);
+    }
+
+    console.history.reset();
+    console.update_candidates();
+}
+
+/// The 'console.*' commands, the keys of the open console (see editor_keymap.rs)
+pub fn console_navigation(command: ConsoleCommand, console: &mut EditorConsole, buffers: &mut EditorBuffers,
+    jumps: &mut EditorJumpList, audio: &EditorAudio) {
+    if let Some(search) = console.search.as_mut() {
+        match command {
+            // Again looks further back
+            ConsoleCommand::SearchHistory => {
+                let before = search.found.unwrap_or(console.history.entries.len());
+                if let Some(found) = console.history.search(&search.query, before) {
+                    audio.play_nav();
+                    search.found = Some(found);
+                }
+            }
+            ConsoleCommand::Cancel => {
+                console.input = std::mem::take(&mut search.saved_input);
+                console.search = None;
+            }
+            // Enter runs the match, Up/Down only take it into the input
+            ConsoleCommand::Accept | ConsoleCommand::Up | ConsoleCommand::Down => {
+                if let Some(found) = search.found {
+                    console.input = console.history.entries[found].clone();
+                }
+                console.search = None;
+                console.update_candidates();
+
+                if command == ConsoleCommand::Accept {
+                    run_input(console, buffers, jumps, audio);
+                }
+            }
+            ConsoleCommand::Complete | ConsoleCommand::Expand | ConsoleCommand::Collapse => {}
+        }
+        return;
+    }
+
+    // Up/Down pick a candidate, a recent file or a grep result while the input is empty,
+    // or walk through the history when there is nothing to pick or Up is pressed at the top of the recent files
+    let walk_history = console.prompt.is_none()
+        && (console.history.walking() || console.list_len() == 0 || (console.typing_directive() && console.candidates.is_empty()));
+
+    match command {
+        ConsoleCommand::SearchHistory => {
+            audio.play_nav();
+            console.history.reset();
+            console.search = Some(ConsoleSearch { query: String::new(), found: None, saved_input: console.input.clone() });
+        }
+        ConsoleCommand::Cancel => {
+            match console.prompt.take() {
+                // Asked by the watcher, which would ask again at once, so ours is kept
+                Some(prompt @ ConsolePrompt::ExternalChange(_)) => answer_prompt(prompt, "k", console, buffers),
+                Some(_) => console.message("Cancelled"),
+                None => {}
+            }
+            console.mode = false;
+        }
+        ConsoleCommand::Up => {
+            if walk_history || (console.showing_recent() && console.selected == 0) {
+                if let Some(entry) = console.history.older(&console.input) {
+                    audio.play_nav();
+                    console.input = entry;
+                    console.candidates.clear();
+                }
+            } else if console.selected > 0 {
+                audio.play_nav();
+                console.selected -= 1;
+            }
+        }
+        ConsoleCommand::Down => {
+            if walk_history {
+                if let Some(entry) = console.history.newer() {
+                    audio.play_nav();
+                    console.input = entry;
+                    console.candidates.clear();
+                }
+            } else if console.selected + 1 < console.list_len() {
+                audio.play_nav();
+                console.selected += 1;
+            }
+        }
+        // Collapse/expand the selected directory of the tree
+        ConsoleCommand::Expand | ConsoleCommand::Collapse => {
+            if console.tree.is_some() && !console.showing_candidates() && console.prompt.is_none() {
+                audio.play_nav();
+                console.expand_selected(command == ConsoleCommand::Expand);
+            }
+        }
+        // Autocomplete the selected candidate or directive argument, the closest one unless another was picked
+        ConsoleCommand::Complete => {
+            if console.showing_candidates() && let Some(candidate) = console.candidates.get(console.selected) {
+                audio.play_space();
+                console.input = candidate.clone();
+                console.update_candidates();
+            }
+        }
+        ConsoleCommand::Accept => run_input(console, buffers, jumps, audio)
+    }
+}
+
+/// Run the input, answer the question asked with it, or open the selected entry when it is empty
+fn run_input(console: &mut EditorConsole, buffers: &mut EditorBuffers, jumps: &mut EditorJumpList, audio: &EditorAudio) {
+    audio.play_return();
+
+    // A name that is neither open nor on the disk stands for the selected candidate,
+    // directives run as typed
+    let typed = console.input.trim();
+    let exact = console.cwd.join(typed).exists() || buffers.files.iter().any(|f| f.name() == typed);
+    if console.showing_candidates() && !console.typing_directive() && !exact && let Some(candidate) = console.candidates.get(console.selected) {
+        console.input = candidate.clone();
+    }
+
+    let input = std::mem::take(&mut console.input);
+    console.candidates.clear();
+    console.hint = None;
+
+    if let Some(prompt) = console.prompt.take() {
+        answer_prompt(prompt, &input, console, buffers);
+    } else if input.is_empty() {
+        console.open_selected(buffers, jumps);
+    } else {
+        console.history.push(&input);
+        execute_directive(&input, console);
     }
 }
@@ -1,18 +1,32 @@
-// Cursor navigation module
-
-use macroquad::prelude::*;
+// Cursor navigation module, runs the 'cursor.*' commands
+// (see editor_keymap.rs for their keys)
+//
+// The 'select.*' commands move the cursor the same way and select the text
+// between where they started and the cursor. Other cursor moves and edits
+// drop the selection, ':i' shows its statistics.
 
 use crate::editor_audio::EditorAudio;
+use crate::editor_commands::CursorMove;
 
 #[allow(dead_code)]
+#[derive(Clone, Copy)]
 pub struct EditorCursor {
-    pub xy: (usize, usize)
+    pub xy: (usize, usize),
+    pub anchor: Option<(usize, usize)> // Where the selection started, it runs from there to 'xy'
 }
 
 impl EditorCursor {
     #[allow(dead_code)]
     pub fn new() -> EditorCursor {
-        EditorCursor { xy: (0, 0) }
+        EditorCursor { xy: (0, 0), anchor: None }
+    }
+
+    /// Start and end of the selection, in text order, None if nothing is selected
+    #[allow(dead_code)]
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        let anchor = self.anchor.filter(|a| *a != self.xy)?;
+        // Compared line first, then column
+        if (anchor.1, anchor.0) < (self.xy.1, self.xy.0) { Some((anchor, self.xy)) } else { Some((self.xy, anchor)) }
     }
 }
 
@@ -20,8 +34,8 @@ pub static CURSOR_LINE_TO_WIDTH: bool = true;
 
 /// Standard cursor navigation
 #[allow(dead_code)] // Compiler won't shut the fuck up
-pub fn file_text_navigation(cursor: &mut (usize, usize), text: &mut Vec<String>, audio: &EditorAudio) {
-    if is_key_pressed(KeyCode::Up) {
+pub fn file_text_navigation(motion: CursorMove, cursor: &mut (usize, usize), text: &mut Vec<String>, audio: &EditorAudio) {
+    if motion == CursorMove::Up {
         if cursor.1 > 0 {
             audio.play_nav();
             cursor.1 -= 1;
@@ -29,7 +43,7 @@ pub fn file_text_navigation(cursor: &mut (usize, usize), text: &mut Vec<String>,
         }
     }
 
-    if is_key_pressed(KeyCode::Down) {
+    if motion == CursorMove::Down {
         if text.len() > cursor.1 + 1 {
             audio.play_nav();
             cursor.1 += 1;
@@ -37,7 +51,7 @@ pub fn file_text_navigation(cursor: &mut (usize, usize), text: &mut Vec<String>,
         }
     }
 
-    if is_key_pressed(KeyCode::Left) {
+    if motion == CursorMove::Left {
         if cursor.0 > 0 {
             audio.play_nav();
             cursor.0 -= 1;
@@ -49,7 +63,7 @@ pub fn file_text_navigation(cursor: &mut (usize, usize), text: &mut Vec<String>,
         }
     }
 
-    if is_key_pressed(KeyCode::Right) {
+    if motion == CursorMove::Right {
         if cursor.0 < text[cursor.1].len() {
             audio.play_nav();
             cursor.0 += 1;
@@ -115,10 +129,9 @@ fn calibrate_distance_to_whitespace_or_character(leftorright: bool, cursor_idx:
     }
 }
 
-/// Faster cursor navigation inside the file
-/// only usable when the LCTRL key is down
+/// Faster cursor navigation inside the file, word by word
 #[allow(dead_code)]
-pub fn file_text_special_navigation(cursor: &mut (usize, usize), text: &mut Vec<String>, audio: &EditorAudio) {
+pub fn file_text_special_navigation(motion: CursorMove, cursor: &mut (usize, usize), text: &mut Vec<String>, audio: &EditorAudio) {
     let line = &text[cursor.1];
     let left_steps_to_whitespace = calibrate_distance_to_whitespace_or_character(false, cursor.0, line);
     let right_steps_to_whitespace = calibrate_distance_to_whitespace_or_character(true, cursor.0, line);
@@ -140,7 +153,7 @@ pub fn file_text_special_navigation(cursor: &mut (usize, usize), text: &mut Vec<
     //     }
     // }
 
-    if is_key_pressed(KeyCode::Left) {
+    if motion == CursorMove::WordLeft {
         if cursor.0 > 0 {
             audio.play_nav();
             cursor.0 = cursor.0.saturating_sub(left_steps_to_whitespace);
@@ -151,7 +164,7 @@ pub fn file_text_special_navigation(cursor: &mut (usize, usize), text: &mut Vec<
         }
     }
     
-    if is_key_pressed(KeyCode::Right) {
+    if motion == CursorMove::WordRight {
         if cursor.0 < text[cursor.1].len() {
             audio.play_nav();
             cursor.0 += right_steps_to_whitespace.min(text[cursor.1].len() - cursor.0);
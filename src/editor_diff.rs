@@ -0,0 +1,100 @@
+// Diff module, the lines that differ between two versions of a text,
+// shown in the console when a file with unsaved changes changed on the disk.
+//
+// Lines only in the old text start with '- ', lines only in the new one with '+ ',
+// each group of changes is headed by '@@ line N' of the old text.
+
+const DIFF_MAX_CELLS: usize = 4_000_000; // Largest table for the common subsequence
+const DIFF_MAX_LINES: usize = 200;
+
+#[derive(Clone, Copy, PartialEq)]
+enum DiffOp {
+    Same,
+    Removed,
+    Added
+}
+
+/// Longest common subsequence of the lines, as the steps from 'old' to 'new'
+fn lcs_ops(old: &[String], new: &[String]) -> Vec<DiffOp> {
+    let (n, m) = (old.len(), new.len());
+
+    // lengths[i][j]: common lines of old[i..] and new[j..]
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i * (m + 1) + j] = if old[i] == new[j] {
+                lengths[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let mut ops = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            ops.push(DiffOp::Same);
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1]) {
+            ops.push(DiffOp::Removed);
+            i += 1;
+        } else {
+            ops.push(DiffOp::Added);
+            j += 1;
+        }
+    }
+
+    ops
+}
+
+pub fn diff_lines(old: &[String], new: &[String]) -> Vec<String> {
+    // Only the middle that differs goes through the table
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let ops = if old_middle.len() * new_middle.len() <= DIFF_MAX_CELLS {
+        lcs_ops(old_middle, new_middle)
+    } else {
+        let mut ops = vec![DiffOp::Removed; old_middle.len()];
+        ops.extend(vec![DiffOp::Added; new_middle.len()]);
+        ops
+    };
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    let mut previous = DiffOp::Same;
+
+    for op in ops {
+        if op != DiffOp::Same && previous == DiffOp::Same {
+            lines.push(format!("@@ line {}", prefix + i + 1));
+        }
+
+        match op {
+            DiffOp::Same => {
+                i += 1;
+                j += 1;
+            }
+            DiffOp::Removed => {
+                lines.push(format!("- {}", old_middle[i]));
+                i += 1;
+            }
+            DiffOp::Added => {
+                lines.push(format!("+ {}", new_middle[j]));
+                j += 1;
+            }
+        }
+        previous = op;
+    }
+
+    if lines.len() > DIFF_MAX_LINES {
+        let more = lines.len() - DIFF_MAX_LINES;
+        lines.truncate(DIFF_MAX_LINES);
+        lines.push(format!("... {} more lines", more));
+    }
+
+    lines
+}
@@ -4,7 +4,9 @@
 // , change pallete and create files.
 //
 // The user can go from console, to insert mode and (vize versa)
-// by pressing 'CTRL +`'.
+// by pressing 'CTRL +`' ('view.toggle_console', see editor_keymap.rs).
+//
+// The working directory of the directives is shown in the status bar.
 //
 // All directives have the ':' prefix, as the console will
 // handle input as a switch-to-file directive.
@@ -12,23 +14,47 @@
 // Directives include:
 //      File specific:
 //              :l <N>      : Go to line N inside the file, if possible, else throw an error
+//                 <N:C>    : Go to line N and column C
+//                 <+N/-N>  : Go N lines down/up from the cursor
+//                 <$>      : Go to the last line
 //              :w          : Write the current open file
-//              :i          : Current file info display
-//              :r <f>      : Remove a file with name 'f'
+//              :w <f>      : Write it to 'f' and keep editing it there,
+//                            writes never leave a half written file (see editor_fs.rs)
+//                            and ask first if the file changed on the disk (see editor_watch.rs)
+//              :i          : Current file info display, with the counts of the selection
+//              :en <e>     : Convert the current file to the encoding 'e' (utf-8, utf-8-bom,
+//                            utf-16le, utf-16be, latin1, windows-1252, windows-1253),
+//                            written so on the next ':w' (see editor_encoding.rs)
+//              :le <l>     : Convert the line endings of the current file to 'l' (lf, crlf, cr)
+//              :ro         : Read-only on/off switch of the current file, files
+//                            without write permission and '--readonly' open read-only
+//              :r <f>      : Remove a file with name 'f', it is moved to the trash
+//                            in the state directory (see editor_fs.rs)
 //              :b <f>      : Change the name of the current open file to 'f'
 //              :f <f>      : Go to the line where the first iteration of text 'f' exists
 //              :c <f>      : Create a new file with name 'f'   
 //
 //      Directory specific:
-//              :cd         : Change directory
-//              :od         : Open a directory, create process -> native file explorer
+//              :cd <d>     : Change directory, '..', '~' and absolute paths work,
+//                            without 'd' go to the home directory
+//              :od <d>     : Open a directory, create process -> native file explorer,
+//                            the working directory without 'd' (see editor_open.rs)
 //              :md <f>     : Create a new directory with name 'f'
-//              :rd <f>     : Remove a directory with name 'f' with all its contents
+//              :rd <f>     : Remove a directory with name 'f' with all its contents,
+//                            asks first and moves it to the trash
 //              :bd <f>     : Change the name of the current open directory to 'f'
-//              :ld         : List all files in the directory
-//              :td         : Show all contents of the directory recursively
+//              :ld         : List all files in the directory, with their sizes
+//              :td         : Show all contents of the directory recursively,
+//                            Left/Right or Enter collapse and expand directories
+//              :g <re>     : Search all files under the directory for the regex 're',
+//                            select a 'file:line: text' result to open it at that line
+//
+//      Session specific: <kept per working directory, see editor_session.rs>
+//              :ss <n>     : Save the open files, cursors and splits as the session 'n',
+//                            'default' without 'n'
+//              :os <n>     : Open the session 'n', without 'n' list the saved sessions
 //
-//      Conf: <saved in cal.conf file>
+//      Conf: <saved in cal.conf file, see editor_config.rs>
 //              :epa  <p>   : Change to pallete of name 'p'
 //              :efn  <p>   : Change to a font of name 'p'
 //              :efs <N>    : Change font size to N
@@ -38,6 +64,11 @@
 //              :efl        : Editor fullscreen switch
 //              :ehi        : Editor highlighting toggle
 //              :ewt        : Editor cursor width toggle
+//              :ekb <t>    : List the key bindings and their conflicts, only those
+//                            containing 't' if given (see editor_keymap.rs)
+//              :m <n>      : Run the macro 'n', without 'n' list the macros
+//              :x <c>      : Run the command 'c' by name, with its argument
+//
 //      Other:
 //              :e          : Exit, close editor
 //              :egman      : Editor general manual (All manuals are displayed)
@@ -49,10 +80,961 @@
 //              :eck        : Editor clock (current time and time opened)
 //              :egam <N>   : Editor gamble, display a number from 0 to N
 //
+// Every directive is a named command with a typed argument, ':w <f>' is
+// 'file.save <f>', run the same way as keys and macros (see editor_commands.rs).
+// The manuals list the commands with their directives and keys.
+//
 // When the console is faced with a directive without a ':' prefix
 // it will view it as a switch-to-file command and will try to switch 
 // to a file with that name if found, same with directorys.
-// The console, as long as you are typing, will display files with names close to it.
+// The console, as long as you are typing, will display files with names close to it,
+// recently opened ones first. With nothing typed it lists the recent files (see editor_recent.rs).
 // Pressing TAB will select the first seen file closest to the name given and autocomplete it
-// in the console.
-//
\ No newline at end of file
+// in the console. Up/Down pick another of the listed files instead. These keys,
+// Enter and Escape are the 'console.*' commands (see editor_keymap.rs).
+//
+// Directives are completed the same way, their names and then their arguments:
+// palette names for ':epa', fonts for ':efn', files and directories relative to
+// the working directory for ':w', ':c', ':r', ':b', ':cd', ':od', ':md' and ':rd',
+// '$' and line numbers for ':l' and command names for ':x'.
+// A hint under the input shows the syntax of the directive being typed.
+//
+use regex::Regex;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::editor_buffers::EditorBuffers;
+use crate::editor_completion::resolve_path;
+use crate::editor_config::{home_dir, save_value};
+use crate::editor_console::{ConsolePrompt, EditorConsole};
+use crate::editor_diff::diff_lines;
+use crate::editor_commands::parse_call;
+use crate::editor_encoding::{Encoding, LineEnding};
+use crate::editor_file::{DiskStamp, EditorFile, is_writable, read_file_lines};
+use crate::editor_fs::{format_size, move_path, move_to_trash, summarize_directory};
+use crate::editor_palette::{EditorPalette, set_palette};
+use crate::editor_grep::GrepSearch;
+use crate::editor_info::file_info;
+use crate::editor_jumps::EditorJumpList;
+use crate::editor_session::{DEFAULT_SESSION_NAME, SessionRequest, named_session_path, session_names};
+use crate::editor_swap::{discard_swap, recover_swap};
+use crate::editor_tree::DirectoryTree;
+use crate::editor_watch::ask_external_change;
+
+const RD_SUMMARY_ENTRIES: usize = 10; // Entries listed under the ':rd' question
+const TD_MAX_ENTRIES: usize = 2000;    // Entries read before ':td' stops expanding
+
+/// Parse a line typed in the console into a command call, queued to run this frame
+/// (see editor_commands.rs)
+pub fn execute_directive(input: &str, console: &mut EditorConsole) {
+    let input = input.trim();
+
+    let (directive, param) = match input.split_once(char::is_whitespace) {
+        Some((directive, param)) => (directive, Some(param.trim())),
+        None => (input, None)
+    };
+
+    console.directive = Some(directive.to_string());
+    console.directive_param = param.map(|p| p.to_string());
+
+    if !directive.starts_with(':') {
+        console.commands.push("file.open", Some(input));
+        return;
+    }
+
+    match parse_call(input) {
+        Ok(call) => console.commands.queue.push_back(call),
+        Err(e) => console.message(&e)
+    }
+}
+
+/// Handle the answer to a question asked through the console
+pub fn answer_prompt(prompt: ConsolePrompt, answer: &str, console: &mut EditorConsole, buffers: &mut EditorBuffers) {
+    let yes = matches!(answer.trim(), "y" | "Y" | "yes");
+
+    match prompt {
+        ConsolePrompt::CloseBuffer(id) => match buffers.index_of(id) {
+            Some(index) if yes => {
+                let name = buffers.files[index].name();
+                buffers.close(index);
+                console.message(&format!("Closed {}", name));
+                console.mode = false;
+            }
+            Some(_) => console.message("Kept open"),
+            None => console.message("The buffer was closed")
+        },
+        ConsolePrompt::RemoveFile(path) => {
+            if yes {
+                remove_file(console, buffers, &path);
+            } else {
+                console.message("Nothing removed");
+            }
+        }
+        ConsolePrompt::RemoveDirectory(path) => {
+            if yes {
+                remove_directory(console, buffers, &path);
+            } else {
+                console.message("Nothing removed");
+            }
+        }
+        ConsolePrompt::WriteFile { id, to } => {
+            if yes {
+                write_buffer(console, buffers, id, Some(to));
+            } else {
+                console.message("Nothing written");
+            }
+        }
+        ConsolePrompt::RecoverSwap(path) => {
+            let result = if yes { recover_swap(&path, buffers) } else { discard_swap(&path).map(|_| String::new()) };
+
+            match result {
+                Ok(name) if yes => {
+                    console.message(&format!("Recovered {}, write it with ':w'", name));
+                    console.mode = false;
+                }
+                Ok(_) => console.message("Discarded"),
+                Err(e) => console.message(&format!("Cannot read {}: {}", path.display(), e))
+            }
+        }
+        ConsolePrompt::ExternalChange(id) => answer_external_change(console, buffers, id, answer),
+        ConsolePrompt::OverwriteChanged(id) => {
+            if yes {
+                write_buffer(console, buffers, id, None);
+            } else {
+                console.message("Nothing written");
+            }
+        }
+        ConsolePrompt::RestoreSession(path) => {
+            if yes {
+                console.session_request = Some(SessionRequest::Restore(path));
+            } else {
+                console.message("Starting a new session");
+            }
+        }
+        ConsolePrompt::RenameFile { id, to } => {
+            if yes {
+                rename_buffer(console, buffers, id, &to);
+            } else {
+                console.message("Nothing renamed");
+            }
+        }
+        ConsolePrompt::Quit => answer_quit(console, buffers, answer)
+    }
+}
+
+/// Closing the window quits at once, unless buffers have unsaved changes
+pub fn request_quit(console: &mut EditorConsole, buffers: &EditorBuffers) {
+    let dirty: Vec<String> = buffers.files.iter().filter(|f| f.is_dirty()).map(|f| f.name()).collect();
+    if dirty.is_empty() {
+        console.quit_request = true;
+        return;
+    }
+
+    console.ask(ConsolePrompt::Quit,
+        &format!("Unsaved changes in {}: (w)rite all, (d)iscard or (c)ancel?", dirty.join(", ")));
+}
+
+/// (w)rite all, (d)iscard or (c)ancel for quitting with unsaved changes
+fn answer_quit(console: &mut EditorConsole, buffers: &mut EditorBuffers, answer: &str) {
+    match answer.trim() {
+        "w" | "write" => {
+            let dirty: Vec<usize> = buffers.files.iter().filter(|f| f.is_dirty()).map(|f| f.id).collect();
+            for id in dirty {
+                write_buffer(console, buffers, id, None);
+            }
+
+            // An untitled buffer or a failed write keeps the editor open
+            let left: Vec<String> = buffers.files.iter().filter(|f| f.is_dirty()).map(|f| f.name()).collect();
+            if left.is_empty() {
+                console.quit_request = true;
+            } else {
+                console.message(&format!("Not quitting, {} still has unsaved changes", left.join(", ")));
+            }
+        }
+        "d" | "discard" => console.quit_request = true,
+        "c" | "cancel" => console.message("Not quitting"),
+        _ => request_quit(console, buffers)
+    }
+}
+
+/// (r)eload, (k)eep ours or (d)iff for a file changed on the disk
+fn answer_external_change(console: &mut EditorConsole, buffers: &mut EditorBuffers, id: usize, answer: &str) {
+    let file = match buffers.index_of(id) {
+        Some(index) => &mut buffers.files[index],
+        None => {
+            console.message("The buffer was closed");
+            return;
+        }
+    };
+
+    match answer.trim() {
+        "r" | "reload" => match file.reload() {
+            Ok(()) => console.message(&format!("Read {} again, undo to get ours back", file.name())),
+            Err(e) => console.message(&format!("Cannot read {} again: {}", file.name(), e))
+        },
+        "k" | "keep" => {
+            file.kept_over = file.path.as_ref().and_then(|p| DiskStamp::of(p));
+            console.message(&format!("Keeping ours, ':w' asks before writing over {}", file.name()));
+        }
+        "d" | "diff" => {
+            let disk = match file.path.as_ref().map(|p| read_file_lines(p)) {
+                Some(Ok((lines, _))) => lines,
+                _ => {
+                    console.message(&format!("Cannot read {}", file.name()));
+                    return;
+                }
+            };
+
+            // Asked again under the diff, ours is '-' and the disk '+'
+            let diff = diff_lines(&file.text, &disk);
+            ask_external_change(console, id, &file.name());
+            console.output.push(String::from("- ours, + on the disk"));
+            console.output.extend(diff);
+        }
+        _ => ask_external_change(console, id, &file.name())
+    }
+}
+
+/// Input without a ':' prefix, switch to an open file with that name
+/// or open it from the working directory
+pub fn switch_to_file(console: &mut EditorConsole, buffers: &mut EditorBuffers, jumps: &mut EditorJumpList, name: &str) {
+    let from = (buffers.current().path.clone(), buffers.current().cursor.xy);
+
+    let open = buffers.files.iter().position(|f| f.name() == name || f.path.as_ref().is_some_and(|p| p.ends_with(name)));
+    if let Some(index) = open {
+        buffers.current = index;
+    } else {
+        let path = console.cwd.join(Path::new(name));
+
+        if path.is_dir() {
+            console.cwd = path.canonicalize().unwrap_or(path);
+            console.message(&format!("Directory {}", console.cwd.display()));
+            return;
+        }
+
+        if let Err(e) = buffers.open(&path) {
+            console.message(&format!("Cannot open {}: {}", name, e));
+            return;
+        }
+    }
+
+    if from.0 != buffers.current().path {
+        jumps.push(&from.0, from.1);
+    }
+
+    console.message(&format!("Switched to {}", buffers.current().name()));
+    console.mode = false;
+}
+
+/// :w, :w <f>
+pub fn directive_write(console: &mut EditorConsole, buffers: &mut EditorBuffers, to: Option<PathBuf>) {
+    let id = buffers.current().id;
+
+    let to = match to {
+        Some(to) => to,
+        None => {
+            if buffers.current().path.is_none() {
+                console.message("The file has no name yet, ':w <f>'");
+            } else {
+                write_current(console, buffers);
+            }
+            return;
+        }
+    };
+    let shown = display_path(console, &to);
+
+    match buffers.find(&to) {
+        Some(index) if index == buffers.current => {
+            write_current(console, buffers);
+            return;
+        }
+        Some(_) => {
+            console.message(&format!("{} is open in another buffer, close it first", shown));
+            return;
+        }
+        None => {}
+    }
+
+    if to.is_dir() {
+        console.message(&format!("{} is a directory", shown));
+    } else if fs::symlink_metadata(&to).is_ok() {
+        console.ask(ConsolePrompt::WriteFile { id, to }, &format!("{} already exists, replace it? (y/n)", shown));
+    } else {
+        write_buffer(console, buffers, id, Some(to));
+    }
+}
+
+/// Write the current buffer to its file, asking first if the file changed on the disk
+fn write_current(console: &mut EditorConsole, buffers: &mut EditorBuffers) {
+    let file = buffers.current();
+
+    if file.large.is_some() {
+        console.message(&file.readonly_message());
+    } else if file.readonly {
+        console.message(&format!("{} is read-only, ':ro' makes it writable or ':w <f>' writes a copy", file.name()));
+    } else if file.changed_on_disk() {
+        console.ask(ConsolePrompt::OverwriteChanged(file.id),
+            &format!("{} changed on the disk since it was read, write over it? (y/n)", file.name()));
+    } else {
+        write_buffer(console, buffers, file.id, None);
+    }
+}
+
+/// Write a buffer to its file, or to 'to' which then becomes its file
+fn write_buffer(console: &mut EditorConsole, buffers: &mut EditorBuffers, id: usize, to: Option<PathBuf>) {
+    let file = match buffers.index_of(id) {
+        Some(index) => &mut buffers.files[index],
+        None => {
+            console.message("The buffer was closed");
+            return;
+        }
+    };
+
+    // Only its lines on the screen are in memory (see editor_large.rs)
+    if file.large.is_some() {
+        console.message(&format!("{}, nothing written", file.readonly_message()));
+        return;
+    }
+
+    let previous = file.path.clone();
+    let readonly = file.readonly;
+    if to.is_some() {
+        file.path = to;
+        file.readonly = false; // A copy elsewhere, its own file is left alone
+    }
+
+    match file.save(console.backup) {
+        Ok(warning) => {
+            let lines = file.text.len();
+            let warning = warning.map_or_else(String::new, |w| format!(", {}", w));
+            console.message(&format!("Wrote {}, {} line{}{}", file.name(), lines, if lines == 1 { "" } else { "s" }, warning));
+        }
+        Err(e) => {
+            console.message(&format!("Cannot write {}: {}, it still has unsaved changes", file.name(), e));
+            file.path = previous;
+            file.readonly = readonly;
+        }
+    }
+}
+
+/// :i
+pub fn directive_file_info(console: &mut EditorConsole, file: &EditorFile) {
+    console.message(&format!("Info of {}", file.name()));
+    console.output.extend(file_info(file));
+}
+
+pub fn directive_toggle_readonly(console: &mut EditorConsole, file: &mut EditorFile) {
+    if file.large.is_some() {
+        console.message(&file.readonly_message());
+        return;
+    }
+
+    file.readonly = !file.readonly;
+
+    if file.readonly {
+        console.message(&format!("{} is read-only", file.name()));
+    } else if file.path.as_ref().is_some_and(|p| p.exists() && !is_writable(p)) {
+        console.message(&format!("{} can be edited, but the file is not writable, ':w <f>' writes a copy", file.name()));
+    } else {
+        console.message(&format!("{} can be edited", file.name()));
+    }
+}
+
+/// :en <e>
+pub fn directive_convert_encoding(console: &mut EditorConsole, file: &mut EditorFile, encoding: Encoding) {
+    if file.readonly {
+        console.message(&file.readonly_message());
+        return;
+    }
+
+    // Checked now rather than failing on the next ':w'
+    let text = file.text.join("\n");
+    if let Err(c) = encoding.encode(&text) {
+        console.message(&format!("'{}' cannot be written in {}, nothing converted", c, encoding.name()));
+        return;
+    }
+
+    file.format.encoding = encoding;
+    console.message(&format!("{} is now {}, ':w' writes it so", file.name(), encoding.name()));
+}
+
+/// :le <l>
+pub fn directive_convert_line_endings(console: &mut EditorConsole, file: &mut EditorFile, line_ending: LineEnding) {
+    if file.readonly {
+        console.message(&file.readonly_message());
+        return;
+    }
+
+    // The CRs a mixed file kept at the ends of its lines go with the conversion
+    let stray = file.text.iter().filter(|l| l.ends_with('\r')).count();
+    if stray > 0 {
+        file.history.record(&file.text, 0, file.text.len(), file.text.len(), file.cursor.xy, false);
+        for line in file.text.iter_mut().filter(|l| l.ends_with('\r')) {
+            line.pop();
+        }
+        file.cursor.xy.0 = file.cursor.xy.0.min(file.text[file.cursor.xy.1].chars().count());
+    }
+
+    file.format.line_ending = line_ending;
+    console.message(&format!("{} now has {} line endings, ':w' writes them", file.name(), line_ending.name()));
+}
+
+/// A session name, a plain file name
+fn session_name(console: &mut EditorConsole, param: Option<&str>) -> Option<String> {
+    let name = param.filter(|p| !p.is_empty()).unwrap_or(DEFAULT_SESSION_NAME);
+
+    if name.contains(['/', '\\']) || name.starts_with('.') {
+        console.message(&format!("'{}' cannot be a session name", name));
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// :ss, :ss <n>
+pub fn directive_save_session(console: &mut EditorConsole, param: Option<&str>) {
+    if let Some(name) = session_name(console, param) {
+        console.session_request = Some(SessionRequest::Save(name));
+    }
+}
+
+/// :os, :os <n>
+pub fn directive_open_session(console: &mut EditorConsole, param: Option<&str>) {
+    if param.is_none_or(|p| p.is_empty()) {
+        let names = session_names(&console.cwd);
+        if names.is_empty() {
+            console.message("No sessions saved in this directory, ':ss <n>' saves one");
+        } else {
+            console.message(&format!("Sessions of {}:", console.cwd.display()));
+            console.output.extend(names);
+        }
+        return;
+    }
+
+    let name = match session_name(console, param) {
+        Some(name) => name,
+        None => return
+    };
+
+    let path = named_session_path(&console.cwd, &name);
+    if path.is_file() {
+        console.session_request = Some(SessionRequest::Restore(path));
+    } else {
+        console.message(&format!("No session '{}' in this directory, ':os' lists them", name));
+    }
+}
+
+/// :epa <p>, the palette is used at once and kept in the config
+pub fn directive_palette(console: &mut EditorConsole, name: &str) {
+    match EditorPalette::load(name) {
+        Ok(palette) => set_palette(palette),
+        Err(e) => {
+            console.message(&e);
+            return;
+        }
+    }
+
+    keep_in_config(console, "palette", name, &format!("Palette {}", name));
+}
+
+/// :efn <p>, the font is loaded before the next frame is drawn and kept in the config
+pub fn directive_font(console: &mut EditorConsole, path: PathBuf) {
+    let name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    console.font_request = Some(path);
+    keep_in_config(console, "font", &name, &format!("Font {}", name));
+}
+
+/// Write a setting changed by a directive to the config, 'done' says what changed
+fn keep_in_config(console: &mut EditorConsole, key: &str, value: &str, done: &str) {
+    let path = console.config_path.clone();
+    match save_value(&path, key, value) {
+        Ok(()) => console.message(&format!("{}, kept in {}", done, path.display())),
+        Err(e) => console.message(&format!("{}, but it cannot be kept in {}: {}", done, path.display(), e))
+    }
+}
+
+/// :ekb, :ekb <t>
+pub fn directive_key_bindings(console: &mut EditorConsole, param: Option<&str>) {
+    let filter = param.unwrap_or("");
+    let bindings: Vec<String> = console.keymap.describe().into_iter().filter(|l| l.contains(filter)).collect();
+    let problems: Vec<String> = console.keymap.problems.iter().filter(|p| p.contains(filter)).cloned().collect();
+
+    if bindings.is_empty() && problems.is_empty() {
+        console.message(&format!("No key bindings containing '{}'", filter));
+        return;
+    }
+
+    console.message("Key bindings, 'key.<command> = <keys>' in cal.conf changes them:");
+    console.output.extend(bindings);
+
+    if !problems.is_empty() {
+        console.output.push(String::from("Problems:"));
+        console.output.extend(problems);
+    }
+}
+
+/// Path as shown in the console, relative to the working directory when under it
+fn display_path(console: &EditorConsole, path: &Path) -> String {
+    path.strip_prefix(&console.cwd).unwrap_or(path).display().to_string()
+}
+
+/// :c <f>
+pub fn directive_create_file(console: &mut EditorConsole, buffers: &mut EditorBuffers, jumps: &mut EditorJumpList, path: PathBuf) {
+    let shown = display_path(console, &path);
+
+    if fs::symlink_metadata(&path).is_ok() {
+        console.message(&format!("{} already exists", shown));
+        return;
+    }
+
+    // A buffer may still hold a file removed from the disk
+    if buffers.find(&path).is_some() {
+        console.message(&format!("{} is open in a buffer, close it first", shown));
+        return;
+    }
+
+    if let Some(parent) = path.parent() && !parent.is_dir() {
+        console.message(&format!("Directory {} does not exist", parent.display()));
+        return;
+    }
+
+    if let Err(e) = fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+        console.message(&format!("Cannot create {}: {}", shown, e));
+        return;
+    }
+
+    let from = (buffers.current().path.clone(), buffers.current().cursor.xy);
+    if let Err(e) = buffers.open(&path) {
+        console.message(&format!("Created {} but cannot open it: {}", shown, e));
+        return;
+    }
+
+    jumps.push(&from.0, from.1);
+    console.message(&format!("Created {}", shown));
+    console.mode = false;
+}
+
+/// :r <f>, asks first
+pub fn directive_remove_file(console: &mut EditorConsole, buffers: &mut EditorBuffers, path: PathBuf) {
+    let shown = display_path(console, &path);
+
+    match fs::symlink_metadata(&path) {
+        Ok(metadata) if metadata.is_dir() => {
+            console.message(&format!("{} is a directory, use ':rd'", shown));
+            return;
+        }
+        Ok(_) => {}
+        Err(e) => {
+            console.message(&format!("Cannot remove {}: {}", shown, e));
+            return;
+        }
+    }
+
+    let question = match buffers.find(&path) {
+        Some(index) if buffers.files[index].is_dirty() =>
+            format!("{} is open with unsaved changes, remove it and close the buffer? (y/n)", shown),
+        _ => format!("Move {} to the trash? (y/n)", shown)
+    };
+
+    console.ask(ConsolePrompt::RemoveFile(path), &question);
+}
+
+/// Move a file to the trash and close its buffer
+fn remove_file(console: &mut EditorConsole, buffers: &mut EditorBuffers, path: &Path) {
+    let shown = display_path(console, path);
+
+    // Found before the move, the path stops resolving after it
+    let open = buffers.find(path);
+
+    match move_to_trash(path) {
+        Ok(target) => {
+            if let Some(index) = open {
+                buffers.close(index);
+            }
+            console.message(&format!("Moved {} to {}", shown, target.display()));
+        }
+        Err(e) => console.message(&format!("Cannot remove {}: {}", shown, e))
+    }
+}
+
+/// :b <f>, asks first if 'f' exists
+pub fn directive_rename_file(console: &mut EditorConsole, buffers: &mut EditorBuffers, to: PathBuf) {
+    let shown = display_path(console, &to);
+
+    if buffers.find(&to).is_some_and(|index| index != buffers.current) {
+        console.message(&format!("{} is open in another buffer, close it first", shown));
+        return;
+    }
+
+    if buffers.find(&to) == Some(buffers.current) {
+        console.message(&format!("{} is already the name of the file", shown));
+        return;
+    }
+
+    if let Some(parent) = to.parent() && !parent.is_dir() {
+        console.message(&format!("Directory {} does not exist", parent.display()));
+        return;
+    }
+
+    let id = buffers.current().id;
+    match fs::symlink_metadata(&to) {
+        Ok(metadata) if metadata.is_dir() => console.message(&format!("{} is a directory", shown)),
+        Ok(_) => console.ask(ConsolePrompt::RenameFile { id, to }, &format!("{} already exists, replace it? (y/n)", shown)),
+        Err(_) => rename_buffer(console, buffers, id, &to)
+    }
+}
+
+/// Rename the file of a buffer on the disk, an unsaved file is only given the name
+fn rename_buffer(console: &mut EditorConsole, buffers: &mut EditorBuffers, id: usize, to: &Path) {
+    let index = match buffers.index_of(id) {
+        Some(index) => index,
+        None => {
+            console.message("The buffer was closed");
+            return;
+        }
+    };
+
+    let from = buffers.files[index].path.clone();
+    let name = buffers.files[index].name();
+
+    if let Some(from) = &from && fs::symlink_metadata(from).is_ok()
+        && let Err(e) = move_path(from, to) {
+        console.message(&format!("Cannot rename {}: {}", name, e));
+        return;
+    }
+
+    buffers.files[index].path = Some(to.to_path_buf());
+    console.message(&format!("Renamed {} to {}", name, display_path(console, to)));
+}
+
+/// :cd <d>, the home directory without one
+pub fn directive_change_directory(console: &mut EditorConsole, path: Option<PathBuf>) {
+    let path = path.unwrap_or_else(home_dir);
+
+    if !path.is_dir() {
+        console.message(&format!("{} is not a directory", path.display()));
+        return;
+    }
+
+    console.cwd = path.canonicalize().unwrap_or(path);
+    console.message(&format!("Directory {}", console.cwd.display()));
+}
+
+/// :md <f>
+pub fn directive_make_directory(console: &mut EditorConsole, path: PathBuf) {
+    let shown = display_path(console, &path);
+
+    if fs::symlink_metadata(&path).is_ok() {
+        console.message(&format!("{} already exists", shown));
+        return;
+    }
+
+    match fs::create_dir_all(&path) {
+        Ok(()) => console.message(&format!("Created {}/", shown)),
+        Err(e) => console.message(&format!("Cannot create {}: {}", shown, e))
+    }
+}
+
+/// :rd <f>, asks first with a summary of its contents
+pub fn directive_remove_directory(console: &mut EditorConsole, buffers: &mut EditorBuffers, path: PathBuf) {
+    let path = path.canonicalize().unwrap_or(path);
+    let shown = display_path(console, &path);
+
+    if !fs::symlink_metadata(&path).is_ok_and(|m| m.is_dir()) {
+        console.message(&format!("{} is not a directory", shown));
+        return;
+    }
+
+    if path.parent().is_none() || path == home_dir() {
+        console.message(&format!("Refusing to remove {}", path.display()));
+        return;
+    }
+
+    let summary = match summarize_directory(&path) {
+        Ok(summary) => summary,
+        Err(e) => {
+            console.message(&format!("Cannot read {}: {}", shown, e));
+            return;
+        }
+    };
+
+    let unsaved = buffers.files.iter()
+        .filter(|f| f.is_dirty() && f.path.as_ref().is_some_and(|p| p.starts_with(&path)))
+        .count();
+
+    let mut question = format!("Move {}/ to the trash? {} file{}, {} director{}, {} (y/n)", shown,
+        summary.files, if summary.files == 1 { "" } else { "s" },
+        summary.directories, if summary.directories == 1 { "y" } else { "ies" },
+        format_size(summary.bytes));
+    if unsaved > 0 {
+        question = format!("{} open file{} with unsaved changes will be closed. {}", unsaved, if unsaved == 1 { "" } else { "s" }, question);
+    }
+
+    console.ask(ConsolePrompt::RemoveDirectory(path.clone()), &question);
+
+    // What is directly inside, under the question
+    let mut entries: Vec<String> = fs::read_dir(&path).map(|entries| entries.filter_map(|e| e.ok())
+        .map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            if e.file_type().is_ok_and(|t| t.is_dir()) { format!("  {}/", name) } else { format!("  {}", name) }
+        })
+        .collect()).unwrap_or_default();
+    entries.sort();
+
+    if entries.len() > RD_SUMMARY_ENTRIES {
+        let more = entries.len() - RD_SUMMARY_ENTRIES;
+        entries.truncate(RD_SUMMARY_ENTRIES);
+        entries.push(format!("  and {} more", more));
+    }
+    console.output.extend(entries);
+}
+
+/// Move a directory to the trash, closing the buffers of the files inside
+fn remove_directory(console: &mut EditorConsole, buffers: &mut EditorBuffers, path: &Path) {
+    let shown = display_path(console, path);
+
+    if let Err(e) = move_to_trash(path) {
+        console.message(&format!("Cannot remove {}: {}", shown, e));
+        return;
+    }
+
+    while let Some(index) = buffers.files.iter().position(|f| f.path.as_ref().is_some_and(|p| p.starts_with(path))) {
+        buffers.close(index);
+    }
+
+    if console.cwd.starts_with(path) {
+        console.cwd = path.parent().map_or_else(home_dir, |p| p.to_path_buf());
+    }
+
+    console.tree = None;
+    console.message(&format!("Moved {}/ to the trash", shown));
+}
+
+/// :bd <f>, 'f' is relative to the parent of the working directory
+pub fn directive_rename_directory(console: &mut EditorConsole, buffers: &mut EditorBuffers, name: &str) {
+    let from = console.cwd.clone();
+    let parent = match from.parent() {
+        Some(parent) => parent.to_path_buf(),
+        None => {
+            console.message(&format!("Cannot rename {}", from.display()));
+            return;
+        }
+    };
+
+    let to = resolve_path(&parent, name);
+
+    if fs::symlink_metadata(&to).is_ok() {
+        console.message(&format!("{} already exists", to.display()));
+        return;
+    }
+
+    if let Err(e) = move_path(&from, &to) {
+        console.message(&format!("Cannot rename {}: {}", from.display(), e));
+        return;
+    }
+
+    // Files open from inside keep pointing at them
+    for file in buffers.files.iter_mut() {
+        if let Some(path) = &file.path && let Ok(rest) = path.strip_prefix(&from) {
+            file.path = Some(to.join(rest));
+        }
+    }
+
+    console.cwd = to.canonicalize().unwrap_or(to);
+    console.tree = None;
+    console.message(&format!("Renamed {} to {}", from.display(), console.cwd.display()));
+}
+
+/// :od <d>, the working directory without one
+pub fn directive_open_directory(console: &mut EditorConsole, path: Option<PathBuf>) {
+    let path = path.unwrap_or_else(|| console.cwd.clone());
+
+    match console.opener.open(&path) {
+        Ok(()) => console.message(&format!("Opened {}", path.display())),
+        Err(e) => console.message(&e)
+    }
+}
+
+/// :ld, directories first then files with their sizes
+pub fn directive_list_directory(console: &mut EditorConsole) {
+    let entries = match fs::read_dir(&console.cwd) {
+        Ok(entries) => entries,
+        Err(e) => {
+            console.message(&format!("Cannot read {}: {}", console.cwd.display(), e));
+            return;
+        }
+    };
+
+    let mut directories = vec![];
+    let mut files = vec![];
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => directories.push(format!("{}/", name)),
+            Ok(metadata) => files.push((name, metadata.len())),
+            Err(_) => files.push((name, 0))
+        }
+    }
+
+    directories.sort();
+    files.sort();
+
+    console.message(&format!("{}: {} directories, {} files", console.cwd.display(), directories.len(), files.len()));
+    console.output.extend(directories);
+    console.output.extend(files.into_iter().map(|(name, size)| format!("{}  {}", name, format_size(size))));
+}
+
+/// :td, Up/Down select, Enter opens a file or expands/collapses a directory,
+/// Left/Right collapse/expand
+pub fn directive_tree_directory(console: &mut EditorConsole) {
+    let mut tree = DirectoryTree::new(&console.cwd);
+    tree.expand_all(TD_MAX_ENTRIES);
+
+    console.tree = Some(tree);
+    console.grep = None;
+    console.grep_results.clear();
+    console.selected = 0;
+    console.message(&format!("Tree of {}", console.cwd.display()));
+}
+
+/// :g <re>
+pub fn directive_grep(console: &mut EditorConsole, pattern: &str) {
+    let regex = match Regex::new(pattern) {
+        Ok(regex) => regex,
+        Err(e) => {
+            console.message(&format!("Invalid regex: {}", e));
+            return;
+        }
+    };
+
+    // The matches are listed as the search finds them (see editor_grep.rs)
+    console.grep = Some(GrepSearch::start(&console.cwd, &regex));
+    console.grep_results.clear();
+    console.tree = None;
+    console.selected = 0;
+    console.message(&format!("Searching {} for '{}'", console.cwd.display(), pattern));
+}
+
+/// Parse a 1-indexed number of a ':l' argument
+fn parse_position(number: &str, what: &str) -> Result<usize, String> {
+    match number.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        Ok(_) => Err(format!("{} numbers start from 1", what)),
+        Err(_) => Err(format!("'{}' is not a {} number", number, what.to_lowercase()))
+    }
+}
+
+/// Resolve a ':l' argument to a 0-indexed line and optional column
+pub fn parse_goto_target(param: &str, current_line: usize, line_count: usize) -> Result<(usize, Option<usize>), String> {
+    if param == "$" {
+        return Ok((line_count.saturating_sub(1), None));
+    }
+
+    // Relative jumps
+    if let Some(offset) = param.strip_prefix('+') {
+        let offset = parse_position(offset, "Line")?;
+        let line = current_line + offset;
+        if line >= line_count {
+            return Err(format!("Cannot go {} lines down, only {} below", offset, line_count - 1 - current_line));
+        }
+        return Ok((line, None));
+    }
+
+    if let Some(offset) = param.strip_prefix('-') {
+        let offset = parse_position(offset, "Line")?;
+        if offset > current_line {
+            return Err(format!("Cannot go {} lines up, only {} above", offset, current_line));
+        }
+        return Ok((current_line - offset, None));
+    }
+
+    let (line, column) = match param.split_once(':') {
+        Some((line, column)) => (line, Some(column)),
+        None => (param, None)
+    };
+
+    let line = parse_position(line, "Line")?;
+    if line > line_count {
+        return Err(format!("Line {} is out of range (1-{})", line, line_count));
+    }
+
+    let column = match column {
+        Some(column) => Some(parse_position(column, "Column")? - 1),
+        None => None
+    };
+
+    Ok((line - 1, column))
+}
+
+/// :l <N>, :l <N:C>, :l <+N/-N>, :l <$>
+pub fn directive_goto_line(console: &mut EditorConsole, file: &mut EditorFile, jumps: &mut EditorJumpList,
+    (line, column): (usize, Option<usize>)) {
+    file.decode_lines(line, line + 1);
+    let line_len = file.text.get(line).map_or(0, |l| l.chars().count());
+    let column = match column {
+        Some(column) if column > line_len => {
+            console.message(&format!("Column {} is out of range (1-{})", column + 1, line_len + 1));
+            return;
+        }
+        Some(column) => column,
+        None => 0
+    };
+
+    jumps.push(&file.path, file.cursor.xy);
+    file.cursor.xy = (column, line);
+    file.center_on_line(line);
+
+    console.message(&format!("Line {}", line + 1));
+    console.mode = false;
+}
+
+/// :f <f>
+pub fn directive_find(console: &mut EditorConsole, file: &mut EditorFile, jumps: &mut EditorJumpList, needle: &str) {
+    // Searched on the disk, the cursor moves once it is found (see editor_large.rs)
+    if let Some(large) = &mut file.large {
+        large.search(needle);
+        console.message(&format!("Searching {} for '{}'", file.name(), needle));
+        console.mode = false;
+        return;
+    }
+
+    let found = file.text.iter().enumerate()
+        .find_map(|(line_index, line)| line.find(needle).map(|byte| (line[..byte].chars().count(), line_index)));
+
+    match found {
+        Some(xy) => {
+            jumps.push(&file.path, file.cursor.xy);
+            file.cursor.xy = xy;
+            file.center_on_line(xy.1);
+
+            console.message(&format!("Found at line {}", xy.1 + 1));
+            console.mode = false;
+        }
+        None => console.message(&format!("'{}' not found", needle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn goto_targets() {
+        assert_eq!(parse_goto_target("3", 0, 10), Ok((2, None)));
+        assert_eq!(parse_goto_target("3:5", 0, 10), Ok((2, Some(4))));
+        assert_eq!(parse_goto_target("+2", 4, 10), Ok((6, None)));
+        assert_eq!(parse_goto_target("-4", 4, 10), Ok((0, None)));
+        assert_eq!(parse_goto_target("$", 4, 10), Ok((9, None)));
+    }
+
+    #[test]
+    fn goto_targets_out_of_range() {
+        assert!(parse_goto_target("11", 0, 10).is_err());
+        assert!(parse_goto_target("0", 0, 10).is_err());
+        assert!(parse_goto_target("+6", 4, 10).is_err());
+        assert!(parse_goto_target("-5", 4, 10).is_err());
+        assert!(parse_goto_target("x", 0, 10).is_err());
+    }
+}
@@ -0,0 +1,452 @@
+// Encoding module, how the text of a file is stored on the disk:
+// its character encoding, line endings and whether it ends with a newline.
+//
+// A file is read as UTF-16 if it starts with a UTF-16 byte order mark,
+// as UTF-8 (with or without a BOM) if it is valid UTF-8, and with
+// 'fallback_encoding' from cal.conf otherwise (latin1 by default).
+//
+// Bytes the encoding cannot decode are kept as characters from
+// ESCAPE_BASE on, which are written back as the same bytes,
+// so reading and writing a file never changes it. A character of
+// the file that is itself in that range is kept as its escaped bytes.
+//
+// Supported encodings: utf-8, utf-8-bom, utf-16le, utf-16be,
+// latin1 (ISO-8859-1), windows-1252, windows-1253 (Greek).
+// Line endings: lf, crlf, cr.
+
+use once_cell::sync::OnceCell;
+
+use crate::editor_config::EditorConfig;
+
+const ESCAPE_BASE: u32 = 0x10FF00; // Private use, + the undecodable byte
+const UTF8_BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: [u8; 2] = [0xFF, 0xFE];
+const UTF16BE_BOM: [u8; 2] = [0xFE, 0xFF];
+
+pub const ENCODINGS: [Encoding; 7] = [
+    Encoding::Utf8, Encoding::Utf8Bom, Encoding::Utf16Le, Encoding::Utf16Be,
+    Encoding::Latin1, Encoding::Windows1252, Encoding::Windows1253
+];
+pub const LINE_ENDINGS: [LineEnding; 3] = [LineEnding::Lf, LineEnding::CrLf, LineEnding::Cr];
+
+static FALLBACK_ENCODING: OnceCell<Encoding> = OnceCell::new();
+
+// 0x80..=0x9F of windows-1252, 0 where undefined, 0xA0..=0xFF are latin1
+const WINDOWS_1252: [u16; 32] = [
+    0x20AC, 0, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039, 0x0152, 0, 0x017D, 0,
+    0, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014, 0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0, 0x017E, 0x0178
+];
+
+// 0x80..=0xBF of windows-1253, 0 where undefined,
+// 0xC0..=0xFE are U+0390 on, except the undefined 0xD2, 0xFF is undefined
+const WINDOWS_1253: [u16; 64] = [
+    0x20AC, 0, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0, 0x2030, 0, 0x2039, 0, 0, 0, 0,
+    0, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014, 0, 0x2122, 0, 0x203A, 0, 0, 0, 0,
+    0x00A0, 0x0385, 0x0386, 0x00A3, 0x00A4, 0x00A5, 0x00A6, 0x00A7, 0x00A8, 0x00A9, 0, 0x00AB, 0x00AC, 0x00AD, 0x00AE, 0x2015,
+    0x00B0, 0x00B1, 0x00B2, 0x00B3, 0x0384, 0x00B5, 0x00B6, 0x00B7, 0x0388, 0x0389, 0x038A, 0x00BB, 0x038C, 0x00BD, 0x038E, 0x038F
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le, // Always with a byte order mark
+    Utf16Be,
+    Latin1,
+    Windows1252,
+    Windows1253
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr
+}
+
+/// Everything needed to write the text back as it was read
+#[derive(Clone, Copy, PartialEq)]
+pub struct TextFormat {
+    pub encoding: Encoding,
+    pub line_ending: LineEnding,
+    pub final_newline: bool
+}
+
+impl TextFormat {
+    /// Format of new files
+    pub fn new() -> TextFormat {
+        TextFormat { encoding: Encoding::Utf8, line_ending: LineEnding::Lf, final_newline: true }
+    }
+}
+
+/// Read 'fallback_encoding' from cal.conf, once at startup
+pub fn set_fallback_encoding(config: &EditorConfig) {
+    let encoding = config.get_str("fallback_encoding").and_then(Encoding::from_name).unwrap_or(Encoding::Latin1);
+    let _ = FALLBACK_ENCODING.set(encoding);
+}
+
+fn escape(byte: u8) -> char {
+    char::from_u32(ESCAPE_BASE + byte as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// The byte an escaped character stands for
+fn unescape(c: char) -> Option<u8> {
+    let c = c as u32;
+    if (ESCAPE_BASE..ESCAPE_BASE + 0x100).contains(&c) { Some((c - ESCAPE_BASE) as u8) } else { None }
+}
+
+/// Character of a single byte encoding, None where undefined
+fn single_byte_char(encoding: Encoding, byte: u8) -> Option<char> {
+    let code = match (encoding, byte) {
+        (_, 0x00..=0x7F) | (Encoding::Latin1, _) => byte as u32,
+        (Encoding::Windows1252, 0x80..=0x9F) => WINDOWS_1252[(byte - 0x80) as usize] as u32,
+        (Encoding::Windows1252, _) => byte as u32,
+        (Encoding::Windows1253, 0x80..=0xBF) => WINDOWS_1253[(byte - 0x80) as usize] as u32,
+        (Encoding::Windows1253, 0xD2) | (Encoding::Windows1253, 0xFF) => 0,
+        (Encoding::Windows1253, _) => 0x0390 + (byte - 0xC0) as u32,
+        _ => 0
+    };
+
+    if code == 0 && byte != 0 { None } else { char::from_u32(code) }
+}
+
+/// Push a decoded character, escaping the bytes of one taken for an escape
+fn push_decoded(c: char, bytes: &[u8], text: &mut String) {
+    if unescape(c).is_some() {
+        text.extend(bytes.iter().map(|&b| escape(b)));
+    } else {
+        text.push(c);
+    }
+}
+
+fn decode_utf8(bytes: &[u8], text: &mut String) {
+    for chunk in bytes.utf8_chunks() {
+        let valid = chunk.valid();
+        if valid.contains(|c| unescape(c).is_some()) {
+            let mut buffer = [0u8; 4];
+            for c in valid.chars() {
+                push_decoded(c, c.encode_utf8(&mut buffer).as_bytes(), text);
+            }
+        } else {
+            text.push_str(valid);
+        }
+        text.extend(chunk.invalid().iter().map(|&b| escape(b)));
+    }
+}
+
+fn decode_utf16(bytes: &[u8], big_endian: bool, text: &mut String) {
+    let units = bytes.chunks_exact(2)
+        .map(|p| if big_endian { u16::from_be_bytes([p[0], p[1]]) } else { u16::from_le_bytes([p[0], p[1]]) });
+
+    let mut units_of = [0u16; 2];
+    for result in char::decode_utf16(units) {
+        match result {
+            Ok(c) if unescape(c).is_some() => {
+                let bytes: Vec<u8> = c.encode_utf16(&mut units_of).iter()
+                    .flat_map(|u| if big_endian { u.to_be_bytes() } else { u.to_le_bytes() }).collect();
+                push_decoded(c, &bytes, text);
+            }
+            Ok(c) => text.push(c),
+            Err(e) => {
+                let bytes = if big_endian { e.unpaired_surrogate().to_be_bytes() } else { e.unpaired_surrogate().to_le_bytes() };
+                text.extend(bytes.iter().map(|&b| escape(b)));
+            }
+        }
+    }
+
+    if bytes.len() % 2 == 1 {
+        text.push(escape(bytes[bytes.len() - 1]));
+    }
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Utf8Bom => "utf-8-bom",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+            Encoding::Latin1 => "latin1",
+            Encoding::Windows1252 => "windows-1252",
+            Encoding::Windows1253 => "windows-1253"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        match name.to_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Some(Encoding::Utf8),
+            "utf-8-bom" | "utf8-bom" => Some(Encoding::Utf8Bom),
+            "utf-16le" | "utf-16" | "utf16le" => Some(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Encoding::Utf16Be),
+            "latin1" | "latin-1" | "iso-8859-1" => Some(Encoding::Latin1),
+            "windows-1252" | "cp1252" => Some(Encoding::Windows1252),
+            "windows-1253" | "cp1253" | "greek" => Some(Encoding::Windows1253),
+            _ => None
+        }
+    }
+
+    /// Encoding of the bytes of a file, see the top of this file
+    pub fn detect(bytes: &[u8]) -> Encoding {
+        if bytes.starts_with(&UTF8_BOM) {
+            Encoding::Utf8Bom
+        } else if bytes.starts_with(&UTF16LE_BOM) {
+            Encoding::Utf16Le
+        } else if bytes.starts_with(&UTF16BE_BOM) {
+            Encoding::Utf16Be
+        } else if std::str::from_utf8(bytes).is_ok() {
+            Encoding::Utf8
+        } else {
+            *FALLBACK_ENCODING.get().unwrap_or(&Encoding::Latin1)
+        }
+    }
+
+    /// Decode the bytes of a file, its byte order mark included
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let bom: &[u8] = match self {
+            Encoding::Utf8Bom => &UTF8_BOM,
+            Encoding::Utf16Le => &UTF16LE_BOM,
+            Encoding::Utf16Be => &UTF16BE_BOM,
+            _ => &[]
+        };
+
+        self.decode_part(bytes.strip_prefix(bom).unwrap_or(bytes))
+    }
+
+    /// Decode a part of a file after its byte order mark
+    pub fn decode_part(self, bytes: &[u8]) -> String {
+        let mut text = String::with_capacity(bytes.len());
+
+        match self {
+            Encoding::Utf8 | Encoding::Utf8Bom => decode_utf8(bytes, &mut text),
+            Encoding::Utf16Le => decode_utf16(bytes, false, &mut text),
+            Encoding::Utf16Be => decode_utf16(bytes, true, &mut text),
+            _ => text.extend(bytes.iter().map(|&b| single_byte_char(self, b).unwrap_or_else(|| escape(b))))
+        }
+
+        text
+    }
+
+    /// Length of the byte order mark written at the start of a file
+    pub fn bom_len(self) -> usize {
+        match self {
+            Encoding::Utf8Bom => UTF8_BOM.len(),
+            Encoding::Utf16Le | Encoding::Utf16Be => UTF16LE_BOM.len(),
+            _ => 0
+        }
+    }
+
+    /// Bytes a text takes once encoded, without the byte order mark
+    pub fn byte_len(self, text: &str) -> usize {
+        text.chars().map(|c| match (self, unescape(c)) {
+            (_, Some(_)) => 1,
+            (Encoding::Utf8 | Encoding::Utf8Bom, None) => c.len_utf8(),
+            (Encoding::Utf16Le | Encoding::Utf16Be, None) => 2 * c.len_utf16(),
+            _ => 1
+        }).sum()
+    }
+
+    /// Encode a text, with the byte order mark of the encoding.
+    /// Fails with the first character the encoding has no bytes for.
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, char> {
+        let mut bytes = Vec::with_capacity(text.len());
+
+        match self {
+            Encoding::Utf8Bom => bytes.extend_from_slice(&UTF8_BOM),
+            Encoding::Utf16Le => bytes.extend_from_slice(&UTF16LE_BOM),
+            Encoding::Utf16Be => bytes.extend_from_slice(&UTF16BE_BOM),
+            _ => {}
+        }
+
+        let mut units = [0u16; 2];
+        for c in text.chars() {
+            if let Some(byte) = unescape(c) {
+                bytes.push(byte);
+                continue;
+            }
+
+            match self {
+                Encoding::Utf8 | Encoding::Utf8Bom => {
+                    let mut buffer = [0u8; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                }
+                Encoding::Utf16Le | Encoding::Utf16Be => {
+                    for unit in c.encode_utf16(&mut units) {
+                        let pair = if self == Encoding::Utf16Le { unit.to_le_bytes() } else { unit.to_be_bytes() };
+                        bytes.extend_from_slice(&pair);
+                    }
+                }
+                _ => {
+                    let byte = (0..=255u8).find(|&b| single_byte_char(self, b) == Some(c)).ok_or(c)?;
+                    bytes.push(byte);
+                }
+            }
+        }
+
+        Ok(bytes)
+    }
+}
+
+impl LineEnding {
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "lf",
+            LineEnding::CrLf => "crlf",
+            LineEnding::Cr => "cr"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<LineEnding> {
+        match name.to_lowercase().as_str() {
+            "lf" | "unix" => Some(LineEnding::Lf),
+            "crlf" | "dos" | "windows" => Some(LineEnding::CrLf),
+            "cr" | "mac" => Some(LineEnding::Cr),
+            _ => None
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r"
+        }
+    }
+
+    /// CRLF if every line break is one, CR if there are only CRs, LF otherwise.
+    /// With LF, the CR of a CRLF in a mixed file stays at the end of its line.
+    pub fn detect(text: &str) -> LineEnding {
+        let lf = text.matches('\n').count();
+        let crlf = text.matches("\r\n").count();
+
+        if lf > 0 && lf == crlf {
+            LineEnding::CrLf
+        } else if lf == 0 && text.contains('\r') {
+            LineEnding::Cr
+        } else {
+            LineEnding::Lf
+        }
+    }
+}
+
+/// Split the bytes of a file into lines and the format to write them back with
+pub fn decode_file(bytes: &[u8]) -> (Vec<String>, TextFormat) {
+    let encoding = Encoding::detect(bytes);
+    let text = encoding.decode(bytes);
+    let line_ending = LineEnding::detect(&text);
+
+    let separator = line_ending.as_str();
+    let final_newline = text.ends_with(separator);
+    let body = text.strip_suffix(separator).unwrap_or(&text);
+
+    let lines = body.split(separator).map(|l| l.to_string()).collect();
+
+    (lines, TextFormat { encoding, line_ending, final_newline })
+}
+
+/// The bytes of a file from its lines, fails with a character the encoding cannot write
+pub fn encode_file(lines: &[String], format: TextFormat) -> Result<Vec<u8>, char> {
+    let separator = format.line_ending.as_str();
+
+    let mut text = lines.join(separator);
+    if format.final_newline {
+        text.push_str(separator);
+    }
+
+    format.encoding.encode(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decoded and encoded again, the bytes must come back unchanged
+    fn round_trip(bytes: &[u8]) -> (Vec<String>, TextFormat) {
+        let (lines, format) = decode_file(bytes);
+        assert_eq!(encode_file(&lines, format).ok().as_deref(), Some(bytes));
+        (lines, format)
+    }
+
+    #[test]
+    fn invalid_utf8_is_kept() {
+        let bytes = b"ok \xC3\x28 and \xFF\n\xE2\x82\n";
+        let (lines, format) = round_trip(bytes);
+        assert!(format.encoding == Encoding::Latin1);
+        assert_eq!(lines.len(), 2);
+    }
+
+    #[test]
+    fn utf8_in_the_escape_range_is_kept() {
+        let mut text = String::from("a\u{10FF41}b\u{10FFFF}");
+        text.push(escape(0x80));
+        let bytes = text.as_bytes();
+
+        let decoded = Encoding::Utf8.decode(bytes);
+        assert_eq!(decoded.chars().count(), 2 + 3 * 4);
+        assert_eq!(Encoding::Utf8.encode(&decoded).ok().as_deref(), Some(bytes));
+        assert_eq!(Encoding::Utf8.byte_len(&decoded), bytes.len());
+    }
+
+    #[test]
+    fn utf16_in_the_escape_range_is_kept() {
+        let mut bytes = UTF16LE_BOM.to_vec();
+        for unit in "x\u{10FF00}".encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+
+        let decoded = Encoding::Utf16Le.decode(&bytes);
+        assert_eq!(Encoding::Utf16Le.encode(&decoded).ok(), Some(bytes));
+    }
+
+    #[test]
+    fn latin1_is_kept() {
+        let bytes: Vec<u8> = (0x20..=0xFF).collect();
+        let (lines, format) = round_trip(&bytes);
+        assert!(format.encoding == Encoding::Latin1 && !format.final_newline);
+        assert!(lines[0].ends_with('\u{FF}'));
+    }
+
+    #[test]
+    fn windows_1253_undefined_bytes_are_kept() {
+        let bytes = [b'a', 0xE1, 0xAA, 0xD2, 0xFF, b'\n'];
+        let text = Encoding::Windows1253.decode(&bytes);
+
+        assert!(text.starts_with("a\u{03B1}"));
+        assert_eq!(text.chars().skip(2).take(3).filter_map(unescape).collect::<Vec<u8>>(), [0xAA, 0xD2, 0xFF]);
+        assert_eq!(Encoding::Windows1253.encode(&text).ok().as_deref(), Some(&bytes[..]));
+    }
+
+    #[test]
+    fn utf16_odd_trailing_byte_is_kept() {
+        let bytes = [0xFF, 0xFE, b'h', 0, b'i', 0, b'\n', 0, 0x41];
+        let (lines, format) = round_trip(&bytes);
+        assert!(format.encoding == Encoding::Utf16Le);
+        assert_eq!(lines[0], "hi");
+    }
+
+    #[test]
+    fn line_endings_are_kept() {
+        let (lines, format) = round_trip(b"one\r\ntwo\r\n");
+        assert!(format.line_ending == LineEnding::CrLf && format.final_newline);
+        assert_eq!(lines, ["one", "two"]);
+
+        let (lines, format) = round_trip(b"one\rtwo\r");
+        assert!(format.line_ending == LineEnding::Cr);
+        assert_eq!(lines, ["one", "two"]);
+
+        // Mixed, the CR stays at the end of its line
+        let (lines, format) = round_trip(b"one\r\ntwo\nthree\r");
+        assert!(format.line_ending == LineEnding::Lf && !format.final_newline);
+        assert_eq!(lines, ["one\r", "two", "three\r"]);
+    }
+
+    #[test]
+    fn missing_final_newline_is_kept() {
+        let (lines, format) = round_trip(b"one\ntwo");
+        assert!(!format.final_newline);
+        assert_eq!(lines, ["one", "two"]);
+
+        let (lines, format) = round_trip(b"");
+        assert!(!format.final_newline);
+        assert_eq!(lines, [""]);
+    }
+}
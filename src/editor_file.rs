@@ -0,0 +1,232 @@
+// File module, a single open file (buffer)
+// and reading it from the disk, in the format it is stored in (see editor_encoding).
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+use crate::editor_cursor::EditorCursor;
+use crate::editor_encoding::{TextFormat, decode_file, encode_file};
+use crate::editor_fs::{BackupMode, write_atomic};
+use crate::editor_history::EditorHistory;
+use crate::editor_large::{LargeFileLoader, LargeText, is_large_file};
+
+static NEXT_FILE_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// What the file looked like on the disk, to notice changes made outside the editor
+#[derive(Clone, Copy, PartialEq)]
+pub struct DiskStamp {
+    modified: SystemTime,
+    len: u64
+}
+
+impl DiskStamp {
+    /// Stamp of the file at 'path', None if it cannot be read
+    pub fn of(path: &Path) -> Option<DiskStamp> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(DiskStamp { modified: metadata.modified().ok()?, len: metadata.len() })
+    }
+}
+
+pub struct EditorFile {
+    pub id: usize, // Stays the same while the file is open, unlike its index
+    pub path: Option<PathBuf>,
+    pub text: Vec<String>,
+    pub cursor: EditorCursor,
+    pub scroll: usize,     // First visible line
+    pub view_lines: usize, // Lines that fit in the pane showing it
+    pub history: EditorHistory,
+    pub saved_at: Option<SystemTime>, // Last write by the editor
+    pub disk: Option<DiskStamp>,      // The file on the disk when last read or written
+    pub kept_over: Option<DiskStamp>, // A change on the disk the user chose to keep ours over
+    pub format: TextFormat,           // Encoding and line endings it is written with
+    pub saved_format: TextFormat,     // The format on the disk, converting it is an unsaved change
+    pub large: Option<LargeText>,     // The line index of a file opened in large file mode (see editor_large)
+    pub readonly: bool,               // Edits and ':w' are refused
+    pub loading: Option<LargeFileLoader>
+}
+
+impl EditorFile {
+    /// File constructor, an empty unnamed file
+    pub fn new() -> EditorFile {
+        EditorFile {
+            id: NEXT_FILE_ID.fetch_add(1, Ordering::Relaxed),
+            path: None,
+            text: vec![String::new()],
+            cursor: EditorCursor::new(),
+            scroll: 0,
+            view_lines: 1,
+            history: EditorHistory::new(),
+            saved_at: None,
+            disk: None,
+            kept_over: None,
+            format: TextFormat::new(),
+            saved_format: TextFormat::new(),
+            large: None,
+            readonly: false,
+            loading: None
+        }
+    }
+
+    /// Read the file at 'path'
+    pub fn load(path: &Path) -> io::Result<EditorFile> {
+        let mut file = EditorFile::new();
+
+        if is_large_file(path) {
+            fs::File::open(path)?; // Errors now rather than in the loading thread
+            file.large = Some(LargeText::new(path));
+            file.loading = Some(LargeFileLoader::start(path));
+        } else {
+            (file.text, file.format) = read_file_lines(path)?;
+            file.saved_format = file.format;
+        }
+
+        file.path = Some(path.to_path_buf());
+        file.readonly = file.large.is_some() || !is_writable(path);
+        file.history.mark_saved();
+        file.disk = DiskStamp::of(path);
+
+        Ok(file)
+    }
+
+    /// Write the text to its path, the buffer stays dirty if that fails.
+    /// Gives what of the original file could not be kept, see editor_fs
+    pub fn save(&mut self, backup: BackupMode) -> io::Result<Option<String>> {
+        let path = self.path.clone()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the file has no name"))?;
+        if self.large.is_some() {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "a large file is opened read-only"));
+        }
+        if self.readonly {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "the buffer is read-only"));
+        }
+
+        let content = encode_file(&self.text, self.format).map_err(|c| io::Error::new(io::ErrorKind::InvalidData,
+            format!("'{}' cannot be written in {}, ':en utf-8' converts the file", c, self.format.encoding.name())))?;
+
+        let warning = write_atomic(&path, &content, backup)?;
+
+        self.history.mark_saved();
+        self.saved_format = self.format;
+        self.saved_at = Some(SystemTime::now());
+        self.disk = DiskStamp::of(&path);
+        Ok(warning)
+    }
+
+    /// Read the file again, as an edit that can be undone.
+    /// A large file is indexed anew instead.
+    pub fn reload(&mut self) -> io::Result<()> {
+        let path = self.path.clone()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the file has no name"))?;
+
+        if self.large.is_some() {
+            fs::File::open(&path)?;
+            self.text = vec![String::new()];
+            self.large = Some(LargeText::new(&path));
+            self.loading = Some(LargeFileLoader::start(&path));
+            self.disk = DiskStamp::of(&path);
+            self.cursor.xy = (0, 0);
+            return Ok(());
+        }
+        let (text, format) = read_file_lines(&path)?;
+
+        self.history.record(&self.text, 0, self.text.len(), text.len(), self.cursor.xy, false);
+        self.text = text;
+        self.history.mark_saved();
+        self.format = format;
+        self.saved_format = format;
+        self.disk = DiskStamp::of(&path);
+
+        let y = self.cursor.xy.1.min(self.text.len() - 1);
+        self.cursor.xy = (self.cursor.xy.0.min(self.text[y].chars().count()), y);
+        Ok(())
+    }
+
+    /// The file on the disk is not the one last read or written
+    pub fn changed_on_disk(&self) -> bool {
+        match (&self.path, &self.disk) {
+            (Some(path), Some(disk)) => DiskStamp::of(path).is_some_and(|now| now != *disk),
+            _ => false
+        }
+    }
+
+    /// Why edits are refused, for the messages of a read-only buffer
+    pub fn readonly_message(&self) -> String {
+        if self.large.is_some() {
+            format!("{} is a large file, opened read-only", self.name())
+        } else {
+            format!("{} is read-only, ':ro' makes it writable", self.name())
+        }
+    }
+
+    /// Decode the lines from 'start' to 'end' of a large file before they are used,
+    /// the lines of other files always are
+    pub fn decode_lines(&mut self, start: usize, end: usize) {
+        if let Some(large) = &mut self.large {
+            large.decode(&mut self.text, self.format, start, end);
+        }
+    }
+
+    /// Name shown to the user, the file name or "untitled"
+    pub fn name(&self) -> String {
+        match &self.path {
+            Some(path) => path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().to_string()),
+            None => String::from("untitled")
+        }
+    }
+
+    /// Has unsaved changes
+    pub fn is_dirty(&self) -> bool {
+        self.history.is_dirty() || self.format != self.saved_format
+    }
+
+    /// Unnamed and never edited, can be replaced by an opened file
+    pub fn is_pristine(&self) -> bool {
+        self.path.is_none() && !self.is_dirty() && self.text.len() <= 1 && self.text.first().is_none_or(|l| l.is_empty())
+    }
+
+    /// Place the cursor at 'line' and 'column', or as close as the text allows,
+    /// in the middle of the view. A large file still loading goes there
+    /// once the line is indexed (see editor_large.rs)
+    pub fn goto(&mut self, line: usize, column: usize) {
+        if let Some(large) = &mut self.large && self.loading.is_some() && line + 1 >= self.text.len() {
+            large.target = Some((column, line));
+            return;
+        }
+
+        let y = line.min(self.text.len().saturating_sub(1));
+        self.decode_lines(y, y + 1);
+        let x = column.min(self.text.get(y).map_or(0, |l| l.chars().count()));
+        self.cursor.xy = (x, y);
+        self.center_on_line(y);
+    }
+
+    /// Scroll so that 'line' is in the middle of the view
+    pub fn center_on_line(&mut self, line: usize) {
+        self.scroll = line.saturating_sub(self.view_lines / 2);
+    }
+
+    /// Scroll just enough for the cursor to be in view
+    pub fn scroll_to_cursor(&mut self) {
+        let line = self.cursor.xy.1;
+
+        if line < self.scroll {
+            self.scroll = line;
+        } else if line >= self.scroll + self.view_lines {
+            self.scroll = line + 1 - self.view_lines;
+        }
+    }
+}
+
+/// The file at 'path' can be opened for writing by this user
+pub fn is_writable(path: &Path) -> bool {
+    fs::OpenOptions::new().write(true).open(path).is_ok()
+}
+
+/// Read a file into lines and the format they are stored in
+pub fn read_file_lines(path: &Path) -> io::Result<(Vec<String>, TextFormat)> {
+    let bytes = fs::read(path)?;
+    Ok(decode_file(&bytes))
+}
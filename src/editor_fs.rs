@@ -0,0 +1,335 @@
+// Filesystem module, the file operations behind the file and directory directives.
+//
+// Files are written atomically: the new content goes to a temporary file
+// in the same directory, which is synced and then renamed over the original,
+// so a crash or a full disk leaves the original untouched. The permissions
+// and owner of the original are kept and symlinks are written through,
+// elsewhere than on unix only the permissions, the read-only flag.
+// When the directory allows no temporary file, or the owner cannot be
+// given to it (a group-writable file of another user), the file is
+// truncated and written in place instead and the caller gets a warning.
+// 'backup' in cal.conf keeps the previous content as 'name~' (tilde)
+// or 'name.YYYYMMDD-HHMMSS~' (timestamp), none by default.
+//
+// Removed files are not deleted but moved to a local trash directory
+// in the state directory, 'trash/info' records where each one came from
+// so it can be put back by hand.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::SystemTime;
+
+use crate::editor_config::{EditorConfig, state_dir};
+use crate::editor_info::civil_time;
+
+const TRASH_DIR: &str = "trash";
+const TRASH_INFO: &str = "info";
+const SIZE_UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+#[cfg(unix)]
+const NEW_FILE_MODE: u32 = 0o644;
+const MAX_SYMLINK_DEPTH: usize = 40;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BackupMode {
+    None,
+    Tilde,     // 'name~'
+    Timestamp  // 'name.YYYYMMDD-HHMMSS~'
+}
+
+impl BackupMode {
+    /// 'backup' in cal.conf, none when missing or unknown
+    pub fn from_config(config: &EditorConfig) -> BackupMode {
+        match config.get_str("backup") {
+            Some("tilde") | Some("~") => BackupMode::Tilde,
+            Some("timestamp") => BackupMode::Timestamp,
+            _ => BackupMode::None
+        }
+    }
+}
+
+pub fn trash_dir() -> PathBuf {
+    state_dir().join(TRASH_DIR)
+}
+
+/// Copy a file or a whole directory
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    if !fs::symlink_metadata(from)?.is_dir() {
+        return fs::copy(from, to).map(|_| ());
+    }
+
+    fs::create_dir(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+    }
+
+    Ok(())
+}
+
+/// Rename 'from' to 'to', copying and removing it when they are on different filesystems
+pub fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            copy_recursive(from, to)?;
+
+            if fs::symlink_metadata(from)?.is_dir() {
+                fs::remove_dir_all(from)
+            } else {
+                fs::remove_file(from)
+            }
+        }
+        result => result
+    }
+}
+
+/// Move a file or directory to the trash, returning where it went
+pub fn move_to_trash(path: &Path) -> io::Result<PathBuf> {
+    let name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?
+        .to_string_lossy()
+        .to_string();
+    let original = std::path::absolute(path)?;
+
+    let dir = trash_dir();
+    fs::create_dir_all(&dir)?;
+
+    // Earlier removals of the same name are kept as 'name.1', 'name.2', ...
+    let mut target = dir.join(&name);
+    let mut n = 1;
+    while fs::symlink_metadata(&target).is_ok() || target == dir.join(TRASH_INFO) {
+        target = dir.join(format!("{}.{}", name, n));
+        n += 1;
+    }
+
+    move_path(path, &target)?;
+
+    let mut info = fs::OpenOptions::new().create(true).append(true).open(dir.join(TRASH_INFO))?;
+    writeln!(info, "{}\t{}", target.file_name().unwrap_or_default().to_string_lossy(), original.display())?;
+
+    Ok(target)
+}
+
+/// What a directory holds, counted recursively
+pub struct DirectorySummary {
+    pub files: usize,
+    pub directories: usize,
+    pub bytes: u64
+}
+
+/// Count everything under 'path', symlinks are counted but not followed
+pub fn summarize_directory(path: &Path) -> io::Result<DirectorySummary> {
+    let mut summary = DirectorySummary { files: 0, directories: 0, bytes: 0 };
+
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            let inner = summarize_directory(&entry.path())?;
+            summary.files += inner.files;
+            summary.directories += inner.directories + 1;
+            summary.bytes += inner.bytes;
+        } else {
+            summary.files += 1;
+            summary.bytes += metadata.len();
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Size in the largest unit that keeps it above 1, '1.5 KB'
+pub fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < SIZE_UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, SIZE_UNITS[unit])
+}
+
+/// The file 'path' finally points to, following symlinks even to a missing target
+fn resolve_symlinks(path: &Path) -> io::Result<PathBuf> {
+    let mut path = path.to_path_buf();
+
+    for _ in 0..MAX_SYMLINK_DEPTH {
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                let target = fs::read_link(&path)?;
+                path = match path.parent() {
+                    Some(parent) => parent.join(target),
+                    None => target
+                };
+            }
+            _ => return Ok(path)
+        }
+    }
+
+    Err(io::Error::other("too many levels of symbolic links"))
+}
+
+/// Where the previous content of 'path' is kept
+fn backup_path(path: &Path, mode: BackupMode) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+
+    match mode {
+        BackupMode::None => None,
+        BackupMode::Tilde => Some(path.with_file_name(format!("{}~", name))),
+        BackupMode::Timestamp => {
+            let (year, month, day, hour, minute, second) = civil_time(SystemTime::now());
+            Some(path.with_file_name(format!("{}.{:04}{:02}{:02}-{:02}{:02}{:02}~", name, year, month, day, hour, minute, second)))
+        }
+    }
+}
+
+
+/// Create the temporary file of an atomic write, with the mode of the original on unix
+#[cfg(unix)]
+fn create_temp(temp: &Path, original: Option<&fs::Metadata>) -> io::Result<fs::File> {
+    use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+
+    let mode = original.map_or(NEW_FILE_MODE, |m| m.mode());
+    fs::OpenOptions::new().write(true).create_new(true).mode(mode).open(temp)
+}
+
+#[cfg(not(unix))]
+fn create_temp(temp: &Path, _original: Option<&fs::Metadata>) -> io::Result<fs::File> {
+    fs::OpenOptions::new().write(true).create_new(true).open(temp)
+}
+
+/// Give the temporary file the permissions of the original
+fn keep_mode(file: &fs::File, original: &fs::Metadata) -> io::Result<()> {
+    // On unix the mode given on creation is cut by the umask
+    file.set_permissions(original.permissions())
+}
+
+/// Give the temporary file the owner of the original, only root or
+/// the owner in the group of the file can
+#[cfg(unix)]
+fn keep_owner(file: &fs::File, original: &fs::Metadata) -> io::Result<()> {
+    use std::os::unix::fs::{MetadataExt, fchown};
+
+    let created = file.metadata()?;
+    if (original.uid(), original.gid()) != (created.uid(), created.gid()) {
+        fchown(file, Some(original.uid()), Some(original.gid()))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn keep_owner(_file: &fs::File, _original: &fs::Metadata) -> io::Result<()> {
+    Ok(())
+}
+
+/// The rename of an atomic write is only durable once the directory is synced
+#[cfg(unix)]
+fn sync_directory(dir: &Path) -> io::Result<()> {
+    fs::File::open(dir)?.sync_all()
+}
+
+/// Directories cannot be opened as files here, the rename is left to the system
+#[cfg(not(unix))]
+fn sync_directory(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Why the temporary file of an atomic write could not replace the original
+enum TempFailure {
+    Create(io::Error),         // The directory does not let us create it
+    Owner(PathBuf, io::Error), // Written, but without the owner of the original
+    Other(io::Error)
+}
+
+/// Write 'content' to a temporary file next to 'path' with the permissions
+/// and owner of the original, the file is left behind when only the owner is missing
+fn write_temp(dir: &Path, name: &str, content: &[u8], original: Option<&fs::Metadata>) -> Result<PathBuf, TempFailure> {
+    let temp = dir.join(format!(".{}.muse-{}.tmp", name, process::id()));
+    let mut file = create_temp(&temp, original).map_err(TempFailure::Create)?;
+
+    let result = (|| {
+        file.write_all(content)?;
+        if let Some(original) = original {
+            keep_mode(&file, original)?;
+        }
+        file.sync_all()
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(TempFailure::Other(e));
+    }
+
+    if let Some(original) = original && let Err(e) = keep_owner(&file, original) {
+        return Err(TempFailure::Owner(temp, e));
+    }
+
+    Ok(temp)
+}
+
+/// Put the temporary file in place of 'path'
+fn replace_with(temp: &Path, path: &Path, dir: &Path) -> io::Result<()> {
+    let result = fs::rename(temp, path).and_then(|_| sync_directory(dir));
+    if result.is_err() {
+        let _ = fs::remove_file(temp);
+    }
+    result
+}
+
+/// Truncate 'path' and write 'content' into it, keeping the file itself with its
+/// owner and permissions, a crash in the middle leaves it half written
+fn write_in_place(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new().write(true).create(true).truncate(true).open(path)?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+/// Write 'content' to 'path' without ever leaving it half written,
+/// see the top of this file. What could not be kept is returned as a warning
+pub fn write_atomic(path: &Path, content: &[u8], backup: BackupMode) -> io::Result<Option<String>> {
+    let path = resolve_symlinks(path)?;
+    let name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?
+        .to_string_lossy()
+        .to_string();
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from(".")
+    };
+
+    let original = match fs::metadata(&path) {
+        Ok(metadata) => Some(metadata),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e)
+    };
+
+    if original.is_some() && let Some(backup) = backup_path(&path, backup) {
+        fs::copy(&path, &backup)?;
+    }
+
+    match write_temp(&dir, &name, content, original.as_ref()) {
+        Ok(temp) => replace_with(&temp, &path, &dir).map(|_| None),
+        Err(TempFailure::Create(e)) => {
+            write_in_place(&path, content).map_err(|_| e)?;
+            Ok(Some(String::from("written in place, the directory allows no temporary file")))
+        }
+        // Writing in place keeps the owner, else the file is written without it
+        Err(TempFailure::Owner(temp, e)) => match write_in_place(&path, content) {
+            Ok(()) => {
+                let _ = fs::remove_file(&temp);
+                Ok(Some(String::from("written in place to keep its owner")))
+            }
+            Err(_) => {
+                replace_with(&temp, &path, &dir)?;
+                Ok(Some(format!("its owner could not be kept: {}", e)))
+            }
+        },
+        Err(TempFailure::Other(e)) => Err(e)
+    }
+}
@@ -0,0 +1,114 @@
+// Fuzzy filename matching module, used by the console to show
+// files with names close to what is being typed.
+//
+// A file matches if the typed characters appear in its path in order.
+// Matches at the start of a path segment or word, inside the file name
+// and right after the previous match score higher.
+// Recently opened files that match are ranked before all others.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::editor_grep::walk_files;
+
+const FUZZY_MAX_FILES: usize = 20000;
+
+const SCORE_MATCH: i32 = 1;
+const BONUS_CONSECUTIVE: i32 = 5;
+const BONUS_SEGMENT_START: i32 = 8; // After a '/'
+const BONUS_WORD_START: i32 = 4;    // After '_', '-', '.' or a lower to upper case change
+const BONUS_FILE_NAME: i32 = 2;     // Inside the last segment
+const PENALTY_GAP: i32 = 1;         // Per skipped character, up to PENALTY_GAP_MAX
+const PENALTY_GAP_MAX: i32 = 3;
+
+/// Greedy in-order match of 'query' starting at 'from'
+fn score_from(query: &[char], chars: &[char], name_start: usize, from: usize) -> Option<i32> {
+    let mut score = 0;
+    let mut q = 0;
+    let mut last_match: Option<usize> = None;
+
+    for (i, &c) in chars.iter().enumerate().skip(from) {
+        if q == query.len() {
+            break;
+        }
+
+        if !c.to_lowercase().eq(std::iter::once(query[q])) {
+            continue;
+        }
+
+        score += SCORE_MATCH;
+
+        let previous = if i > 0 { Some(chars[i - 1]) } else { None };
+        match previous {
+            None | Some('/') => score += BONUS_SEGMENT_START,
+            Some('_') | Some('-') | Some('.') | Some(' ') => score += BONUS_WORD_START,
+            Some(p) if p.is_lowercase() && c.is_uppercase() => score += BONUS_WORD_START,
+            _ => {}
+        }
+
+        if i >= name_start {
+            score += BONUS_FILE_NAME;
+        }
+
+        match last_match {
+            Some(last) if last + 1 == i => score += BONUS_CONSECUTIVE,
+            Some(last) => score -= (PENALTY_GAP * (i - last - 1) as i32).min(PENALTY_GAP_MAX),
+            None => {}
+        }
+
+        last_match = Some(i);
+        q += 1;
+    }
+
+    if q < query.len() { None } else { Some(score) }
+}
+
+/// Score of 'candidate' for 'query', None if it is not a subsequence
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let query: Vec<char> = query.chars().flat_map(|c| c.to_lowercase()).filter(|c| !c.is_whitespace()).collect();
+    let chars: Vec<char> = candidate.chars().collect();
+
+    if query.is_empty() {
+        return Some(-(chars.len() as i32));
+    }
+
+    let name_start = candidate.rfind('/').map_or(0, |b| candidate[..b].chars().count() + 1);
+
+    // Try every segment or word start,
+    // so that 'main' prefers 'src/main.rs' over the 'main' inside 'domain/'
+    let best = (0..chars.len())
+        .filter(|&i| i == 0 || matches!(chars[i - 1], '/' | '_' | '-' | '.'))
+        .filter_map(|i| score_from(&query, &chars, name_start, i))
+        .max()?;
+
+    // Shorter paths win ties
+    Some(best * 100 - chars.len() as i32)
+}
+
+/// Paths of every file under 'root', relative to it
+pub fn list_files(root: &Path) -> Vec<String> {
+    let mut files = vec![];
+
+    walk_files(root, &mut |path| {
+        if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_string_lossy().replace('\\', "/"));
+        }
+        files.len() < FUZZY_MAX_FILES
+    });
+
+    files
+}
+
+/// The best 'limit' matches for 'query', best first,
+/// the matches among 'recent' come before the others
+pub fn fuzzy_rank(query: &str, files: &[String], recent: &[String], limit: usize) -> Vec<String> {
+    let recent_set: HashSet<&String> = recent.iter().collect();
+    let others = files.iter().filter(|f| !recent_set.contains(f));
+    let mut scored: Vec<(bool, i32, &String)> = recent.iter().map(|f| (true, f)).chain(others.map(|f| (false, f)))
+        .filter_map(|(is_recent, f)| fuzzy_score(query, f).map(|score| (is_recent, score, f)))
+        .collect();
+
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| b.1.cmp(&a.1)).then_with(|| a.2.cmp(b.2)));
+
+    scored.into_iter().take(limit).map(|(_, _, f)| f.clone()).collect()
+}
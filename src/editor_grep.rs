@@ -0,0 +1,378 @@
+// Project-wide search module, used by the ':g' directive.
+//
+// Walks the console's working directory recursively, skipping
+// binary files and anything excluded by a '.gitignore' along the way.
+// The search runs in another thread, its matches are listed as they come
+// and the console shows how many files were searched. Hidden directories
+// and 'target' are not searched, and it stops after GREP_MAX_FILES files.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+use macroquad::prelude::get_time;
+use regex::Regex;
+
+use crate::editor_console::EditorConsole;
+
+const GREP_MAX_RESULTS: usize = 1000;
+const GREP_MAX_FILES: usize = 20000;
+const GREP_BINARY_PROBE_SIZE: usize = 8000;
+const GREP_SKIPPED_DIRS: [&str; 1] = ["target"]; // Build output, besides the hidden directories
+const GREP_PROGRESS_FILES: usize = 100;
+const GREP_PROGRESS_SECONDS: f64 = 0.25;
+
+pub struct GrepMatch {
+    pub path: PathBuf,
+    pub line: usize, // 0-indexed
+    pub text: String
+}
+
+/// What the searching thread sends to the console
+enum GrepMessage {
+    Matches(Vec<GrepMatch>, usize), // Found in a file, and the files searched so far
+    Progress(usize),
+    Done(usize, bool)               // Files searched, and whether GREP_MAX_FILES stopped it
+}
+
+/// A ':g' going on, replaced by the next one
+pub struct GrepSearch {
+    pattern: String,
+    receiver: Receiver<GrepMessage>,
+    files: usize, // Searched so far
+    last_progress: f64
+}
+
+struct IgnoreRule {
+    pattern: Regex,
+    negate: bool,
+    dir_only: bool,
+    anchored: bool // Matches against the full relative path, not the name
+}
+
+/// Rules of a single '.gitignore', relative to the directory it lives in
+struct GitIgnore {
+    base: PathBuf,
+    rules: Vec<IgnoreRule>
+}
+
+/// Translate a gitignore glob into an anchored regex
+fn glob_to_regex(glob: &str) -> Option<Regex> {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut re = String::from("^");
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if i + 1 < chars.len() && chars[i + 1] == '*' => {
+                if i + 2 < chars.len() && chars[i + 2] == '/' {
+                    re.push_str("(?:.*/)?");
+                    i += 3;
+                } else {
+                    re.push_str(".*");
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => {
+                match chars[i..].iter().position(|&c| c == ']') {
+                    Some(end) if end > 1 => {
+                        let class: String = chars[i + 1..i + end].iter().collect();
+                        re.push('[');
+                        if let Some(rest) = class.strip_prefix('!') {
+                            re.push('^');
+                            re.push_str(&rest.replace('\\', "\\\\"));
+                        } else {
+                            re.push_str(&class.replace('\\', "\\\\"));
+                        }
+                        re.push(']');
+                        i += end + 1;
+                        continue;
+                    }
+                    _ => re.push_str("\\[")
+                }
+            }
+            '\\' if i + 1 < chars.len() => {
+                re.push_str(&regex::escape(&chars[i + 1].to_string()));
+                i += 2;
+                continue;
+            }
+            c => re.push_str(&regex::escape(&c.to_string()))
+        }
+        i += 1;
+    }
+
+    re.push('$');
+    Regex::new(&re).ok()
+}
+
+impl GitIgnore {
+    /// Load the '.gitignore' of 'dir', if any
+    fn load(dir: &Path) -> Option<GitIgnore> {
+        let content = fs::read_to_string(dir.join(".gitignore")).ok()?;
+        let mut rules = vec![];
+
+        for line in content.lines() {
+            let mut line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let negate = line.starts_with('!');
+            if negate {
+                line = &line[1..];
+            }
+
+            let dir_only = line.ends_with('/');
+            let line = line.trim_end_matches('/');
+
+            // A slash anywhere but the end anchors the pattern to this directory
+            let anchored = line.contains('/');
+            let line = line.trim_start_matches('/');
+
+            if let Some(pattern) = glob_to_regex(line) {
+                rules.push(IgnoreRule { pattern, negate, dir_only, anchored });
+            }
+        }
+
+        Some(GitIgnore { base: dir.to_path_buf(), rules })
+    }
+
+    /// None if no rule matched, otherwise whether the path is ignored
+    fn matches(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.base).ok()?.to_string_lossy().replace('\\', "/");
+        let name = path.file_name()?.to_string_lossy();
+        let mut ignored = None;
+
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+
+            let subject = if rule.anchored { relative.as_str() } else { &name };
+            if rule.pattern.is_match(subject) {
+                ignored = Some(!rule.negate);
+            }
+        }
+
+        ignored
+    }
+}
+
+/// Deeper '.gitignore' files override the outer ones
+fn is_ignored(ignores: &[GitIgnore], path: &Path, is_dir: bool) -> bool {
+    let mut ignored = false;
+
+    for ignore in ignores {
+        if let Some(result) = ignore.matches(path, is_dir) {
+            ignored = result;
+        }
+    }
+
+    ignored
+}
+
+/// A NUL byte near the start of a file marks it as binary
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(GREP_BINARY_PROBE_SIZE)].contains(&0)
+}
+
+fn grep_file(path: &Path, pattern: &Regex, results: &mut Vec<GrepMatch>) {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(_) => return
+    };
+
+    if is_binary(&bytes) {
+        return;
+    }
+
+    let content = String::from_utf8_lossy(&bytes);
+    for (line_index, line) in content.lines().enumerate() {
+        if results.len() >= GREP_MAX_RESULTS {
+            return;
+        }
+
+        if pattern.is_match(line) {
+            results.push(GrepMatch { path: path.to_path_buf(), line: line_index, text: line.trim().to_string() });
+        }
+    }
+}
+
+/// Visit every file under 'dir' that is not ignored nor in a directory 'skip_dir' gives true for,
+/// 'visit' returns false to stop the walk
+fn walk(dir: &Path, ignores: &mut Vec<GitIgnore>, skip_dir: fn(&Path) -> bool, visit: &mut dyn FnMut(&Path) -> bool) -> bool {
+    let pushed = match GitIgnore::load(dir) {
+        Some(ignore) => {
+            ignores.push(ignore);
+            true
+        }
+        None => false
+    };
+
+    let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(_) => vec![]
+    };
+    entries.sort();
+
+    let mut keep_going = true;
+
+    for path in entries {
+        // Symlinks are not followed, to avoid walking in circles
+        let file_type = match fs::symlink_metadata(&path) {
+            Ok(meta) => meta.file_type(),
+            Err(_) => continue
+        };
+
+        let is_dir = file_type.is_dir();
+        if path.file_name().is_some_and(|n| n == ".git") || (is_dir && skip_dir(&path)) || is_ignored(ignores, &path, is_dir) {
+            continue;
+        }
+
+        keep_going = if is_dir {
+            walk(&path, ignores, skip_dir, visit)
+        } else if file_type.is_file() {
+            visit(&path)
+        } else {
+            true
+        };
+
+        if !keep_going {
+            break;
+        }
+    }
+
+    if pushed {
+        ignores.pop();
+    }
+
+    keep_going
+}
+
+/// Visit every file under 'root', skipping '.git' and anything a '.gitignore' excludes
+pub fn walk_files(root: &Path, visit: &mut dyn FnMut(&Path) -> bool) {
+    let mut ignores = vec![];
+    walk(root, &mut ignores, |_| false, visit);
+}
+
+/// Hidden directories and build output are not worth a search
+fn is_skipped_dir(path: &Path) -> bool {
+    path.file_name().is_some_and(|n| {
+        let name = n.to_string_lossy();
+        name.starts_with('.') || GREP_SKIPPED_DIRS.contains(&name.as_ref())
+    })
+}
+
+/// Search every text file under 'root' for lines matching 'pattern', sending the matches of each file.
+/// Stops after GREP_MAX_FILES files, GREP_MAX_RESULTS matches or once nobody listens
+fn grep_directory(root: &Path, pattern: &Regex, sender: &Sender<GrepMessage>) -> (usize, bool) {
+    let mut ignores = vec![];
+    let (mut files, mut results) = (0, 0);
+
+    walk(root, &mut ignores, is_skipped_dir, &mut |path| {
+        let mut found = vec![];
+        grep_file(path, pattern, &mut found);
+        files += 1;
+        results += found.len();
+
+        let sent = if !found.is_empty() {
+            sender.send(GrepMessage::Matches(found, files))
+        } else if files % GREP_PROGRESS_FILES == 0 {
+            sender.send(GrepMessage::Progress(files))
+        } else {
+            Ok(())
+        };
+
+        sent.is_ok() && files < GREP_MAX_FILES && results < GREP_MAX_RESULTS
+    });
+
+    (files, files >= GREP_MAX_FILES)
+}
+
+impl GrepSearch {
+    /// Search 'root' in another thread
+    pub fn start(root: &Path, pattern: &Regex) -> GrepSearch {
+        let (sender, receiver) = mpsc::channel();
+        let (root, thread_pattern) = (root.to_path_buf(), pattern.clone());
+
+        thread::spawn(move || {
+            let (files, capped) = grep_directory(&root, &thread_pattern, &sender);
+            let _ = sender.send(GrepMessage::Done(files, capped));
+        });
+
+        GrepSearch { pattern: pattern.as_str().to_string(), receiver, files: 0, last_progress: get_time() }
+    }
+}
+
+/// List the matches of a ':g' going on as they come, and show how far it is
+pub fn update_grep(console: &mut EditorConsole) {
+    let search = match console.grep.as_mut() {
+        Some(search) => search,
+        None => return
+    };
+
+    let mut finished = None;
+    while finished.is_none() {
+        match search.receiver.try_recv() {
+            Ok(GrepMessage::Matches(matches, files)) => {
+                search.files = files;
+                let room = GREP_MAX_RESULTS.saturating_sub(console.grep_results.len());
+                console.grep_results.extend(matches.into_iter().take(room));
+            }
+            Ok(GrepMessage::Progress(files)) => search.files = files,
+            Ok(GrepMessage::Done(files, capped)) => finished = Some((files, capped)),
+            Err(TryRecvError::Empty) => break,
+            Err(TryRecvError::Disconnected) => finished = Some((search.files, false))
+        }
+    }
+
+    let now = get_time();
+    let Some((files, capped)) = finished else {
+        if now - search.last_progress >= GREP_PROGRESS_SECONDS && console.prompt.is_none() {
+            search.last_progress = now;
+            let message = format!("Searching for '{}': {} files, {} matches so far", search.pattern, search.files, console.grep_results.len());
+            console.message(&message);
+        }
+        return;
+    };
+
+    let pattern = search.pattern.clone();
+    console.grep = None;
+
+    let count = console.grep_results.len();
+    let capped = if capped { format!(", stopped after {} files", files) } else { String::new() };
+    console.message(&format!("{} match{} for '{}'{}", count, if count == 1 { "" } else { "es" }, pattern, capped));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn hidden_and_build_directories_are_not_searched() {
+        let root = env::temp_dir().join(format!("muse-grep-{}-skipped", process::id()));
+        for dir in ["src", ".cache", "target"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+            fs::write(root.join(dir).join("a.txt"), "needle\n").unwrap();
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let (files, capped) = grep_directory(&root, &Regex::new("needle").unwrap(), &sender);
+        let found: Vec<PathBuf> = receiver.try_iter()
+            .filter_map(|m| match m { GrepMessage::Matches(matches, _) => Some(matches), _ => None })
+            .flatten()
+            .map(|m| m.path)
+            .collect();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!((files, capped), (1, false));
+        assert_eq!(found, vec![root.join("src").join("a.txt")]);
+    }
+}
@@ -0,0 +1,227 @@
+// Undo/redo history module, one per open file.
+//
+// Every edit replaces a range of lines, so only the lines
+// it touched are kept, not a copy of the whole file.
+//
+// LCTRL + Z undoes, LCTRL + Y redoes.
+
+use std::collections::VecDeque;
+
+const HISTORY_CAPACITY: usize = 1000;
+const CHANGED_LINES_CAPACITY: usize = 64; // Recent changes whose first line is kept
+
+struct HistoryEntry {
+    start: usize,       // First line of the edit
+    lines: Vec<String>, // Lines to put back
+    len: usize,         // Lines the edit currently occupies
+    xy: (usize, usize), // Cursor to put back
+    id: usize,
+    coalesce: bool
+}
+
+pub struct EditorHistory {
+    undo: Vec<HistoryEntry>,
+    redo: Vec<HistoryEntry>,
+    next_id: usize,
+    saved_id: usize,
+    sealed: bool,   // Don't merge the next edit into the last one
+    changes: usize, // Edits, undos and redos so far
+    changed_lines: VecDeque<(usize, usize)>, // Changes so far after a recent change, and the first line it touched
+    line_shifts: Vec<LineShift> // Edits that changed the number of lines, until the panes take them
+}
+
+/// 'old_len' lines from 'start' became 'new_len' lines
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineShift {
+    pub start: usize,
+    pub old_len: usize,
+    pub new_len: usize
+}
+
+impl EditorHistory {
+    /// History constructor
+    pub fn new() -> EditorHistory {
+        EditorHistory { undo: vec![], redo: vec![], next_id: 1, saved_id: 0, sealed: false, changes: 0, changed_lines: VecDeque::new(), line_shifts: vec![] }
+    }
+
+    fn current_id(&self) -> usize {
+        self.undo.last().map_or(0, |e| e.id)
+    }
+
+    /// The text differs from the one last saved or loaded
+    pub fn is_dirty(&self) -> bool {
+        self.current_id() != self.saved_id
+    }
+
+    /// The text was written to (or read from) the disk
+    pub fn mark_saved(&mut self) {
+        self.saved_id = self.current_id();
+        self.sealed = true;
+    }
+
+    /// Grows with every change of the text, coalesced edits included
+    pub fn changes(&self) -> usize {
+        self.changes
+    }
+
+    /// The edits that inserted or removed lines since the last call,
+    /// the jump lists of every pane follow them (see editor_panes.rs)
+    pub fn take_line_shifts(&mut self) -> Vec<LineShift> {
+        std::mem::take(&mut self.line_shifts)
+    }
+
+    fn shift_lines(&mut self, start: usize, old_len: usize, new_len: usize) {
+        if old_len != new_len {
+            self.line_shifts.push(LineShift { start, old_len, new_len });
+        }
+    }
+
+    fn count_change(&mut self, line: usize) {
+        self.changes += 1;
+        self.changed_lines.push_back((self.changes, line));
+        if self.changed_lines.len() > CHANGED_LINES_CAPACITY {
+            self.changed_lines.pop_front();
+        }
+    }
+
+    /// First line changed since there were 'changes' changes, None if the text did not change.
+    /// The first line of the text when they are too many to tell
+    pub fn changed_since(&self, changes: usize) -> Option<usize> {
+        if changes == self.changes {
+            return None;
+        }
+        if changes > self.changes || self.changed_lines.front().is_none_or(|&(after, _)| after > changes + 1) {
+            return Some(0);
+        }
+
+        self.changed_lines.iter().filter(|(after, _)| *after > changes).map(|(_, line)| *line).min()
+    }
+
+    /// Keep the 'old_len' lines from 'start' before an edit turns them into 'new_len' lines.
+    /// Consecutive 'coalesce' edits on the same line are undone at once, like a typed word.
+    pub fn record(&mut self, text: &[String], start: usize, old_len: usize, new_len: usize, xy: (usize, usize), coalesce: bool) {
+        self.count_change(start);
+        self.shift_lines(start, old_len.min(text.len().saturating_sub(start)), new_len);
+
+        if coalesce && !self.sealed && self.redo.is_empty() && old_len == 1 && new_len == 1
+            && let Some(last) = self.undo.last()
+            && last.coalesce && last.start == start && last.len == 1 && last.lines.len() == 1 {
+            return;
+        }
+
+        let end = (start + old_len).min(text.len());
+        self.undo.push(HistoryEntry {
+            start,
+            lines: text[start.min(end)..end].to_vec(),
+            len: new_len,
+            xy,
+            id: self.next_id,
+            coalesce
+        });
+
+        if self.undo.len() > HISTORY_CAPACITY {
+            self.undo.remove(0);
+        }
+
+        self.next_id += 1;
+        self.sealed = false;
+        self.redo.clear();
+    }
+
+    /// Swap an entry's lines back into the text, returns the entry to reverse it
+    fn apply(entry: HistoryEntry, text: &mut Vec<String>, xy: (usize, usize)) -> HistoryEntry {
+        let start = entry.start.min(text.len());
+        let end = (start + entry.len).min(text.len());
+        let len = entry.lines.len();
+
+        let removed: Vec<String> = text.splice(start..end, entry.lines).collect();
+        if text.is_empty() {
+            text.push(String::new());
+        }
+
+        HistoryEntry { start, lines: removed, len, xy, id: entry.id, coalesce: false }
+    }
+
+    /// Undo the last edit, returns where the cursor should go
+    pub fn undo(&mut self, text: &mut Vec<String>, xy: (usize, usize)) -> Option<(usize, usize)> {
+        let entry = self.undo.pop()?;
+        let restore = entry.xy;
+
+        // The reverse keeps the id, so redoing restores the same state
+        let reverse = Self::apply(entry, text, xy);
+        self.count_change(reverse.start);
+        self.shift_lines(reverse.start, reverse.lines.len(), reverse.len);
+        self.redo.push(reverse);
+        self.sealed = true;
+
+        Some(restore)
+    }
+
+    /// Redo the last undone edit, returns where the cursor should go
+    pub fn redo(&mut self, text: &mut Vec<String>, xy: (usize, usize)) -> Option<(usize, usize)> {
+        let entry = self.redo.pop()?;
+        let restore = entry.xy;
+
+        let reverse = Self::apply(entry, text, xy);
+        self.count_change(reverse.start);
+        self.shift_lines(reverse.start, reverse.lines.len(), reverse.len);
+        self.undo.push(reverse);
+        self.sealed = true;
+
+        Some(restore)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(count: usize) -> Vec<String> {
+        (0..count).map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn first_changed_line_since_a_count() {
+        let mut history = EditorHistory::new();
+        let mut text = lines(10);
+        assert_eq!(history.changed_since(0), None);
+
+        history.record(&text, 7, 1, 1, (0, 7), false);
+        history.record(&text, 3, 1, 1, (0, 3), false);
+        history.record(&text, 5, 1, 1, (0, 5), false);
+        assert_eq!(history.changed_since(0), Some(3));
+        assert_eq!(history.changed_since(2), Some(5));
+        assert_eq!(history.changed_since(3), None);
+
+        history.undo(&mut text, (0, 0));
+        assert_eq!(history.changed_since(3), Some(5));
+    }
+
+    #[test]
+    fn too_many_changes_start_from_the_top() {
+        let mut history = EditorHistory::new();
+        let text = lines(10);
+
+        for _ in 0..CHANGED_LINES_CAPACITY + 1 {
+            history.record(&text, 9, 1, 1, (0, 9), false);
+        }
+        assert_eq!(history.changed_since(0), Some(0));
+        assert_eq!(history.changed_since(1), Some(9));
+    }
+
+    #[test]
+    fn line_shifts_follow_edits_and_undos() {
+        let mut history = EditorHistory::new();
+        let mut text = lines(10);
+
+        history.record(&text, 3, 1, 1, (0, 3), false);
+        history.record(&text, 4, 2, 1, (0, 5), false);
+        text.splice(4..6, [String::new()]);
+        assert_eq!(history.take_line_shifts(), vec![LineShift { start: 4, old_len: 2, new_len: 1 }]);
+        assert_eq!(history.take_line_shifts(), vec![]);
+
+        history.undo(&mut text, (0, 4));
+        assert_eq!(history.take_line_shifts(), vec![LineShift { start: 4, old_len: 1, new_len: 2 }]);
+        assert_eq!(text.len(), 10);
+    }
+}
@@ -0,0 +1,207 @@
+// File info module, the statistics ':i' shows about the current buffer:
+// counts of its text, how it is stored on the disk and how it is indented.
+// With text selected (see editor_cursor.rs) the counts of the selection follow.
+
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::editor_encoding::Encoding;
+use crate::editor_file::EditorFile;
+use crate::editor_fs::format_size;
+
+const INDENT_SAMPLE_LINES: usize = 1000; // Lines looked at to guess the indentation
+
+const LANGUAGES: [(&str, &str); 23] = [
+    ("c", "C"), ("h", "C"), ("cpp", "C++"), ("cc", "C++"), ("hpp", "C++"),
+    ("rs", "Rust"), ("py", "Python"), ("js", "JavaScript"), ("ts", "TypeScript"),
+    ("go", "Go"), ("java", "Java"), ("sh", "Shell"), ("lua", "Lua"), ("zig", "Zig"),
+    ("html", "HTML"), ("css", "CSS"), ("json", "JSON"), ("toml", "TOML"), ("yaml", "YAML"),
+    ("yml", "YAML"), ("md", "Markdown"), ("txt", "Text"), ("conf", "Config")
+];
+
+/// Counts of a piece of text
+pub struct TextStats {
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize // Line breaks included
+}
+
+pub fn text_stats(lines: &[String]) -> TextStats {
+    TextStats {
+        lines: lines.len(),
+        words: lines.iter().map(|l| l.split_whitespace().count()).sum(),
+        chars: lines.iter().map(|l| l.chars().count()).sum::<usize>() + lines.len().saturating_sub(1)
+    }
+}
+
+/// The lines of 'text' from 'start' to 'end', (column, line) pairs
+/// in text order, cut at the columns
+pub fn selected_text(text: &[String], (start, end): ((usize, usize), (usize, usize))) -> Vec<String> {
+    let last = end.1.min(text.len().saturating_sub(1));
+
+    text.iter().enumerate().take(last + 1).skip(start.1).map(|(index, line)| {
+        let from = if index == start.1 { start.0 } else { 0 };
+        let to = if index == end.1 { end.0 } else { usize::MAX };
+        line.chars().skip(from).take(to.saturating_sub(from)).collect()
+    }).collect()
+}
+
+/// Language of a file by its extension
+pub fn language_of(path: Option<&Path>) -> &'static str {
+    let extension = match path.and_then(|p| p.extension()) {
+        Some(extension) => extension.to_string_lossy().to_lowercase(),
+        None => return "Plain text"
+    };
+
+    LANGUAGES.iter().find(|(e, _)| *e == extension).map_or("Plain text", |(_, language)| language)
+}
+
+/// Name of an encoding, plain UTF-8 text is ASCII if it could be
+pub fn format_encoding(encoding: Encoding, lines: &[String]) -> String {
+    match encoding {
+        Encoding::Utf8 if lines.iter().all(|l| l.is_ascii()) => String::from("ASCII"),
+        _ => encoding.name().to_uppercase()
+    }
+}
+
+/// Tabs, a number of spaces, mixed or none, from the leading whitespace of the lines
+pub fn detect_indentation(lines: &[String]) -> String {
+    let mut tabs = 0;
+    let mut spaces = 0;
+    let mut width = 0; // Smallest space indentation seen
+
+    for line in lines.iter().take(INDENT_SAMPLE_LINES) {
+        if line.starts_with('\t') {
+            tabs += 1;
+        } else if line.starts_with(' ') {
+            let indent = line.len() - line.trim_start_matches(' ').len();
+            if indent == line.len() {
+                continue; // Whitespace only
+            }
+
+            spaces += 1;
+            if width == 0 || indent < width {
+                width = indent;
+            }
+        }
+    }
+
+    match (tabs, spaces) {
+        (0, 0) => String::from("None"),
+        (_, 0) => String::from("Tabs"),
+        (0, _) => format!("{} spaces", width),
+        _ => format!("Mixed, {} tab and {} space indented lines", tabs, spaces)
+    }
+}
+
+/// Time in UTC as (year, month, day, hour, minute, second)
+pub fn civil_time(time: SystemTime) -> (i64, i64, i64, u64, u64, u64) {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rest) = (seconds / 86400, seconds % 86400);
+
+    // Days since 1970-01-01 to a civil date (Howard Hinnant's algorithm)
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day, rest / 3600, rest / 60 % 60, rest % 60)
+}
+
+/// Time as 'YYYY-MM-DD HH:MM:SS UTC'
+pub fn format_time(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = civil_time(time);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, hour, minute, second)
+}
+
+/// The lines ':i' shows for a buffer
+pub fn file_info(file: &EditorFile) -> Vec<String> {
+    let mut info = vec![];
+
+    match &file.path {
+        Some(path) => info.push(format!("Path: {}", fs::canonicalize(path).unwrap_or_else(|_| path.clone()).display())),
+        None => info.push(String::from("Path: none, the buffer was never saved"))
+    }
+
+    match file.path.as_ref().and_then(|p| fs::metadata(p).ok()) {
+        Some(metadata) => info.push(format!("Size on disk: {}", format_size(metadata.len()))),
+        None => info.push(String::from("Size on disk: not on the disk"))
+    }
+
+    info.push(format!("Lines: {}", file.text.len()));
+
+    // Only the lines shown of a large file are decoded (see editor_large.rs)
+    if file.large.is_some() {
+        info.push(String::from("Words and characters: not counted in a large file"));
+        info.push(format!("Encoding: {}", file.format.encoding.name().to_uppercase()));
+    } else {
+        let stats = text_stats(&file.text);
+        info.push(format!("Words: {}", stats.words));
+        info.push(format!("Characters: {}", stats.chars));
+
+        if let Some(selection) = file.cursor.selection() {
+            let selected = text_stats(&selected_text(&file.text, selection));
+            info.push(format!("Selection: {} lines, {} words, {} characters", selected.lines, selected.words, selected.chars));
+        }
+
+        info.push(format!("Encoding: {}", format_encoding(file.format.encoding, &file.text)));
+    }
+
+    info.push(format!("Line endings: {}{}", file.format.line_ending.name().to_uppercase(),
+        if file.format.final_newline { "" } else { ", no newline at the end" }));
+
+    if file.large.is_none() {
+        info.push(format!("Indentation: {}", detect_indentation(&file.text)));
+    }
+    info.push(format!("Language: {}", language_of(file.path.as_deref())));
+    info.push(format!("Modified: {}", if file.is_dirty() { "yes, unsaved changes" } else { "no" }));
+
+    let saved = match file.saved_at {
+        Some(time) => format_time(time),
+        None => String::from("not saved since opened")
+    };
+    info.push(format!("Last saved: {}", saved));
+
+    if let Some(modified) = file.path.as_ref().and_then(|p| fs::metadata(p).ok()).and_then(|m| m.modified().ok()) {
+        info.push(format!("Changed on disk: {}", format_time(modified)));
+    }
+
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split('\n').map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn selection_within_a_line() {
+        let text = lines("one two three");
+        assert_eq!(selected_text(&text, ((4, 0), (7, 0))), ["two"]);
+    }
+
+    #[test]
+    fn selection_across_lines() {
+        let text = lines("one two\nthree\nfour five");
+        let selected = selected_text(&text, ((4, 0), (4, 2)));
+        assert_eq!(selected, ["two", "three", "four"]);
+
+        let stats = text_stats(&selected);
+        assert_eq!((stats.lines, stats.words, stats.chars), (3, 3, 14));
+    }
+
+    #[test]
+    fn selection_past_the_end_is_cut() {
+        let text = lines("ab\ncd");
+        assert_eq!(selected_text(&text, ((1, 0), (9, 7))), ["b", "cd"]);
+    }
+}
@@ -0,0 +1,126 @@
+// Jump list module, remembers where the cursor was
+// before a big jump (:l, :f, opening a file, grep results, LCTRL + Home/End)
+// so it can be visited again, even inside another file.
+//
+// LALT + Left goes back, LALT + Right goes forward, the default keys
+// of 'jump.back' and 'jump.forward' (see editor_keymap.rs).
+
+use std::path::PathBuf;
+
+use crate::editor_audio::EditorAudio;
+use crate::editor_buffers::EditorBuffers;
+use crate::editor_commands::JumpCommand;
+use crate::editor_console::EditorConsole;
+use crate::editor_history::LineShift;
+
+const JUMP_LIST_CAPACITY: usize = 100;
+
+#[derive(Clone, PartialEq)]
+pub struct Jump {
+    pub path: Option<PathBuf>,
+    pub xy: (usize, usize)
+}
+
+/// One per window
+pub struct EditorJumpList {
+    pub entries: Vec<Jump>,
+    pub index: usize // entries.len() when not travelling through the list
+}
+
+impl EditorJumpList {
+    /// Jump list constructor
+    pub fn new() -> EditorJumpList {
+        EditorJumpList { entries: vec![], index: 0 }
+    }
+
+    /// Record the position the cursor is jumping away from,
+    /// this drops any positions ahead of the current one
+    pub fn push(&mut self, path: &Option<PathBuf>, xy: (usize, usize)) {
+        let jump = Jump { path: path.clone(), xy };
+
+        self.entries.truncate(self.index);
+
+        if self.entries.last() != Some(&jump) {
+            self.entries.push(jump);
+        }
+
+        if self.entries.len() > JUMP_LIST_CAPACITY {
+            self.entries.remove(0);
+        }
+
+        self.index = self.entries.len();
+    }
+
+    /// Previous position, 'current' is kept so that forward can return to it
+    pub fn back(&mut self, path: &Option<PathBuf>, current: (usize, usize)) -> Option<Jump> {
+        if self.index == 0 {
+            return None;
+        }
+
+        if self.index == self.entries.len() {
+            self.push(path, current);
+            self.index -= 1;
+
+            if self.index == 0 {
+                return None;
+            }
+        }
+
+        self.index -= 1;
+        Some(self.entries[self.index].clone())
+    }
+
+    /// Next position, after having gone back
+    pub fn forward(&mut self) -> Option<Jump> {
+        if self.index + 1 >= self.entries.len() {
+            return None;
+        }
+
+        self.index += 1;
+        Some(self.entries[self.index].clone())
+    }
+
+    /// Keep the entries of a file on their text when an edit turned
+    /// lines into more or fewer, those on removed lines go to the last one left
+    pub fn shift_lines(&mut self, path: &Option<PathBuf>, shift: LineShift) {
+        let old_end = shift.start + shift.old_len;
+        let new_end = shift.start + shift.new_len;
+
+        for jump in self.entries.iter_mut().filter(|j| &j.path == path) {
+            if jump.xy.1 >= old_end {
+                jump.xy.1 = jump.xy.1 - old_end + new_end;
+            } else if jump.xy.1 >= new_end {
+                jump.xy.1 = new_end.saturating_sub(1).max(shift.start);
+            }
+        }
+    }
+}
+
+/// Back and forward through the jump list, 'jump.back' and 'jump.forward'
+pub fn jump_list_navigation(command: JumpCommand, jumps: &mut EditorJumpList, buffers: &mut EditorBuffers, audio: &EditorAudio, console: &mut EditorConsole) {
+    let file = buffers.current();
+    let target = match command {
+        JumpCommand::Back => jumps.back(&file.path, file.cursor.xy),
+        JumpCommand::Forward => jumps.forward()
+    };
+
+    let jump = match target {
+        Some(jump) => jump,
+        None => return
+    };
+
+    audio.play_nav();
+
+    if jump.path != buffers.current().path && let Some(path) = &jump.path
+        && let Err(e) = buffers.open(path) {
+        console.message(&format!("Cannot open {}: {}", path.display(), e));
+        return;
+    }
+
+    // The text may have shrunk since the jump was recorded
+    let file = buffers.current_mut();
+    let y = jump.xy.1.min(file.text.len().saturating_sub(1));
+    file.decode_lines(y, y + 1);
+    let x = jump.xy.0.min(file.text.get(y).map_or(0, |l| l.chars().count()));
+    file.cursor.xy = (x, y);
+}
@@ -0,0 +1,376 @@
+// Keymap module, turns key presses into named commands.
+//
+// A chord is a key with the modifiers held down with it, written
+// 'ctrl+z', 'ctrl+alt+v', 'ctrl+shift+tab' or just 'up'. A binding is
+// one chord or a sequence of them separated by spaces, 'ctrl+k ctrl+w',
+// whose chords follow each other within KEY_SEQUENCE_SECONDS.
+// A chord with shift that is not bound runs the binding without shift.
+//
+// The commands and their default keys are those of the command registry
+// (see editor_commands.rs), they can be changed in cal.conf, one line per command:
+//      key.edit.undo = ctrl+u                : Replace the keys of a command
+//      key.buffer.close = ctrl+w, ctrl+k ctrl+w  : Several bindings, separated by commas
+//      key.jump.back = none                  : Unbind it
+//      key.file.save = ctrl+s                : Bind a command without default keys
+// A command that needs an argument, like 'file.create', cannot be bound.
+//
+// The keys run the commands of where the keyboard is: the text runs all but
+// the 'console.*' and 'sidebar.*' ones, the console only those and
+// 'view.toggle_console', the sidebar its own and the two 'view.toggle_*'.
+// The same keys can so move the cursor, the console selection and the sidebar's.
+//
+// A binding that is also the start of another one, or the same keys
+// bound twice, where both can run, is a conflict: a binding from cal.conf takes the keys from
+// a default one, of two bindings from cal.conf the first by command name
+// is kept. Conflicts and unreadable lines, or default keys, are reported on startup.
+//
+// ':ekb' lists the active bindings and the conflicts, ':ekb <t>' only those containing 't'.
+
+use macroquad::prelude::*;
+
+use crate::editor_commands::{COMMANDS, Command, CommandInfo, command_info};
+use crate::editor_config::EditorConfig;
+
+const KEY_SEQUENCE_SECONDS: f64 = 1.5;
+
+/// Commands that also run while the sidebar has the keyboard,
+/// in the console only the first one does
+const GLOBAL_COMMANDS: [Command; 2] = [Command::ToggleConsole, Command::ToggleSidebar];
+
+const KEY_SCOPES: [KeyScope; 3] = [KeyScope::Text, KeyScope::Sidebar, KeyScope::Console];
+
+const KEY_NAMES: [(&str, KeyCode); 74] = [
+    ("a", KeyCode::A), ("b", KeyCode::B), ("c", KeyCode::C), ("d", KeyCode::D), ("e", KeyCode::E),
+    ("f", KeyCode::F), ("g", KeyCode::G), ("h", KeyCode::H), ("i", KeyCode::I), ("j", KeyCode::J),
+    ("k", KeyCode::K), ("l", KeyCode::L), ("m", KeyCode::M), ("n", KeyCode::N), ("o", KeyCode::O),
+    ("p", KeyCode::P), ("q", KeyCode::Q), ("r", KeyCode::R), ("s", KeyCode::S), ("t", KeyCode::T),
+    ("u", KeyCode::U), ("v", KeyCode::V), ("w", KeyCode::W), ("x", KeyCode::X), ("y", KeyCode::Y),
+    ("z", KeyCode::Z),
+    ("0", KeyCode::Key0), ("1", KeyCode::Key1), ("2", KeyCode::Key2), ("3", KeyCode::Key3), ("4", KeyCode::Key4),
+    ("5", KeyCode::Key5), ("6", KeyCode::Key6), ("7", KeyCode::Key7), ("8", KeyCode::Key8), ("9", KeyCode::Key9),
+    ("`", KeyCode::GraveAccent), ("-", KeyCode::Minus), ("=", KeyCode::Equal), ("[", KeyCode::LeftBracket),
+    ("]", KeyCode::RightBracket), ("\\", KeyCode::Backslash), (";", KeyCode::Semicolon), ("'", KeyCode::Apostrophe),
+    (",", KeyCode::Comma), (".", KeyCode::Period), ("/", KeyCode::Slash),
+    ("space", KeyCode::Space), ("tab", KeyCode::Tab), ("enter", KeyCode::Enter), ("backspace", KeyCode::Backspace),
+    ("escape", KeyCode::Escape), ("insert", KeyCode::Insert), ("delete", KeyCode::Delete),
+    ("up", KeyCode::Up), ("down", KeyCode::Down), ("left", KeyCode::Left), ("right", KeyCode::Right),
+    ("home", KeyCode::Home), ("end", KeyCode::End), ("pageup", KeyCode::PageUp), ("pagedown", KeyCode::PageDown),
+    ("f1", KeyCode::F1), ("f2", KeyCode::F2), ("f3", KeyCode::F3), ("f4", KeyCode::F4), ("f5", KeyCode::F5),
+    ("f6", KeyCode::F6), ("f7", KeyCode::F7), ("f8", KeyCode::F8), ("f9", KeyCode::F9), ("f10", KeyCode::F10),
+    ("f11", KeyCode::F11), ("f12", KeyCode::F12)
+];
+
+const MODIFIER_KEYS: [KeyCode; 8] = [
+    KeyCode::LeftControl, KeyCode::RightControl, KeyCode::LeftAlt, KeyCode::RightAlt,
+    KeyCode::LeftShift, KeyCode::RightShift, KeyCode::LeftSuper, KeyCode::RightSuper
+];
+
+/// Where the keyboard goes, deciding which commands can run
+#[derive(Clone, Copy, PartialEq)]
+pub enum KeyScope {
+    Text,
+    Sidebar,
+    Console
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct KeyChord {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool
+}
+
+pub struct KeyBinding {
+    pub keys: Vec<KeyChord>,
+    pub command: &'static str,
+    pub id: Command,
+    pub from_config: bool
+}
+
+pub struct EditorKeymap {
+    pub bindings: Vec<KeyBinding>,
+    pub problems: Vec<String>, // Conflicts and unreadable cal.conf lines
+    pending: Vec<KeyChord>,    // Chords of a sequence typed so far
+    pending_at: f64
+}
+
+impl KeyChord {
+    /// Read a chord like 'ctrl+shift+tab'
+    pub fn parse(text: &str) -> Result<KeyChord, String> {
+        let mut chord = KeyChord { key: KeyCode::Unknown, ctrl: false, alt: false, shift: false };
+        let parts: Vec<&str> = text.split('+').collect();
+        let (key, modifiers) = parts.split_last().ok_or_else(|| format!("'{}' has no key", text))?;
+
+        for modifier in modifiers {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" => chord.ctrl = true,
+                "alt" => chord.alt = true,
+                "shift" => chord.shift = true,
+                _ => return Err(format!("'{}' is not ctrl, alt or shift", modifier))
+            }
+        }
+
+        let key = key.to_lowercase();
+        chord.key = KEY_NAMES.iter().find(|(name, _)| *name == key).map(|(_, code)| *code)
+            .ok_or_else(|| format!("'{}' is not a key", key))?;
+        Ok(chord)
+    }
+
+    /// The chord as written in cal.conf
+    pub fn name(&self) -> String {
+        let key = KEY_NAMES.iter().find(|(_, code)| *code == self.key).map_or("?", |(name, _)| name);
+        let mut name = String::new();
+        for (held, modifier) in [(self.ctrl, "ctrl+"), (self.alt, "alt+"), (self.shift, "shift+")] {
+            if held {
+                name.push_str(modifier);
+            }
+        }
+        name.push_str(key);
+        name
+    }
+}
+
+/// Read a binding, chords separated by spaces
+fn parse_keys(text: &str) -> Result<Vec<KeyChord>, String> {
+    let keys = text.split_whitespace().map(KeyChord::parse).collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err(String::from("no keys given"));
+    }
+    Ok(keys)
+}
+
+/// Keys of a binding as written in cal.conf
+pub fn keys_name(keys: &[KeyChord]) -> String {
+    keys.iter().map(|k| k.name()).collect::<Vec<_>>().join(" ")
+}
+
+/// One of the two sequences is the start of the other, or they are the same
+fn overlaps(a: &[KeyChord], b: &[KeyChord]) -> bool {
+    a.iter().zip(b).all(|(x, y)| x == y)
+}
+
+fn usable(command: Command, scope: KeyScope) -> bool {
+    match (command, scope) {
+        (Command::Console(_), scope) => scope == KeyScope::Console,
+        (Command::Sidebar(_), scope) => scope == KeyScope::Sidebar,
+        (_, KeyScope::Text) => true,
+        (_, KeyScope::Sidebar) => GLOBAL_COMMANDS.contains(&command),
+        (_, KeyScope::Console) => command == GLOBAL_COMMANDS[0]
+    }
+}
+
+/// The bindings can be pressed at the same time and their keys overlap
+fn conflict(a: &KeyBinding, b: &KeyBinding) -> bool {
+    KEY_SCOPES.iter().any(|&scope| usable(a.id, scope) && usable(b.id, scope)) && overlaps(&a.keys, &b.keys)
+}
+
+impl KeyBinding {
+    fn new(keys: Vec<KeyChord>, info: &'static CommandInfo, from_config: bool) -> KeyBinding {
+        KeyBinding { keys, command: info.name, id: info.id, from_config }
+    }
+}
+
+impl EditorKeymap {
+    /// The default bindings, with the 'key.<command>' lines of cal.conf applied
+    pub fn load(config: &EditorConfig) -> EditorKeymap {
+        let mut problems = vec![];
+        let mut bindings: Vec<KeyBinding> = COMMANDS.iter().filter(|c| !c.keys.is_empty())
+            .filter_map(|c| match parse_keys(c.keys) {
+                Ok(keys) => Some(KeyBinding::new(keys, c, false)),
+                Err(e) => {
+                    problems.push(format!("default keys of {}: {}", c.name, e));
+                    None
+                }
+            })
+            .collect();
+
+        for (name, value) in config.with_prefix("key.") {
+            let command = match command_info(name) {
+                Some(info) if info.arg.as_ref().is_some_and(|a| a.required) => {
+                    problems.push(format!("key.{}: the command needs an argument", name));
+                    continue;
+                }
+                Some(info) => info,
+                None => {
+                    problems.push(format!("key.{}: no such command", name));
+                    continue;
+                }
+            };
+
+            // A line that cannot be read leaves the default keys
+            let keys = if value == "none" { Ok(vec![]) } else { value.split(',').map(parse_keys).collect() };
+            match keys {
+                Ok(keys) => {
+                    bindings.retain(|b| b.command != command.name);
+                    bindings.extend(keys.into_iter().map(|keys| KeyBinding::new(keys, command, true)));
+                }
+                Err(e) => problems.push(format!("key.{}: {}", name, e))
+            }
+        }
+
+        let mut keymap = EditorKeymap { bindings: vec![], problems, pending: vec![], pending_at: 0.0 };
+        for binding in bindings {
+            keymap.add(binding);
+        }
+        keymap
+    }
+
+    /// Keep a binding unless it conflicts with one kept before,
+    /// a binding from cal.conf takes the keys of default ones
+    fn add(&mut self, binding: KeyBinding) {
+        if binding.from_config {
+            let (taken, kept): (Vec<KeyBinding>, Vec<KeyBinding>) = std::mem::take(&mut self.bindings).into_iter()
+                .partition(|b| !b.from_config && conflict(b, &binding));
+            self.bindings = kept;
+
+            // The same keys simply change command, a shared start is worth a word
+            for old in taken.iter().filter(|old| old.keys != binding.keys) {
+                self.problems.push(format!("{} no longer runs {}, {} starts with it",
+                    keys_name(&old.keys), old.command, keys_name(&binding.keys)));
+            }
+        }
+
+        if let Some(other) = self.bindings.iter().find(|b| conflict(b, &binding)) {
+            self.problems.push(format!("{} for {} conflicts with {} for {}, left out",
+                keys_name(&binding.keys), binding.command, keys_name(&other.keys), other.command));
+            return;
+        }
+
+        self.bindings.push(binding);
+    }
+
+    /// The command the keys pressed this frame complete, if any
+    pub fn update(&mut self, scope: KeyScope) -> Option<&'static str> {
+        let now = get_time();
+        if !self.pending.is_empty() && now - self.pending_at > KEY_SEQUENCE_SECONDS {
+            self.pending.clear();
+        }
+
+        let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        let alt = is_key_down(KeyCode::LeftAlt) || is_key_down(KeyCode::RightAlt);
+        let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+
+        let mut keys: Vec<KeyCode> = get_keys_pressed().into_iter().filter(|k| !MODIFIER_KEYS.contains(k)).collect();
+        keys.sort_by_key(|k| *k as u32);
+
+        for key in keys {
+            let chord = KeyChord { key, ctrl, alt, shift };
+            if let Some(command) = self.press(chord, scope, now) {
+                return Some(command);
+            }
+        }
+
+        None
+    }
+
+    /// Add a chord to the pending sequence, the command it completes if any
+    fn press(&mut self, chord: KeyChord, scope: KeyScope, now: f64) -> Option<&'static str> {
+        let without_shift = KeyChord { shift: false, ..chord };
+        let tries = if chord.shift { vec![chord, without_shift] } else { vec![chord] };
+
+        for chord in tries {
+            let mut keys = self.pending.clone();
+            keys.push(chord);
+
+            let mut started = self.bindings.iter()
+                .filter(|b| usable(b.id, scope) && b.keys.starts_with(&keys));
+            if let Some(binding) = started.next() {
+                if binding.keys.len() == keys.len() {
+                    self.pending.clear();
+                    clear_input_queue();
+                    return Some(binding.command);
+                }
+
+                self.pending = keys;
+                self.pending_at = now;
+                clear_input_queue();
+                return None;
+            }
+        }
+
+        // A chord that continues no sequence ends the pending one, and is dropped
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            clear_input_queue();
+        }
+        None
+    }
+
+    /// Chords of a sequence waiting for the rest, as written in cal.conf
+    pub fn pending(&self) -> Option<String> {
+        if self.pending.is_empty() { None } else { Some(keys_name(&self.pending)) }
+    }
+
+    /// The bindings of 'command', as written in cal.conf
+    pub fn keys_of(&self, command: &str) -> Vec<String> {
+        self.bindings.iter().filter(|b| b.command == command).map(|b| keys_name(&b.keys)).collect()
+    }
+
+    /// The keys of every command that can be bound as ':ekb' lists them, 'keys  command'
+    pub fn describe(&self) -> Vec<String> {
+        COMMANDS.iter().filter(|c| c.arg.as_ref().is_none_or(|a| !a.required)).map(|c| {
+            let keys = self.keys_of(c.name);
+            let keys = if keys.is_empty() { String::from("none") } else { keys.join(", ") };
+            format!("{}  {}", keys, c.name)
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    /// Keymap of a config holding 'conf', written to a file named after the test
+    fn keymap_with(name: &str, conf: &str) -> EditorKeymap {
+        let path = env::temp_dir().join(format!("muse-keymap-{}-{}.conf", process::id(), name));
+        fs::write(&path, conf).unwrap();
+        let keymap = EditorKeymap::load(&EditorConfig::load(&path));
+        let _ = fs::remove_file(&path);
+        keymap
+    }
+
+    #[test]
+    fn every_default_parses() {
+        for info in COMMANDS.iter().filter(|c| !c.keys.is_empty()) {
+            assert!(parse_keys(info.keys).is_ok(), "{}: '{}'", info.name, info.keys);
+        }
+    }
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        let keymap = keymap_with("defaults", "");
+        assert_eq!(keymap.problems, Vec::<String>::new());
+        assert_eq!(keymap.bindings.len(), COMMANDS.iter().filter(|c| !c.keys.is_empty()).count());
+    }
+
+    #[test]
+    fn override_takes_the_keys_of_a_default() {
+        let keymap = keymap_with("override", "key.file.save = ctrl+z ctrl+s\nkey.buffer.alternate = ctrl+y\n");
+
+        assert_eq!(keymap.keys_of("file.save"), ["ctrl+z ctrl+s"]);
+        assert!(keymap.keys_of("edit.undo").is_empty());
+        assert_eq!(keymap.keys_of("buffer.alternate"), ["ctrl+y"]);
+        assert!(keymap.keys_of("edit.redo").is_empty());
+
+        // Taking the same keys is not worth a word, a shared start is
+        assert_eq!(keymap.problems, ["ctrl+z no longer runs edit.undo, ctrl+z ctrl+s starts with it"]);
+    }
+
+    #[test]
+    fn keys_conflict_only_where_both_run() {
+        let keymap = keymap_with("scopes", "key.console.complete = ctrl+k\nkey.cursor.up = ctrl+k\nkey.sidebar.open = ctrl+`\n");
+
+        assert_eq!(keymap.keys_of("console.complete"), ["ctrl+k"]);
+        assert_eq!(keymap.keys_of("cursor.up"), ["ctrl+k"]);
+
+        // The console switch runs in the sidebar too
+        assert!(keymap.keys_of("view.toggle_console").is_empty());
+        assert_eq!(keymap.keys_of("sidebar.open"), ["ctrl+`"]);
+        assert!(keymap.problems.is_empty());
+    }
+}
@@ -0,0 +1,615 @@
+// Large file module, opens files above 'large_file_size' megabytes
+// (LARGE_FILE_DEFAULT_MB by default, set in cal.conf) without freezing the editor.
+//
+// A thread reads such a file in chunks of CHUNK_BYTES and indexes where every
+// line starts, the buffer gets an empty line for each line found, so it can
+// already be scrolled and visited with ':l' while the indexing goes on.
+// Only the lines a pane shows are decoded, BLOCK_LINES at a time, and the
+// blocks not shown for a while are dropped beyond MAX_DECODED_BLOCKS.
+// ':f' searches the file on the disk in another thread and moves the cursor
+// once found, a line the file is opened at is gone to once it is indexed.
+// The console and the buffer's tab show how far the indexing is.
+//
+// Large buffers are read-only, drawn without highlighting and get
+// no swap file (see editor_swap.rs).
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+use macroquad::prelude::get_time;
+use once_cell::sync::OnceCell;
+
+use crate::editor_buffers::EditorBuffers;
+use crate::editor_config::EditorConfig;
+use crate::editor_console::EditorConsole;
+use crate::editor_encoding::{Encoding, LineEnding, TextFormat};
+use crate::editor_file::EditorFile;
+use crate::editor_panes::EditorPanes;
+
+const LARGE_FILE_DEFAULT_MB: u64 = 64;
+const CHUNK_BYTES: usize = 4 * 1024 * 1024;
+const PROGRESS_INTERVAL_SECONDS: f64 = 0.25;
+const BLOCK_LINES: usize = 1024;      // Lines decoded at once, and around the view
+const MAX_DECODED_BLOCKS: usize = 64; // The blocks used least recently go back to empty lines
+
+static LARGE_FILE_BYTES: OnceCell<u64> = OnceCell::new();
+
+/// What the indexing thread sends to the buffer
+enum LoadMessage {
+    Format(TextFormat),   // Of the first chunk, the final newline is not known yet
+    Lines(Vec<u64>, u64), // Where the next lines start and the bytes read so far
+    Done(u64),            // Size of the file
+    Failed(io::Error)
+}
+
+/// What the searching thread sends to the buffer
+enum SearchMessage {
+    Progress(u64),                 // Bytes searched so far
+    Found(Option<(usize, usize)>), // Column and line of the first match
+    Failed(io::Error)
+}
+
+pub struct LargeFileLoader {
+    receiver: Receiver<LoadMessage>,
+    pub total: u64, // Size of the file
+    pub read: u64,  // Bytes indexed so far
+    started_at: f64,
+    last_progress: f64
+}
+
+struct LargeSearch {
+    needle: String,
+    receiver: Receiver<SearchMessage>,
+    total: u64,
+    read: u64,
+    last_progress: f64
+}
+
+/// The line index of a large buffer, its text has an empty line for every line not decoded
+pub struct LargeText {
+    path: PathBuf,
+    starts: Vec<u64>,             // Where every line found so far starts in the file
+    end: Option<u64>,             // Where the last line ends, once the whole file is indexed
+    decoded: Vec<(usize, usize)>, // Decoded blocks and the line they were decoded up to, oldest first
+    search: Option<LargeSearch>,
+    pub target: Option<(usize, usize)> // Column and line the cursor goes to once indexed
+}
+
+/// Read 'large_file_size' from cal.conf, once at startup
+pub fn set_large_file_threshold(config: &EditorConfig) {
+    let megabytes = config.get_usize("large_file_size", LARGE_FILE_DEFAULT_MB as usize) as u64;
+    let _ = LARGE_FILE_BYTES.set(megabytes * 1024 * 1024);
+}
+
+/// The file at 'path' is opened in large file mode
+pub fn is_large_file(path: &Path) -> bool {
+    let threshold = *LARGE_FILE_BYTES.get().unwrap_or(&(LARGE_FILE_DEFAULT_MB * 1024 * 1024));
+    path.metadata().is_ok_and(|m| m.len() >= threshold)
+}
+
+/// Bytes of a code unit, 2 in UTF-16
+fn unit_len(encoding: Encoding) -> usize {
+    match encoding {
+        Encoding::Utf16Le | Encoding::Utf16Be => 2,
+        _ => 1
+    }
+}
+
+fn unit_value(unit: &[u8], encoding: Encoding) -> u16 {
+    match encoding {
+        Encoding::Utf16Le => u16::from_le_bytes([unit[0], unit[1]]),
+        Encoding::Utf16Be => u16::from_be_bytes([unit[0], unit[1]]),
+        _ => unit[0] as u16
+    }
+}
+
+/// The character a line ends with, a CRLF line is split at its LF and loses the CR
+fn line_break(line_ending: LineEnding) -> char {
+    match line_ending {
+        LineEnding::Cr => '\r',
+        _ => '\n'
+    }
+}
+
+fn strip_cr(line: &str, line_ending: LineEnding) -> &str {
+    match line_ending {
+        LineEnding::CrLf => line.strip_suffix('\r').unwrap_or(line),
+        _ => line
+    }
+}
+
+/// Encoding and line endings of a file from its first chunk
+fn detect_format(bytes: &[u8]) -> TextFormat {
+    // A character cut at the end of the chunk does not make it invalid UTF-8
+    let valid = match std::str::from_utf8(bytes) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => bytes.len()
+    };
+    let encoding = Encoding::detect(&bytes[..valid]);
+
+    TextFormat { encoding, line_ending: LineEnding::detect(&encoding.decode(bytes)), final_newline: false }
+}
+
+/// Where the decoded part of 'bytes' ends: after the last line break,
+/// so no character is cut in two
+fn split_point(encoding: Encoding, bytes: &[u8]) -> Option<usize> {
+    match encoding {
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let big_endian = encoding == Encoding::Utf16Be;
+            (0..bytes.len() / 2).rev().find(|&i| {
+                let pair = [bytes[2 * i], bytes[2 * i + 1]];
+                let unit = if big_endian { u16::from_be_bytes(pair) } else { u16::from_le_bytes(pair) };
+                unit == b'\n' as u16 || unit == b'\r' as u16
+            }).map(|i| 2 * i + 2)
+        }
+        _ => bytes.iter().rposition(|&b| b == b'\n' || b == b'\r').map(|i| i + 1)
+    }
+}
+
+/// Read the file chunk by chunk, sending where every line starts, returns its size
+fn index_lines(path: &Path, sender: &Sender<LoadMessage>) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut chunk = vec![0u8; CHUNK_BYTES];
+    let mut pending: Vec<u8> = vec![]; // Half a UTF-16 unit
+    let mut offset = 0u64;             // Where 'pending' starts in the file
+    let mut format: Option<TextFormat> = None;
+
+    loop {
+        let n = file.read(&mut chunk)?;
+        pending.extend_from_slice(&chunk[..n]);
+
+        let format = *format.get_or_insert_with(|| {
+            let format = detect_format(&pending);
+            let _ = sender.send(LoadMessage::Format(format));
+            format
+        });
+
+        let unit = unit_len(format.encoding);
+        let line_break = line_break(format.line_ending) as u16;
+        let whole = pending.len() / unit * unit;
+
+        let starts: Vec<u64> = pending[..whole].chunks_exact(unit).enumerate()
+            .filter(|(_, u)| unit_value(u, format.encoding) == line_break)
+            .map(|(i, _)| offset + ((i + 1) * unit) as u64)
+            .collect();
+
+        offset += whole as u64;
+        pending.drain(..whole);
+
+        if sender.send(LoadMessage::Lines(starts, offset)).is_err() {
+            return Err(io::Error::other("the buffer was closed"));
+        }
+
+        if n == 0 {
+            return Ok(offset + pending.len() as u64);
+        }
+    }
+}
+
+/// Find the first line holding 'needle', reading the file chunk by chunk
+fn search_lines(path: &Path, needle: &str, sender: &Sender<SearchMessage>) -> io::Result<Option<(usize, usize)>> {
+    let mut file = File::open(path)?;
+    let mut chunk = vec![0u8; CHUNK_BYTES];
+    let mut pending: Vec<u8> = vec![];
+    let mut carry = String::new(); // Start of a line whose end is not read yet
+    let mut read = 0u64;
+    let mut line_index = 0;
+    let mut format: Option<TextFormat> = None;
+
+    loop {
+        let n = file.read(&mut chunk)?;
+        read += n as u64;
+        pending.extend_from_slice(&chunk[..n]);
+
+        let first = format.is_none();
+        let format = *format.get_or_insert_with(|| detect_format(&pending));
+
+        let end = if n == 0 { pending.len() } else { split_point(format.encoding, &pending).unwrap_or(0) };
+        if end == 0 && n > 0 {
+            continue; // A line longer than a chunk
+        }
+
+        let decoded = if first { format.encoding.decode(&pending[..end]) } else { format.encoding.decode_part(&pending[..end]) };
+        pending.drain(..end);
+        carry.push_str(&decoded);
+
+        let line_break = line_break(format.line_ending);
+        let complete = carry.rfind(line_break).map_or(0, |i| i + line_break.len_utf8());
+        for line in carry[..complete].split_terminator(line_break) {
+            let line = strip_cr(line, format.line_ending);
+            if let Some(byte) = line.find(needle) {
+                return Ok(Some((line[..byte].chars().count(), line_index)));
+            }
+            line_index += 1;
+        }
+        carry.drain(..complete);
+
+        // What is left is the last line, without a newline after it
+        if n == 0 {
+            return Ok(carry.find(needle).map(|byte| (carry[..byte].chars().count(), line_index)));
+        }
+
+        if sender.send(SearchMessage::Progress(read)).is_err() {
+            return Err(io::Error::other("the search was replaced"));
+        }
+    }
+}
+
+impl LargeFileLoader {
+    /// Start indexing the file at 'path' in the background
+    pub fn start(path: &Path) -> LargeFileLoader {
+        let (sender, receiver) = mpsc::channel();
+        let total = path.metadata().map_or(0, |m| m.len());
+        let path: PathBuf = path.to_path_buf();
+
+        thread::spawn(move || {
+            let message = match index_lines(&path, &sender) {
+                Ok(size) => LoadMessage::Done(size),
+                Err(e) => LoadMessage::Failed(e)
+            };
+            let _ = sender.send(message);
+        });
+
+        let now = get_time();
+        LargeFileLoader { receiver, total, read: 0, started_at: now, last_progress: now }
+    }
+
+    /// Indexed part of the file in percent
+    pub fn percent(&self) -> u64 {
+        (self.read * 100).checked_div(self.total).unwrap_or(100)
+    }
+}
+
+impl LargeText {
+    pub fn new(path: &Path) -> LargeText {
+        LargeText { path: path.to_path_buf(), starts: vec![], end: None, decoded: vec![], search: None, target: None }
+    }
+
+    /// Where 'line' starts in the file, after the byte order mark
+    pub fn line_start(&self, line: usize) -> Option<u64> {
+        self.starts.get(line).copied()
+    }
+
+    /// Lines whose end is known, the last one found is not while indexing
+    fn complete_lines(&self) -> usize {
+        if self.end.is_some() { self.starts.len() } else { self.starts.len().saturating_sub(1) }
+    }
+
+    /// Where 'line' ends in the file, before its line break
+    fn line_end(&self, line: usize, format: TextFormat) -> Option<u64> {
+        match self.starts.get(line + 1) {
+            Some(next) => Some(next - unit_len(format.encoding) as u64),
+            None => self.end
+        }
+    }
+
+    /// The whole file is indexed, a line break at its very end is the final newline
+    fn finish(&mut self, size: u64, format: &mut TextFormat) {
+        format.final_newline = self.starts.len() > 1 && self.starts.last() == Some(&size);
+        if format.final_newline {
+            self.starts.pop();
+        }
+        self.end = Some(if format.final_newline { size - unit_len(format.encoding) as u64 } else { size });
+    }
+
+    /// Read the lines of 'lines' from the disk into 'text'
+    fn read_lines(&self, text: &mut [String], format: TextFormat, lines: Range<usize>) -> io::Result<()> {
+        let start = self.starts[lines.start];
+        let end = self.line_end(lines.end - 1, format).unwrap_or(start);
+
+        let mut bytes = vec![0u8; end.saturating_sub(start) as usize];
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut bytes)?;
+
+        let decoded = format.encoding.decode_part(&bytes);
+        for (slot, line) in text[lines].iter_mut().zip(decoded.split(line_break(format.line_ending))) {
+            *slot = strip_cr(line, format.line_ending).to_string();
+        }
+        Ok(())
+    }
+
+    /// Decode the blocks of the lines from 'from' to 'to' not decoded yet
+    pub fn decode(&mut self, text: &mut [String], format: TextFormat, from: usize, to: usize) {
+        let to = to.min(self.complete_lines()).min(text.len());
+        if from >= to {
+            return;
+        }
+
+        for block in from / BLOCK_LINES..=(to - 1) / BLOCK_LINES {
+            let lines = block * BLOCK_LINES..((block + 1) * BLOCK_LINES).min(self.complete_lines()).min(text.len());
+
+            // Decoded already, only kept longer
+            if let Some(index) = self.decoded.iter().position(|&d| d == (block, lines.end)) {
+                let used = self.decoded.remove(index);
+                self.decoded.push(used);
+                continue;
+            }
+
+            // A file changed on the disk is read again by the watcher (see editor_watch.rs),
+            // until then the lines that could not be read stay empty
+            let _ = self.read_lines(text, format, lines.clone());
+            self.decoded.retain(|&(b, _)| b != block);
+            self.decoded.push((block, lines.end));
+
+            if self.decoded.len() > MAX_DECODED_BLOCKS {
+                let (oldest, end) = self.decoded.remove(0);
+                for line in text.iter_mut().take(end).skip(oldest * BLOCK_LINES) {
+                    *line = String::new();
+                }
+            }
+        }
+    }
+
+    /// Search the file on the disk for 'needle' in another thread,
+    /// replacing a search still going on
+    pub fn search(&mut self, needle: &str) {
+        let (sender, receiver) = mpsc::channel();
+        let total = self.path.metadata().map_or(0, |m| m.len());
+        let (path, thread_needle) = (self.path.clone(), needle.to_string());
+
+        thread::spawn(move || {
+            let message = match search_lines(&path, &thread_needle, &sender) {
+                Ok(found) => SearchMessage::Found(found),
+                Err(e) => SearchMessage::Failed(e)
+            };
+            let _ = sender.send(message);
+        });
+
+        self.search = Some(LargeSearch { needle: needle.to_string(), receiver, total, read: 0, last_progress: get_time() });
+    }
+}
+
+/// Give the buffer an empty line for every line indexed so far, and show the progress
+fn receive_lines(file: &mut EditorFile, console: &mut EditorConsole, now: f64) {
+    let name = file.name();
+    let EditorFile { text, format, saved_format, loading, large, .. } = file;
+    let (Some(loader), Some(large)) = (loading.as_mut(), large.as_mut()) else {
+        return;
+    };
+
+    let mut finished = None;
+    while finished.is_none() {
+        match loader.receiver.try_recv() {
+            Ok(LoadMessage::Format(found)) => {
+                *format = found;
+                large.starts.push(found.encoding.bom_len() as u64);
+            }
+            Ok(LoadMessage::Lines(starts, read)) => {
+                loader.read = read;
+                large.starts.extend(starts);
+            }
+            Ok(LoadMessage::Done(size)) => finished = Some(Ok(size)),
+            Ok(LoadMessage::Failed(e)) => finished = Some(Err(e.to_string())),
+            Err(TryRecvError::Empty) => break,
+            Err(TryRecvError::Disconnected) => finished = Some(Err(String::from("the loading stopped")))
+        }
+    }
+
+    if let Some(Ok(size)) = finished {
+        large.finish(size, format);
+    }
+    *saved_format = *format;
+    text.resize(large.starts.len().max(1), String::new());
+
+    match finished {
+        Some(Ok(_)) => {
+            let seconds = now - loader.started_at;
+            *loading = None;
+            if console.prompt.is_none() {
+                console.message(&format!("Loaded {}, {} lines in {:.1}s", name, text.len(), seconds));
+            }
+        }
+        Some(Err(e)) => {
+            *loading = None;
+            console.message(&format!("Cannot read all of {}: {}, the lines read so far are shown read-only", name, e));
+        }
+        None => {
+            if now - loader.last_progress >= PROGRESS_INTERVAL_SECONDS && console.prompt.is_none() {
+                loader.last_progress = now;
+                console.message(&format!("Loading {}: {}%, {} lines", name, loader.percent(), text.len()));
+            }
+        }
+    }
+}
+
+/// Move the cursor to the match of a finished ':f', as the focused pane's jump if it shows the file
+fn receive_search(file: &mut EditorFile, panes: &mut EditorPanes, console: &mut EditorConsole, now: f64) {
+    let name = file.name();
+    let search = match file.large.as_mut().and_then(|l| l.search.as_mut()) {
+        Some(search) => search,
+        None => return
+    };
+
+    let mut finished = None;
+    while finished.is_none() {
+        match search.receiver.try_recv() {
+            Ok(SearchMessage::Progress(read)) => search.read = read,
+            Ok(SearchMessage::Found(found)) => finished = Some(Ok(found)),
+            Ok(SearchMessage::Failed(e)) => finished = Some(Err(e.to_string())),
+            Err(TryRecvError::Empty) => break,
+            Err(TryRecvError::Disconnected) => finished = Some(Err(String::from("the search stopped")))
+        }
+    }
+
+    let found = match finished {
+        Some(found) => found,
+        None => {
+            if now - search.last_progress >= PROGRESS_INTERVAL_SECONDS && console.prompt.is_none() {
+                search.last_progress = now;
+                let percent = (search.read * 100).checked_div(search.total).unwrap_or(100);
+                console.message(&format!("Searching {} for '{}': {}%", name, search.needle, percent));
+            }
+            return;
+        }
+    };
+
+    let needle = search.needle.clone();
+    if let Some(large) = file.large.as_mut() {
+        large.search = None;
+    }
+
+    match found {
+        Ok(Some((column, line))) => {
+            let pane = panes.focused_mut();
+            if pane.buffer == file.id {
+                pane.jumps.push(&file.path, pane.cursor.xy);
+            }
+
+            file.goto(line, column);
+            show_in_focused_pane(file, panes);
+            console.message(&format!("Found at line {}", line + 1));
+        }
+        Ok(None) => console.message(&format!("'{}' not found", needle)),
+        Err(e) => console.message(&format!("Cannot search {}: {}", name, e))
+    }
+}
+
+/// The focused pane showing 'file' takes its cursor, the panes otherwise give theirs to the current buffer
+fn show_in_focused_pane(file: &EditorFile, panes: &mut EditorPanes) {
+    let pane = panes.focused_mut();
+    if pane.buffer == file.id {
+        pane.cursor = file.cursor;
+        pane.scroll = file.scroll;
+    }
+}
+
+/// Go to the line the file was opened at, once it is indexed or the indexing stopped
+fn go_to_target(file: &mut EditorFile, panes: &mut EditorPanes) {
+    let target = match &mut file.large {
+        Some(large) if file.loading.is_none() || large.target.is_some_and(|(_, line)| line + 1 < file.text.len()) => large.target.take(),
+        _ => None
+    };
+
+    if let Some((column, line)) = target {
+        file.goto(line, column);
+        show_in_focused_pane(file, panes);
+    }
+}
+
+/// Decode the lines the panes showing a large buffer need, with a block around them
+fn decode_shown(file: &mut EditorFile, panes: &EditorPanes) {
+    let EditorFile { id, text, format, large, .. } = file;
+    let large = match large {
+        Some(large) => large,
+        None => return
+    };
+
+    for pane in panes.panes.iter().filter(|p| p.buffer == *id) {
+        let (first, last) = (pane.scroll.min(pane.cursor.xy.1), pane.scroll.max(pane.cursor.xy.1) + pane.view_lines);
+        large.decode(text, *format, first.saturating_sub(BLOCK_LINES), last + BLOCK_LINES);
+    }
+}
+
+/// Index, search and decode the large buffers for the next frame
+pub fn update_large_files(buffers: &mut EditorBuffers, panes: &mut EditorPanes, console: &mut EditorConsole) {
+    let now = get_time();
+
+    for file in buffers.files.iter_mut().filter(|f| f.large.is_some()) {
+        receive_lines(file, console, now);
+        go_to_target(file, panes);
+        receive_search(file, panes, console, now);
+        decode_shown(file, panes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    /// Index and decode 'bytes' the way a large buffer does
+    fn large_lines(name: &str, bytes: &[u8]) -> (Vec<String>, TextFormat) {
+        let path = env::temp_dir().join(format!("muse-large-{}-{}", process::id(), name));
+        fs::write(&path, bytes).unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let size = index_lines(&path, &sender).unwrap();
+
+        let mut large = LargeText::new(&path);
+        let mut format = TextFormat::new();
+        for message in receiver.try_iter() {
+            match message {
+                LoadMessage::Format(found) => {
+                    format = found;
+                    large.starts.push(found.encoding.bom_len() as u64);
+                }
+                LoadMessage::Lines(starts, _) => large.starts.extend(starts),
+                _ => {}
+            }
+        }
+        large.finish(size, &mut format);
+
+        let mut text = vec![String::new(); large.starts.len()];
+        large.decode(&mut text, format, 0, large.starts.len());
+        fs::remove_file(&path).unwrap();
+
+        (text, format)
+    }
+
+    /// A large buffer must hold the same lines as a small one
+    fn same_as_small(name: &str, bytes: &[u8]) {
+        let (text, format) = large_lines(name, bytes);
+        let (small, small_format) = crate::editor_encoding::decode_file(bytes);
+
+        assert_eq!(text, small);
+        assert!(format == small_format);
+    }
+
+    #[test]
+    fn lines_match_a_small_buffer() {
+        same_as_small("lf", b"one\ntwo\n\nthree");
+        same_as_small("final", b"one\ntwo\n");
+        same_as_small("crlf", b"one\r\ntwo\r\n");
+        same_as_small("cr", b"one\rtwo");
+        same_as_small("empty", b"");
+        same_as_small("newline", b"\n");
+        same_as_small("bom", b"\xEF\xBB\xBFbom\nline\n");
+        same_as_small("utf16", b"\xFF\xFEa\0\n\0b\0");
+    }
+
+    #[test]
+    fn only_shown_blocks_stay_decoded() {
+        let path = env::temp_dir().join(format!("muse-large-{}-blocks", process::id()));
+        let lines: Vec<String> = (0..BLOCK_LINES * (MAX_DECODED_BLOCKS + 2)).map(|i| i.to_string()).collect();
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let mut large = LargeText::new(&path);
+        large.starts = std::iter::once(0).chain(lines.iter().scan(0, |at, l| { *at += l.len() as u64 + 1; Some(*at) }))
+            .take(lines.len()).collect();
+        let mut format = TextFormat::new();
+        large.finish(fs::metadata(&path).unwrap().len(), &mut format);
+
+        let mut text = vec![String::new(); lines.len()];
+        for block in 0..MAX_DECODED_BLOCKS + 2 {
+            large.decode(&mut text, format, block * BLOCK_LINES, block * BLOCK_LINES + 1);
+        }
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(large.decoded.len(), MAX_DECODED_BLOCKS);
+        assert!(text[0].is_empty() && text[BLOCK_LINES].is_empty());
+        assert_eq!(text[2 * BLOCK_LINES], lines[2 * BLOCK_LINES]);
+        assert_eq!(text.last(), lines.last());
+    }
+
+    #[test]
+    fn search_finds_the_line_and_column() {
+        let path = env::temp_dir().join(format!("muse-large-{}-search", process::id()));
+        fs::write(&path, "first\r\nsecond line\r\nthird\r\n").unwrap();
+
+        let (sender, _receiver) = mpsc::channel();
+        let found = search_lines(&path, "line", &sender).unwrap();
+        let missing = search_lines(&path, "fourth", &sender).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(found, Some((7, 1)));
+        assert_eq!(missing, None);
+    }
+}
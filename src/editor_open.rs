@@ -0,0 +1,165 @@
+// Open module, hands a directory over to the native file manager for ':od'.
+//
+// 'xdg-open' is used unless cal.conf sets 'file_manager', a command
+// where '{}' stands for the directory, or which gets it as its last argument.
+//
+// Processes are started through the ProcessSpawner trait
+// so something else can stand in for the real system.
+
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::editor_config::{CONFIG_FILE, EditorConfig};
+
+const DEFAULT_FILE_MANAGER: &str = "xdg-open";
+const DIRECTORY_PLACEHOLDER: &str = "{}";
+
+pub trait ProcessSpawner {
+    /// Start 'program' without waiting for it to finish
+    fn spawn(&self, program: &str, args: &[String]) -> io::Result<()>;
+}
+
+/// Starts real processes, detached from the editor's input and output
+pub struct SystemSpawner;
+
+impl ProcessSpawner for SystemSpawner {
+    fn spawn(&self, program: &str, args: &[String]) -> io::Result<()> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        // Collect the exit status so no zombie process is left behind
+        thread::spawn(move || {
+            let _ = child.wait();
+        });
+
+        Ok(())
+    }
+}
+
+pub struct DirectoryOpener {
+    command: Vec<String>,
+    spawner: Box<dyn ProcessSpawner>
+}
+
+impl DirectoryOpener {
+    /// Opener using 'file_manager' from cal.conf, or 'xdg-open'
+    pub fn new(config: &EditorConfig, spawner: Box<dyn ProcessSpawner>) -> DirectoryOpener {
+        let command = config.get_str("file_manager")
+            .map(|c| c.split_whitespace().map(|w| w.to_string()).collect::<Vec<String>>())
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| vec![DEFAULT_FILE_MANAGER.to_string()]);
+
+        DirectoryOpener { command, spawner }
+    }
+
+    /// Open 'dir' in the file manager, the error is ready to be shown in the console
+    pub fn open(&self, dir: &Path) -> Result<(), String> {
+        if !dir.is_dir() {
+            return Err(format!("{} is not a directory", dir.display()));
+        }
+
+        let program = &self.command[0];
+        let dir = dir.display().to_string();
+
+        let mut args: Vec<String> = self.command[1..].iter().map(|a| a.replace(DIRECTORY_PLACEHOLDER, &dir)).collect();
+        if !self.command[1..].iter().any(|a| a.contains(DIRECTORY_PLACEHOLDER)) {
+            args.push(dir);
+        }
+
+        self.spawner.spawn(program, &args).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => format!("'{}' was not found, set 'file_manager' in {}", program, CONFIG_FILE),
+            io::ErrorKind::PermissionDenied => format!("'{}' cannot be executed: {}", program, e),
+            _ => format!("Cannot start '{}': {}", program, e)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::env;
+    use std::fs;
+    use std::rc::Rc;
+
+    type Spawned = Rc<RefCell<Vec<(String, Vec<String>)>>>;
+
+    /// Records the processes it is asked to start, failing with 'error' if set
+    struct StubSpawner {
+        spawned: Spawned,
+        error: Option<io::ErrorKind>
+    }
+
+    impl ProcessSpawner for StubSpawner {
+        fn spawn(&self, program: &str, args: &[String]) -> io::Result<()> {
+            self.spawned.borrow_mut().push((program.to_string(), args.to_vec()));
+            match self.error {
+                Some(kind) => Err(io::Error::from(kind)),
+                None => Ok(())
+            }
+        }
+    }
+
+    /// An opener reading 'file_manager' from a config with 'line', and what it spawned
+    fn opener(name: &str, line: &str, error: Option<io::ErrorKind>) -> (DirectoryOpener, Spawned) {
+        let path = env::temp_dir().join(format!("muse-open-{}-{}.conf", name, std::process::id()));
+        fs::write(&path, line).unwrap();
+        let config = EditorConfig::load(&path);
+        let _ = fs::remove_file(&path);
+
+        let spawned = Rc::new(RefCell::new(vec![]));
+        (DirectoryOpener::new(&config, Box::new(StubSpawner { spawned: spawned.clone(), error })), spawned)
+    }
+
+    #[test]
+    fn placeholder_is_replaced_by_the_directory() {
+        let (opener, spawned) = opener("placeholder", "file_manager = fm --path={} --new", None);
+        let dir = env::temp_dir();
+
+        assert_eq!(opener.open(&dir), Ok(()));
+        let expected = vec![format!("--path={}", dir.display()), String::from("--new")];
+        assert_eq!(*spawned.borrow(), vec![(String::from("fm"), expected)]);
+    }
+
+    #[test]
+    fn directory_is_appended_without_a_placeholder() {
+        let (opener, spawned) = opener("appended", "file_manager = fm --new", None);
+        let dir = env::temp_dir();
+
+        assert_eq!(opener.open(&dir), Ok(()));
+        let expected = vec![String::from("--new"), dir.display().to_string()];
+        assert_eq!(*spawned.borrow(), vec![(String::from("fm"), expected)]);
+    }
+
+    #[test]
+    fn xdg_open_without_a_file_manager() {
+        let (opener, spawned) = opener("default", "", None);
+        let dir = env::temp_dir();
+
+        assert_eq!(opener.open(&dir), Ok(()));
+        assert_eq!(*spawned.borrow(), vec![(String::from(DEFAULT_FILE_MANAGER), vec![dir.display().to_string()])]);
+    }
+
+    #[test]
+    fn missing_program_points_at_the_config() {
+        let (opener, _) = opener("missing", "file_manager = nope", Some(io::ErrorKind::NotFound));
+
+        let error = opener.open(&env::temp_dir()).unwrap_err();
+        assert_eq!(error, format!("'nope' was not found, set 'file_manager' in {}", CONFIG_FILE));
+    }
+
+    #[test]
+    fn no_process_for_a_missing_directory() {
+        let (opener, spawned) = opener("not-a-dir", "", None);
+
+        assert!(opener.open(Path::new("/no/such/directory")).is_err());
+        assert!(spawned.borrow().is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+// Palette module, the colours the editor is drawn with.
+//
+// The palettes live in 'palletes/<name>.pal', one colour per line
+// in the form of the consts below ('CURSOR_COLOR: Color = Color::from_rgba(r, g, b, a)').
+// Colours a palette leaves out keep their melpomene value.
+//
+// The palette is chosen at startup, with '--palette <name>'
+// or 'palette = <name>' in cal.conf, and changed with ':epa <name>'.
+
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+
+use macroquad::prelude::Color;
+use regex::Regex;
+
+pub const PALETTE_DIR: &str = "palletes";
+pub const PALETTE_EXTENSION: &str = "pal";
+
+// Leaked on every change, a palette is small and changed by hand
+static PALETTE: RwLock<Option<&'static EditorPalette>> = RwLock::new(None);
+
+// Melpomene, the palette built into the editor
+const BACKGROUND_COLOR: Color         = Color::from_rgba(8, 0, 15, 255);        // Theater dark — emotional void
+const IDENTIFIER_COLOR: Color         = Color::from_rgba(190, 140, 230, 255);   // Pale violet — fateful names
+const PUNCTUATION_COLOR: Color        = Color::from_rgba(255, 255, 255, 255);   // White — clarity in despair
+const CONTROL_FLOW_COLOR: Color       = Color::from_rgba(130, 100, 255, 255);   // Tragic blue — falling motion
+const STORAGE_CLASS_COLOR: Color      = Color::from_rgba(255, 70, 110, 255);    // Crimson sorrow — bleeding intent
+const TYPE_QUALIFIER_COLOR: Color     = Color::from_rgba(255, 210, 90, 255);    // Pale gold — faded grandeur
+const COMPOSITE_TYPE_COLOR: Color     = Color::from_rgba(140, 0, 180, 255);     // Dark purple — structure of fate
+const MISC_COLOR: Color               = Color::from_rgba(100, 130, 200, 255);   // Twilight blue — haunting echo
+const DATA_TYPE_COLOR: Color          = Color::from_rgba(60, 190, 150, 255);    // Teal — fragile balance
+const NUMBER_LITERAL_COLOR: Color     = Color::from_rgba(255, 235, 150, 255);   // Candle gold — memory counts
+const STRING_LITERAL_COLOR: Color     = Color::from_rgba(255, 120, 170, 255);   // Mourning rose — spoken sorrow
+const COMMENT_COLOR: Color            = Color::from_rgba(100, 90, 110, 255);    // Smoke gray — whispered lament
+const CURSOR_COLOR: Color             = Color::from_rgba(255, 0, 130, 255);     // Magenta glow — pulse of pain
+const MACRO_COLOR: Color              = Color::from_rgba(255, 110, 0, 255);     // Ember orange — spark of catharsis
+const MAIN_COLOR: Color               = Color::from_rgba(180, 60, 255, 255);    // Regal violet — tragic beauty
+
+pub struct EditorPalette {
+    pub background: Color,
+    pub identifier: Color,
+    pub punctuation: Color,
+    pub control_flow: Color,
+    pub storage_class: Color,
+    pub type_qualifier: Color,
+    pub composite_type: Color, // Also the tabs, borders and bars
+    pub misc: Color,
+    pub data_type: Color,
+    pub number_literal: Color,
+    pub string_literal: Color,
+    pub comment: Color,
+    pub cursor: Color,
+    pub macros: Color,
+    pub main: Color
+}
+
+impl EditorPalette {
+    /// Palette constructor, melpomene
+    pub fn new() -> EditorPalette {
+        EditorPalette {
+            background: BACKGROUND_COLOR,
+            identifier: IDENTIFIER_COLOR,
+            punctuation: PUNCTUATION_COLOR,
+            control_flow: CONTROL_FLOW_COLOR,
+            storage_class: STORAGE_CLASS_COLOR,
+            type_qualifier: TYPE_QUALIFIER_COLOR,
+            composite_type: COMPOSITE_TYPE_COLOR,
+            misc: MISC_COLOR,
+            data_type: DATA_TYPE_COLOR,
+            number_literal: NUMBER_LITERAL_COLOR,
+            string_literal: STRING_LITERAL_COLOR,
+            comment: COMMENT_COLOR,
+            cursor: CURSOR_COLOR,
+            macros: MACRO_COLOR,
+            main: MAIN_COLOR
+        }
+    }
+
+    /// Read the palette 'palletes/<name>.pal'
+    pub fn load(name: &str) -> Result<EditorPalette, String> {
+        let path = Path::new(PALETTE_DIR).join(format!("{}.{}", name, PALETTE_EXTENSION));
+        let content = fs::read_to_string(&path).map_err(|_| {
+            format!("No palette named '{}', the palettes are: {}", name, palette_names().join(", "))
+        })?;
+
+        let pattern = Regex::new(r"(\w+)_COLOR\s*:\s*Color\s*=\s*Color::from_rgba\(\s*(\d+)\s*,\s*(\d+)\s*,\s*(\d+)\s*,\s*(\d+)\s*\)").unwrap();
+        let mut palette = EditorPalette::new();
+
+        for caps in pattern.captures_iter(&content) {
+            let channel = |i: usize| caps[i].parse::<u8>().unwrap_or(255);
+            let color = Color::from_rgba(channel(2), channel(3), channel(4), channel(5));
+
+            let field = match &caps[1] {
+                "BACKGROUND" => &mut palette.background,
+                "IDENTIFIER" => &mut palette.identifier,
+                "PUNCTUATION" => &mut palette.punctuation,
+                "CONTROL_FLOW" => &mut palette.control_flow,
+                "STORAGE_CLASS" => &mut palette.storage_class,
+                "TYPE_QUALIFIER" => &mut palette.type_qualifier,
+                "COMPOSITE_TYPE" => &mut palette.composite_type,
+                "MISC" => &mut palette.misc,
+                "DATA_TYPE" => &mut palette.data_type,
+                "NUMBER_LITERAL" => &mut palette.number_literal,
+                "STRING_LITERAL" => &mut palette.string_literal,
+                "COMMENT" => &mut palette.comment,
+                "CURSOR" => &mut palette.cursor,
+                "MACRO" => &mut palette.macros,
+                "MAIN" => &mut palette.main,
+                _ => continue
+            };
+            *field = color;
+        }
+
+        Ok(palette)
+    }
+}
+
+/// Names of the palettes in PALETTE_DIR, sorted
+pub fn palette_names() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(PALETTE_DIR)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == PALETTE_EXTENSION))
+            .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().to_string()))
+            .collect())
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// Use 'palette' from now on
+pub fn set_palette(palette: EditorPalette) {
+    *PALETTE.write().unwrap() = Some(Box::leak(Box::new(palette)));
+}
+
+/// The palette in use
+pub fn palette() -> &'static EditorPalette {
+    if let Some(palette) = *PALETTE.read().unwrap() {
+        return palette;
+    }
+
+    set_palette(EditorPalette::new());
+    palette()
+}
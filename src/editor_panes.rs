@@ -0,0 +1,338 @@
+// Split window module, the text area is divided into panes
+// that show different files or the same one, each with its
+// own cursor, scroll and jump list.
+//
+// LCTRL + LALT + V             : Split the focused pane side by side
+// LCTRL + LALT + H             : Split the focused pane one above the other
+// LCTRL + LALT + W             : Close the focused pane
+// LCTRL + LALT + Arrows        : Move focus to the pane in that direction
+// LCTRL + LALT + LSHIFT + Arrows : Move the border of the focused pane
+//
+// These are the default keys of the 'pane.*' commands (see editor_keymap.rs).
+
+use macroquad::prelude::*;
+
+use crate::editor_audio::EditorAudio;
+use crate::editor_buffers::EditorBuffers;
+use crate::editor_commands::PaneCommand;
+use crate::editor_cursor::EditorCursor;
+use crate::editor_jumps::EditorJumpList;
+use crate::editor_palette::palette;
+use crate::editor_text::*;
+
+const PANE_MIN_RATIO: f32 = 0.1;
+const PANE_RESIZE_STEP: f32 = 0.05;
+const PANE_BORDER_WIDTH: f32 = 1.5;
+
+pub struct EditorPane {
+    pub id: usize,
+    pub buffer: usize, // Id of the file shown
+    pub cursor: EditorCursor,
+    pub scroll: usize,
+    pub view_lines: usize,
+    pub jumps: EditorJumpList
+}
+
+/// Panes are the leaves of a tree of splits
+pub enum PaneLayout {
+    Pane(usize),
+    Split {
+        vertical: bool, // Side by side
+        ratio: f32,     // Share of the first child
+        first: Box<PaneLayout>,
+        second: Box<PaneLayout>
+    }
+}
+
+impl PaneLayout {
+    fn contains(&self, id: usize) -> bool {
+        match self {
+            PaneLayout::Pane(pane) => *pane == id,
+            PaneLayout::Split { first, second, .. } => first.contains(id) || second.contains(id)
+        }
+    }
+
+    /// Screen area of every pane inside 'area'
+    pub fn rects(&self, area: Rect, out: &mut Vec<(usize, Rect)>) {
+        match self {
+            PaneLayout::Pane(id) => out.push((*id, area)),
+            PaneLayout::Split { vertical, ratio, first, second } => {
+                let (a, b) = if *vertical {
+                    let w = area.w * ratio;
+                    (Rect::new(area.x, area.y, w, area.h), Rect::new(area.x + w, area.y, area.w - w, area.h))
+                } else {
+                    let h = area.h * ratio;
+                    (Rect::new(area.x, area.y, area.w, h), Rect::new(area.x, area.y + h, area.w, area.h - h))
+                };
+
+                first.rects(a, out);
+                second.rects(b, out);
+            }
+        }
+    }
+
+    /// Turn the pane 'id' into a split of itself and 'new_id'
+    fn split(&mut self, id: usize, new_id: usize, vertical: bool) {
+        match self {
+            PaneLayout::Pane(pane) if *pane == id => {
+                *self = PaneLayout::Split {
+                    vertical,
+                    ratio: 0.5,
+                    first: Box::new(PaneLayout::Pane(id)),
+                    second: Box::new(PaneLayout::Pane(new_id))
+                };
+            }
+            PaneLayout::Pane(_) => {}
+            PaneLayout::Split { first, second, .. } => {
+                first.split(id, new_id, vertical);
+                second.split(id, new_id, vertical);
+            }
+        }
+    }
+
+    /// Remove the pane 'id', its sibling takes the space of their split
+    fn remove(&mut self, id: usize) {
+        if let PaneLayout::Split { first, second, .. } = self {
+            let keep = if matches!(**first, PaneLayout::Pane(p) if p == id) {
+                Some(std::mem::replace(&mut **second, PaneLayout::Pane(0)))
+            } else if matches!(**second, PaneLayout::Pane(p) if p == id) {
+                Some(std::mem::replace(&mut **first, PaneLayout::Pane(0)))
+            } else {
+                None
+            };
+
+            match keep {
+                Some(keep) => *self = keep,
+                None => {
+                    first.remove(id);
+                    second.remove(id);
+                }
+            }
+        }
+    }
+
+    /// Move the border of the innermost split of the right orientation holding 'id'
+    fn resize(&mut self, id: usize, split_vertical: bool, delta: f32) -> bool {
+        if let PaneLayout::Split { vertical, ratio, first, second } = self {
+            if !first.contains(id) && !second.contains(id) {
+                return false;
+            }
+
+            if first.resize(id, split_vertical, delta) || second.resize(id, split_vertical, delta) {
+                return true;
+            }
+
+            if *vertical == split_vertical {
+                *ratio = (*ratio + delta).clamp(PANE_MIN_RATIO, 1.0 - PANE_MIN_RATIO);
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+pub struct EditorPanes {
+    pub panes: Vec<EditorPane>,
+    pub layout: PaneLayout,
+    pub focused: usize, // Id of the focused pane
+    next_id: usize
+}
+
+impl EditorPanes {
+    /// Panes constructor, a single pane showing the current buffer
+    pub fn new(buffers: &EditorBuffers) -> EditorPanes {
+        let file = buffers.current();
+
+        EditorPanes {
+            panes: vec![EditorPane {
+                id: 0,
+                buffer: file.id,
+                cursor: file.cursor,
+                scroll: file.scroll,
+                view_lines: file.view_lines,
+                jumps: EditorJumpList::new()
+            }],
+            layout: PaneLayout::Pane(0),
+            focused: 0,
+            next_id: 1
+        }
+    }
+
+    /// Panes of a saved session, 'layout' holds exactly their ids
+    pub fn restore(panes: Vec<EditorPane>, layout: PaneLayout, focused: usize) -> EditorPanes {
+        let next_id = panes.iter().map(|p| p.id + 1).max().unwrap_or(0);
+        EditorPanes { panes, layout, focused, next_id }
+    }
+
+    pub fn focused(&self) -> &EditorPane {
+        self.panes.iter().find(|p| p.id == self.focused).unwrap()
+    }
+
+    pub fn focused_mut(&mut self) -> &mut EditorPane {
+        let focused = self.focused;
+        self.panes.iter_mut().find(|p| p.id == focused).unwrap()
+    }
+
+    /// Give the focused pane's cursor and scroll to the file it shows,
+    /// so that the editing code only deals with the current buffer
+    pub fn enter(&mut self, buffers: &mut EditorBuffers) {
+        let pane = self.focused_mut();
+
+        match buffers.index_of(pane.buffer) {
+            Some(index) => {
+                buffers.current = index;
+
+                let file = buffers.current_mut();
+                file.cursor = pane.cursor;
+                file.scroll = pane.scroll;
+                file.view_lines = pane.view_lines;
+            }
+            // The file was closed, show whatever is current
+            None => pane.buffer = buffers.current().id
+        }
+    }
+
+    /// Take back the cursor and scroll, the current buffer may have been switched
+    pub fn leave(&mut self, buffers: &EditorBuffers) {
+        let file = buffers.current();
+        let pane = self.focused_mut();
+
+        pane.buffer = file.id;
+        pane.cursor = file.cursor;
+        pane.scroll = file.scroll;
+    }
+
+    /// Move the jumps of every pane along with the lines the edits of
+    /// each file inserted or removed, whichever pane or directive made them
+    pub fn shift_jumps(&mut self, buffers: &mut EditorBuffers) {
+        for file in buffers.files.iter_mut() {
+            for shift in file.history.take_line_shifts() {
+                for pane in self.panes.iter_mut() {
+                    pane.jumps.shift_lines(&file.path, shift);
+                }
+            }
+        }
+    }
+
+    /// Split the focused pane, the new pane shows the same file and gets the focus
+    pub fn split(&mut self, vertical: bool) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let focused = self.focused();
+        let pane = EditorPane {
+            id,
+            buffer: focused.buffer,
+            cursor: focused.cursor,
+            scroll: focused.scroll,
+            view_lines: focused.view_lines,
+            jumps: EditorJumpList::new()
+        };
+
+        self.panes.push(pane);
+        self.layout.split(self.focused, id, vertical);
+        self.focused = id;
+    }
+
+    /// Close the focused pane, the last one always stays
+    pub fn close(&mut self) {
+        if self.panes.len() == 1 {
+            return;
+        }
+
+        let id = self.focused;
+        self.layout.remove(id);
+        self.panes.retain(|p| p.id != id);
+
+        let mut rects = vec![];
+        self.layout.rects(Rect::new(0.0, 0.0, 1.0, 1.0), &mut rects);
+        self.focused = rects[0].0;
+    }
+
+    /// Focus the closest pane in the direction (dx, dy)
+    pub fn move_focus(&mut self, area: Rect, dx: f32, dy: f32) {
+        let mut rects = vec![];
+        self.layout.rects(area, &mut rects);
+
+        let from = match rects.iter().find(|(id, _)| *id == self.focused) {
+            Some((_, rect)) => rect.center(),
+            None => return
+        };
+
+        let target = rects.iter()
+            .filter(|(id, _)| *id != self.focused)
+            .map(|(id, rect)| (*id, rect.center() - from))
+            .filter(|(_, d)| d.x * dx + d.y * dy > 0.0)
+            .min_by(|(_, a), (_, b)| a.length().total_cmp(&b.length()));
+
+        if let Some((id, _)) = target {
+            self.focused = id;
+        }
+    }
+
+    /// Move the border of the focused pane
+    pub fn resize(&mut self, vertical: bool, delta: f32) {
+        self.layout.resize(self.focused, vertical, delta);
+    }
+
+    /// Draw every pane inside 'area'
+    pub fn draw(&mut self, area: Rect, buffers: &EditorBuffers, gts: &mut EditorGeneralTextStylizer) {
+        let mut rects = vec![];
+        self.layout.rects(area, &mut rects);
+
+        for (id, rect) in rects {
+            let focused = id == self.focused;
+            let pane = match self.panes.iter_mut().find(|p| p.id == id) {
+                Some(pane) => pane,
+                None => continue
+            };
+
+            let file = match buffers.index_of(pane.buffer) {
+                Some(index) => &buffers.files[index],
+                None => buffers.current()
+            };
+
+            // Another pane may have removed lines from the same file
+            pane.view_lines = visible_line_count(gts, rect);
+            pane.cursor.xy.1 = pane.cursor.xy.1.min(file.text.len().saturating_sub(1));
+            pane.scroll = pane.scroll.min(pane.cursor.xy.1);
+
+            draw(&file.text, pane.cursor, pane.scroll, rect, focused, file.large.is_none(), gts);
+
+            // Borders between panes
+            if rect.x > area.x {
+                draw_rectangle(rect.x, rect.y, PANE_BORDER_WIDTH, rect.h, palette().composite_type);
+            }
+            if rect.y > area.y {
+                draw_rectangle(rect.x, rect.y, rect.w, PANE_BORDER_WIDTH, palette().composite_type);
+            }
+        }
+    }
+}
+
+/// Pane splitting, focus and resizing, the 'pane.*' commands
+pub fn pane_navigation(command: PaneCommand, panes: &mut EditorPanes, buffers: &mut EditorBuffers, area: Rect, audio: &EditorAudio) {
+    match command {
+        PaneCommand::Split(_) => audio.play_return(),
+        PaneCommand::Close => audio.play_delete(),
+        PaneCommand::Focus(_) | PaneCommand::Resize(_) => audio.play_nav()
+    }
+    panes.leave(buffers);
+
+    match command {
+        PaneCommand::Split(vertical) => panes.split(vertical),
+        PaneCommand::Close => panes.close(),
+        PaneCommand::Focus(direction) => {
+            let (dx, dy) = direction.delta();
+            panes.move_focus(area, dx, dy);
+        }
+        PaneCommand::Resize(direction) => {
+            let (dx, dy) = direction.delta();
+            let vertical = dx != 0.0;
+            panes.resize(vertical, (dx + dy) * PANE_RESIZE_STEP);
+        }
+    }
+
+    panes.enter(buffers);
+}
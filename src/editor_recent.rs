@@ -0,0 +1,108 @@
+// Recent files module, the files shown in the editor, most recent first,
+// saved in the state directory so the list survives restarts.
+//
+// The console lists them while its input is empty, Enter opens the selected one,
+// and the files typed into the console rank recent ones first (see editor_fuzzy.rs).
+// The list holds up to 'recent_files_size' files (RECENT_DEFAULT_SIZE by default, set in cal.conf).
+//
+// LCTRL + 6 swaps between the current buffer and the one shown before it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::editor_buffers::EditorBuffers;
+use crate::editor_config::{EditorConfig, state_dir};
+
+const RECENT_FILE: &str = "recent";
+const RECENT_DEFAULT_SIZE: usize = 100;
+
+pub struct EditorRecentFiles {
+    pub paths: Vec<PathBuf>,    // Most recent first
+    pub alternate: Option<usize>, // File id of the buffer shown before the current one
+    capacity: usize,
+    path: PathBuf,
+    shown: Option<(usize, Option<PathBuf>)>, // File id and path of the buffer last shown
+    known_id: Option<usize>                  // Highest file id seen so far
+}
+
+impl EditorRecentFiles {
+    /// Load the saved list, files that no longer exist are left out
+    pub fn load(config: &EditorConfig) -> EditorRecentFiles {
+        let path = state_dir().join(RECENT_FILE);
+        let capacity = config.get_usize("recent_files_size", RECENT_DEFAULT_SIZE);
+
+        let mut paths: Vec<PathBuf> = fs::read_to_string(&path)
+            .map(|content| content.lines().filter(|l| !l.is_empty()).map(PathBuf::from).filter(|p| p.is_file()).collect())
+            .unwrap_or_default();
+        paths.truncate(capacity);
+
+        EditorRecentFiles { paths, alternate: None, capacity, path, shown: None, known_id: None }
+    }
+
+    fn save(&self) {
+        if let Some(dir) = self.path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+
+        let mut content = self.paths.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join("\n");
+        content.push('\n');
+        let _ = fs::write(&self.path, content);
+    }
+
+    /// Move 'path' to the top of the list
+    pub fn push(&mut self, path: &Path) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if self.paths.first() == Some(&path) {
+            return;
+        }
+
+        self.paths.retain(|p| *p != path);
+        self.paths.insert(0, path);
+        self.paths.truncate(self.capacity);
+        self.save();
+    }
+
+    /// Note the buffers opened and the one shown since the last frame,
+    /// files opened in the background count as well, before the shown one
+    pub fn update(&mut self, buffers: &EditorBuffers) {
+        let mut opened: Vec<(usize, &PathBuf)> = buffers.files.iter()
+            .filter(|f| self.known_id.is_none_or(|known| f.id > known))
+            .filter_map(|f| f.path.as_ref().map(|p| (f.id, p)))
+            .collect();
+        opened.sort();
+        for (_, path) in opened {
+            self.push(path);
+        }
+        self.known_id = buffers.files.iter().map(|f| f.id).max().max(self.known_id);
+
+        let file = buffers.current();
+        let shown = (file.id, file.path.clone());
+        if self.shown.as_ref() == Some(&shown) {
+            return;
+        }
+
+        if let Some((id, _)) = &self.shown && *id != file.id {
+            self.alternate = Some(*id);
+        }
+        if let Some(path) = &file.path {
+            self.push(path);
+        }
+        self.shown = Some(shown);
+    }
+
+    /// Switch to the buffer shown before the current one, false if it was closed
+    pub fn swap(&self, buffers: &mut EditorBuffers) -> bool {
+        match self.alternate.and_then(|id| buffers.index_of(id)) {
+            Some(index) if index != buffers.current => {
+                buffers.current = index;
+                true
+            }
+            _ => false
+        }
+    }
+
+    /// The list as shown in the console, relative to 'cwd' where possible
+    pub fn display(&self, cwd: &Path) -> Vec<String> {
+        self.paths.iter().map(|p| p.strip_prefix(cwd).unwrap_or(p).display().to_string()).collect()
+    }
+}
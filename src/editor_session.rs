@@ -0,0 +1,329 @@
+// Session module, remembers what was open so a restart can pick up
+// where the editor was left: the named buffers with their cursors
+// and scroll, the split layout with each pane's cursor and jump list,
+// the working directory and the console history.
+//
+// The last session is written to the state directory on exit and every
+// SESSION_INTERVAL_SECONDS, and offered through the console on a start
+// without file arguments. Unsaved text is not part of it, swap files
+// keep that (see editor_swap.rs).
+//
+// Named sessions belong to the working directory they were saved in:
+//      :ss <n>     : Save the session as 'n' ('default' without a name)
+//      :os <n>     : Open the session 'n', without a name list the saved ones
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use macroquad::prelude::get_time;
+
+use crate::editor_buffers::EditorBuffers;
+use crate::editor_config::state_dir;
+use crate::editor_console::{ConsolePrompt, EditorConsole};
+use crate::editor_cursor::EditorCursor;
+use crate::editor_fs::{BackupMode, write_atomic};
+use crate::editor_jumps::{EditorJumpList, Jump};
+use crate::editor_panes::{EditorPane, EditorPanes, PaneLayout};
+
+const SESSION_DIR: &str = "sessions";
+const LAST_SESSION: &str = "last";
+const SESSION_EXTENSION: &str = "session";
+const SESSION_INTERVAL_SECONDS: f64 = 30.0;
+pub const DEFAULT_SESSION_NAME: &str = "default";
+
+/// Session work the console asks for, done where the panes are at hand
+pub enum SessionRequest {
+    Save(String),
+    Restore(PathBuf)
+}
+
+pub struct EditorSession {
+    last_save: f64
+}
+
+/// Directory of the named sessions of the project 'cwd'
+fn project_dir(cwd: &Path) -> PathBuf {
+    let key = cwd.display().to_string().replace(['/', '\\', ':'], "%");
+    state_dir().join(SESSION_DIR).join(key)
+}
+
+pub fn last_session_path() -> PathBuf {
+    state_dir().join(SESSION_DIR).join(format!("{}.{}", LAST_SESSION, SESSION_EXTENSION))
+}
+
+/// Path of the session 'name' of the project 'cwd'
+pub fn named_session_path(cwd: &Path, name: &str) -> PathBuf {
+    project_dir(cwd).join(format!("{}.{}", name, SESSION_EXTENSION))
+}
+
+/// Names of the sessions saved for the project 'cwd'
+pub fn session_names(cwd: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(project_dir(cwd))
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == SESSION_EXTENSION))
+            .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().to_string()))
+            .collect())
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// Layout as tokens, 'v0.5' or 'h0.5' before the two halves of a split, a pane by its id
+fn write_layout(layout: &PaneLayout, out: &mut Vec<String>) {
+    match layout {
+        PaneLayout::Pane(id) => out.push(id.to_string()),
+        PaneLayout::Split { vertical, ratio, first, second } => {
+            out.push(format!("{}{}", if *vertical { "v" } else { "h" }, ratio));
+            write_layout(first, out);
+            write_layout(second, out);
+        }
+    }
+}
+
+fn read_layout<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Option<PaneLayout> {
+    let token = tokens.next()?;
+
+    let vertical = match token.chars().next()? {
+        'v' => true,
+        'h' => false,
+        _ => return token.parse().ok().map(PaneLayout::Pane)
+    };
+
+    let ratio: f32 = token[1..].parse().ok()?;
+    let first = Box::new(read_layout(tokens)?);
+    let second = Box::new(read_layout(tokens)?);
+    Some(PaneLayout::Split { vertical, ratio: ratio.clamp(0.1, 0.9), first, second })
+}
+
+fn pane_ids(layout: &PaneLayout, out: &mut Vec<usize>) {
+    match layout {
+        PaneLayout::Pane(id) => out.push(*id),
+        PaneLayout::Split { first, second, .. } => {
+            pane_ids(first, out);
+            pane_ids(second, out);
+        }
+    }
+}
+
+/// The session as lines of tab separated fields, paths last
+fn session_content(console: &EditorConsole, buffers: &EditorBuffers, panes: &EditorPanes) -> String {
+    let mut lines = vec![format!("cwd\t{}", console.cwd.display())];
+
+    // Untitled buffers have nothing to reopen
+    let saved: Vec<usize> = buffers.files.iter().filter(|f| f.path.is_some()).map(|f| f.id).collect();
+
+    for file in buffers.files.iter().filter(|f| f.path.is_some()) {
+        let path = file.path.as_ref().map(|p| fs::canonicalize(p).unwrap_or_else(|_| p.clone())).unwrap_or_default();
+        lines.push(format!("buffer\t{}\t{}\t{}\t{}", file.cursor.xy.0, file.cursor.xy.1, file.scroll, path.display()));
+    }
+
+    if let Some(index) = saved.iter().position(|&id| id == buffers.current().id) {
+        lines.push(format!("current\t{}", index));
+    }
+
+    let mut layout = vec![];
+    write_layout(&panes.layout, &mut layout);
+    lines.push(format!("layout\t{}", layout.join(" ")));
+    lines.push(format!("focused\t{}", panes.focused));
+
+    for pane in &panes.panes {
+        let buffer = saved.iter().position(|&id| id == pane.buffer).map_or(String::new(), |i| i.to_string());
+        lines.push(format!("pane\t{}\t{}\t{}\t{}\t{}", pane.id, buffer, pane.cursor.xy.0, pane.cursor.xy.1, pane.scroll));
+
+        for jump in &pane.jumps.entries {
+            let path = jump.path.as_ref().map_or(String::new(), |p| p.display().to_string());
+            lines.push(format!("jump\t{}\t{}\t{}\t{}", pane.id, jump.xy.0, jump.xy.1, path));
+        }
+    }
+
+    for entry in &console.history.entries {
+        lines.push(format!("history\t{}", entry));
+    }
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+pub fn save_session(path: &Path, console: &EditorConsole, buffers: &EditorBuffers, panes: &EditorPanes) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_atomic(path, session_content(console, buffers, panes).as_bytes(), BackupMode::None).map(|_| ())
+}
+
+/// Buffers a session file reopens, to mention in the restore question
+pub fn session_buffer_count(path: &Path) -> usize {
+    fs::read_to_string(path).map_or(0, |c| c.lines().filter(|l| l.starts_with("buffer\t")).count())
+}
+
+fn number(field: Option<&str>) -> usize {
+    field.and_then(|f| f.parse().ok()).unwrap_or(0)
+}
+
+/// Reopen what a session file holds, returns a summary for the console
+pub fn restore_session(path: &Path, console: &mut EditorConsole, buffers: &mut EditorBuffers, panes: &mut EditorPanes) -> io::Result<String> {
+    let content = fs::read_to_string(path)?;
+
+    let mut ids: Vec<Option<usize>> = vec![]; // File id of each saved buffer, None if it is gone
+    let mut current = None;
+    let mut layout = None;
+    let mut focused = 0;
+    let mut restored_panes: Vec<EditorPane> = vec![];
+    let mut history = vec![];
+    let mut missing = 0;
+
+    for line in content.lines() {
+        let (kind, rest) = line.split_once('\t').unwrap_or((line, ""));
+
+        match kind {
+            "cwd" if Path::new(rest).is_dir() => console.cwd = PathBuf::from(rest),
+            "buffer" => {
+                let fields: Vec<&str> = rest.splitn(4, '\t').collect();
+                let file_path = Path::new(fields.get(3).copied().unwrap_or(""));
+
+                if !file_path.is_file() || buffers.open(file_path).is_err() {
+                    missing += 1;
+                    ids.push(None);
+                    continue;
+                }
+
+                let file = buffers.current_mut();
+                let y = number(fields.get(1).copied()).min(file.text.len().saturating_sub(1));
+                file.cursor.xy = (number(fields.first().copied()).min(file.text[y].chars().count()), y);
+                file.scroll = number(fields.get(2).copied()).min(y);
+                ids.push(Some(file.id));
+            }
+            "current" => current = rest.parse::<usize>().ok(),
+            "layout" => layout = read_layout(&mut rest.split_whitespace()),
+            "focused" => focused = number(Some(rest)),
+            "pane" => {
+                let fields: Vec<&str> = rest.split('\t').collect();
+                let buffer = fields.get(1).and_then(|f| f.parse::<usize>().ok()).and_then(|i| ids.get(i).copied().flatten());
+
+                let mut cursor = EditorCursor::new();
+                cursor.xy = (number(fields.get(2).copied()), number(fields.get(3).copied()));
+
+                restored_panes.push(EditorPane {
+                    id: number(fields.first().copied()),
+                    buffer: buffer.unwrap_or(usize::MAX), // Set to the current buffer below
+                    cursor,
+                    scroll: number(fields.get(4).copied()),
+                    view_lines: 1,
+                    jumps: EditorJumpList::new()
+                });
+            }
+            "jump" => {
+                let fields: Vec<&str> = rest.splitn(4, '\t').collect();
+                let id = number(fields.first().copied());
+                let jump_path = fields.get(3).filter(|p| !p.is_empty()).map(PathBuf::from);
+
+                if let Some(pane) = restored_panes.iter_mut().find(|p| p.id == id) {
+                    pane.jumps.entries.push(Jump { path: jump_path, xy: (number(fields.get(1).copied()), number(fields.get(2).copied())) });
+                    pane.jumps.index = pane.jumps.entries.len();
+                }
+            }
+            "history" => history.push(rest.to_string()),
+            _ => {}
+        }
+    }
+
+    if let Some(index) = current.and_then(|i| ids.get(i).copied().flatten()).and_then(|id| buffers.index_of(id)) {
+        buffers.current = index;
+    }
+
+    // The layout is only used when every pane it shows was read
+    let mut layout_ids = vec![];
+    if let Some(layout) = &layout {
+        pane_ids(layout, &mut layout_ids);
+    }
+    let complete = !layout_ids.is_empty() && layout_ids.len() == restored_panes.len()
+        && layout_ids.iter().all(|id| restored_panes.iter().any(|p| p.id == *id));
+
+    if let (true, Some(layout)) = (complete, layout) {
+        let current_id = buffers.current().id;
+        for pane in restored_panes.iter_mut() {
+            if buffers.index_of(pane.buffer).is_none() {
+                pane.buffer = current_id;
+            }
+        }
+
+        let focused = if layout_ids.contains(&focused) { focused } else { layout_ids[0] };
+        *panes = EditorPanes::restore(restored_panes, layout, focused);
+
+        // The focused pane's cursor is the one the current buffer starts with
+        let pane = panes.focused();
+        if let Some(index) = buffers.index_of(pane.buffer) {
+            buffers.current = index;
+        }
+    } else {
+        *panes = EditorPanes::new(buffers);
+    }
+
+    if !history.is_empty() {
+        console.history.replace(history);
+    }
+
+    let opened = ids.iter().filter(|id| id.is_some()).count();
+    Ok(match missing {
+        0 => format!("Restored {} file{}", opened, if opened == 1 { "" } else { "s" }),
+        _ => format!("Restored {} file{}, {} no longer exist", opened, if opened == 1 { "" } else { "s" }, missing)
+    })
+}
+
+/// Ask whether to reopen the last session, if it had any files
+pub fn offer_last_session(console: &mut EditorConsole) {
+    let path = last_session_path();
+    let count = session_buffer_count(&path);
+    if count == 0 {
+        return;
+    }
+
+    console.ask(ConsolePrompt::RestoreSession(path),
+        &format!("Restore the last session with {} file{}? (y/n)", count, if count == 1 { "" } else { "s" }));
+}
+
+impl EditorSession {
+    /// Session constructor
+    pub fn new() -> EditorSession {
+        EditorSession { last_save: get_time() }
+    }
+
+    /// Do what the console asked for, and save the last session every SESSION_INTERVAL_SECONDS
+    pub fn update(&mut self, console: &mut EditorConsole, buffers: &mut EditorBuffers, panes: &mut EditorPanes) {
+        match console.session_request.take() {
+            Some(SessionRequest::Save(name)) => {
+                let path = named_session_path(&console.cwd, &name);
+                match save_session(&path, console, buffers, panes) {
+                    Ok(()) => console.message(&format!("Saved the session '{}'", name)),
+                    Err(e) => console.message(&format!("Cannot save the session '{}': {}", name, e))
+                }
+            }
+            Some(SessionRequest::Restore(path)) => {
+                match restore_session(&path, console, buffers, panes) {
+                    Ok(summary) => {
+                        console.message(&summary);
+                        console.mode = false;
+                    }
+                    Err(e) => console.message(&format!("Cannot read the session: {}", e))
+                }
+            }
+            None => {}
+        }
+
+        let now = get_time();
+        if now - self.last_save >= SESSION_INTERVAL_SECONDS {
+            self.last_save = now;
+            self.save_last(console, buffers, panes);
+        }
+    }
+
+    /// Save the last session, not while the previous one is still offered
+    pub fn save_last(&self, console: &EditorConsole, buffers: &EditorBuffers, panes: &EditorPanes) {
+        if matches!(console.prompt, Some(ConsolePrompt::RestoreSession(_))) {
+            return;
+        }
+        let _ = save_session(&last_session_path(), console, buffers, panes);
+    }
+}
@@ -0,0 +1,238 @@
+// Sidebar module, a file explorer tree of the working directory
+// drawn to the left of the text.
+//
+// LCTRL + E ('view.toggle_sidebar', see editor_keymap.rs) shows the sidebar
+// and moves the keyboard to it, pressed again while the sidebar has the keyboard
+// it hides the sidebar.
+// While it has the keyboard, the default keys of the 'sidebar.*' commands:
+//      Up/Down     : Select an entry
+//      Left/Right  : Collapse/expand the selected directory
+//      Enter       : Open the selected file, or expand/collapse the selected directory
+//      Escape      : Give the keyboard back to the text
+//
+// The file of the current buffer is highlighted and the directories
+// leading to it are expanded. The tree is read again every
+// SIDEBAR_REFRESH_SECONDS to pick up changes made outside the editor.
+
+use std::path::PathBuf;
+
+use macroquad::prelude::*;
+
+use crate::editor_audio::EditorAudio;
+use crate::editor_buffers::EditorBuffers;
+use crate::editor_commands::SidebarCommand;
+use crate::editor_console::EditorConsole;
+use crate::editor_jumps::EditorJumpList;
+use crate::editor_palette::palette;
+use crate::editor_text::*;
+use crate::editor_tree::DirectoryTree;
+
+const SIDEBAR_WIDTH: f32 = 220.0;
+const SIDEBAR_FONT_SIZE: u16 = 16;
+const SIDEBAR_MARGIN: f32 = 8.0;
+const SIDEBAR_INDENT: f32 = 12.0;
+const SIDEBAR_REFRESH_SECONDS: f64 = 1.0;
+
+pub struct EditorSidebar {
+    pub visible: bool,
+    pub focused: bool,              // Has the keyboard
+    tree: DirectoryTree,
+    selected: usize,
+    current_file: Option<PathBuf>,  // Canonical path of the current buffer's file
+    last_refresh: f64
+}
+
+impl EditorSidebar {
+    /// Sidebar constructor, hidden, showing the working directory of the console
+    pub fn new(console: &EditorConsole) -> EditorSidebar {
+        EditorSidebar {
+            visible: false,
+            focused: false,
+            tree: DirectoryTree::new(&console.cwd),
+            selected: 0,
+            current_file: None,
+            last_refresh: get_time()
+        }
+    }
+
+    /// What is left of 'area' for the text once the sidebar takes its part
+    pub fn text_area(&self, area: Rect) -> Rect {
+        if self.visible {
+            Rect::new(area.x + SIDEBAR_WIDTH, area.y, area.w - SIDEBAR_WIDTH, area.h)
+        } else {
+            area
+        }
+    }
+
+    /// Follow the working directory, the current buffer and the disk
+    pub fn update(&mut self, console: &EditorConsole, buffers: &EditorBuffers) {
+        if !self.visible {
+            return;
+        }
+
+        if self.tree.root() != console.cwd {
+            self.tree = DirectoryTree::new(&console.cwd);
+            self.selected = 0;
+            self.current_file = None;
+        }
+
+        if get_time() - self.last_refresh >= SIDEBAR_REFRESH_SECONDS {
+            self.tree.refresh();
+            self.last_refresh = get_time();
+        }
+
+        let current = buffers.current().path.as_ref().map(|p| p.canonicalize().unwrap_or_else(|_| p.clone()));
+        if current != self.current_file {
+            if let Some(path) = &current {
+                self.tree.reveal(path);
+
+                if let Some(row) = self.tree.rows().iter().position(|r| &r.path == path) {
+                    self.selected = row;
+                }
+            }
+            self.current_file = current;
+        }
+
+        self.selected = self.selected.min(self.tree.rows().len().saturating_sub(1));
+    }
+
+    pub fn draw(&self, area: Rect, gts: &EditorGeneralTextStylizer) {
+        if !self.visible {
+            return;
+        }
+
+        draw_rectangle(area.x, area.y, SIDEBAR_WIDTH, area.h, palette().background);
+        draw_rectangle(area.x + SIDEBAR_WIDTH - 1.5, area.y, 1.5, area.h, palette().composite_type);
+
+        let line_height = SIDEBAR_FONT_SIZE as f32 * 1.25;
+        let rows = self.tree.rows();
+
+        // Keep the selected entry on screen
+        let visible = ((area.h - SIDEBAR_MARGIN) / line_height).max(1.0) as usize;
+        let first = self.selected.saturating_sub(visible - 1);
+        let mut y = area.y + SIDEBAR_MARGIN + SIDEBAR_FONT_SIZE as f32;
+
+        for (i, row) in rows.iter().enumerate().skip(first).take(visible) {
+            let top = y - SIDEBAR_FONT_SIZE as f32 * 0.8;
+
+            if i == self.selected && self.focused {
+                draw_rectangle(area.x, top, SIDEBAR_WIDTH - 1.5, line_height, palette().composite_type);
+            } else if self.current_file.as_ref() == Some(&row.path) {
+                draw_rectangle(area.x, top, SIDEBAR_WIDTH - 1.5, line_height, Color { a: 0.4, ..palette().composite_type });
+            }
+
+            let marker = match (row.is_dir, row.expanded) {
+                (true, true) => "- ",
+                (true, false) => "+ ",
+                (false, _) => "  "
+            };
+            let label = format!("{}{}{}", marker, row.name, if row.is_dir { "/" } else { "" });
+            let x = area.x + SIDEBAR_MARGIN + SIDEBAR_INDENT * row.depth as f32;
+
+            draw_text_ex(fit_text(&label, SIDEBAR_WIDTH - (x - area.x) - SIDEBAR_MARGIN, gts), x, y,
+                TextParams { font: Some(&gts.font), font_size: SIDEBAR_FONT_SIZE, color: WHITE, ..Default::default() });
+
+            y += line_height;
+        }
+    }
+}
+
+/// 'text' cut to fit 'max_width'
+fn fit_text<'a>(text: &'a str, max_width: f32, gts: &EditorGeneralTextStylizer) -> &'a str {
+    let mut end = text.len();
+    while end > 0 && measure_text(&text[..end], Some(&gts.font), SIDEBAR_FONT_SIZE, 1.0).width > max_width {
+        end = text[..end].char_indices().last().map(|(b, _)| b).unwrap_or(0);
+    }
+
+    &text[..end]
+}
+
+/// 'view.toggle_sidebar', show and focus the sidebar or hide it
+pub fn toggle_sidebar(sidebar: &mut EditorSidebar, buffers: &EditorBuffers, audio: &EditorAudio, console: &EditorConsole) {
+    audio.play_nav();
+
+    if sidebar.focused {
+        sidebar.visible = false;
+        sidebar.focused = false;
+    } else {
+        sidebar.visible = true;
+        sidebar.focused = true;
+        sidebar.update(console, buffers);
+    }
+
+    clear_input_queue();
+}
+
+/// Key recording while the sidebar has the keyboard, true if it has it,
+/// 'command' is what the keymap made of the keys pressed
+pub fn record_keyboard_to_sidebar(command: Option<&str>, sidebar: &EditorSidebar, console: &mut EditorConsole) -> bool {
+    if !sidebar.focused {
+        return false;
+    }
+
+    // Typed characters are not meant for the text
+    clear_input_queue();
+
+    if let Some(command) = command {
+        console.commands.push(command, None);
+    }
+    true
+}
+
+/// The 'sidebar.*' commands, the keys of the sidebar (see editor_keymap.rs)
+pub fn sidebar_navigation(command: SidebarCommand, sidebar: &mut EditorSidebar, buffers: &mut EditorBuffers, jumps: &mut EditorJumpList,
+    audio: &EditorAudio, console: &mut EditorConsole) {
+    let rows = sidebar.tree.rows();
+    let row = match rows.get(sidebar.selected) {
+        Some(row) => row,
+        None => return
+    };
+
+    match command {
+        SidebarCommand::Leave => sidebar.focused = false,
+        SidebarCommand::Up => if sidebar.selected > 0 {
+            audio.play_nav();
+            sidebar.selected -= 1;
+        },
+        SidebarCommand::Down => if sidebar.selected + 1 < rows.len() {
+            audio.play_nav();
+            sidebar.selected += 1;
+        },
+        SidebarCommand::Expand => if row.is_dir {
+            audio.play_nav();
+            sidebar.tree.set_expanded(&row.path, true);
+        },
+        SidebarCommand::Collapse => {
+            audio.play_nav();
+
+            // A collapsed directory or a file selects its parent
+            if row.is_dir && row.expanded {
+                sidebar.tree.set_expanded(&row.path, false);
+            } else if let Some(parent) = rows[..sidebar.selected].iter().rposition(|r| r.depth + 1 == row.depth) {
+                sidebar.selected = parent;
+            }
+        }
+        SidebarCommand::Open => {
+            audio.play_return();
+
+            if row.is_dir {
+                sidebar.tree.toggle(&row.path);
+                return;
+            }
+
+            let from = (buffers.current().path.clone(), buffers.current().cursor.xy);
+            match buffers.open(&row.path) {
+                Ok(()) => {
+                    if from.0 != buffers.current().path {
+                        jumps.push(&from.0, from.1);
+                    }
+                    sidebar.focused = false;
+                }
+                Err(e) => {
+                    console.message(&format!("Cannot open {}: {}", row.path.display(), e));
+                    console.mode = true;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,257 @@
+// Status bar module, a line at the bottom of the screen.
+//
+// On the left: the mode (INSERT, CONSOLE or SIDEBAR), the current buffer
+// with a '*' if it has unsaved changes and '[RO]' if it is read-only, then
+// the last console message for STATUS_MESSAGE_SECONDS, or else the working
+// directory of the directives.
+//
+// On the right: the cursor line and column, its offset from the start of
+// the file in characters and in bytes as written on the disk (only in bytes
+// for a large file), the number of lines, the language, encoding, line endings
+// and indentation.
+
+use macroquad::prelude::*;
+
+use crate::editor_buffers::EditorBuffers;
+use crate::editor_config::home_dir;
+use crate::editor_console::EditorConsole;
+use crate::editor_encoding::{Encoding, TextFormat};
+use crate::editor_file::EditorFile;
+use crate::editor_info::{format_encoding, language_of};
+use crate::editor_palette::palette;
+use crate::editor_sidebar::EditorSidebar;
+use crate::editor_text::*;
+
+pub const STATUS_BAR_HEIGHT: f32 = 22.0;
+const STATUS_FONT_SIZE: u16 = 16;
+const STATUS_PADDING: f32 = 10.0;
+const STATUS_MESSAGE_SECONDS: f64 = 4.0;
+const STATUS_SEPARATOR: &str = "  |  ";
+
+/// Offsets of the cursor from the start of the file
+#[derive(Clone, Copy)]
+struct CursorOffsets {
+    chars: usize,
+    bytes: usize
+}
+
+pub struct EditorStatus {
+    // File id, changes and format the line starts were counted for, after an edit
+    // they are counted again from the first line it changed, and only down to the cursor
+    counted_for: Option<(usize, usize, TextFormat)>,
+    line_starts: Vec<CursorOffsets>,
+    named_for: Option<(usize, usize, usize, Encoding)>, // File id, changes, lines and encoding of the encoding name
+    encoding_name: String
+}
+
+/// The screen without the status bar, where the panes are drawn
+pub fn area_above_status_bar() -> Rect {
+    Rect::new(0.0, 0.0, screen_width(), screen_height() - STATUS_BAR_HEIGHT)
+}
+
+/// Working directory with the home directory shortened to '~'
+fn short_cwd(console: &EditorConsole) -> String {
+    match console.cwd.strip_prefix(home_dir()) {
+        Ok(rest) if rest.as_os_str().is_empty() => String::from("~"),
+        Ok(rest) => format!("~/{}", rest.display()),
+        Err(_) => console.cwd.display().to_string()
+    }
+}
+
+fn status_width(text: &str, gts: &EditorGeneralTextStylizer) -> f32 {
+    measure_text(text, Some(&gts.font), STATUS_FONT_SIZE, 1.0).width
+}
+
+/// Draw a piece of the status bar, cut to 'max_width'
+fn draw_status_text(text: &str, x: f32, max_width: f32, color: Color, gts: &EditorGeneralTextStylizer) {
+    let mut end = text.len();
+    while end > 0 && status_width(&text[..end], gts) > max_width {
+        end = text[..end].char_indices().last().map(|(b, _)| b).unwrap_or(0);
+    }
+
+    let y = screen_height() - STATUS_BAR_HEIGHT * 0.25;
+    draw_text_ex(&text[..end], x, y,
+        TextParams { font: Some(&gts.font), font_size: STATUS_FONT_SIZE, color, ..Default::default() });
+}
+
+impl EditorStatus {
+    /// Status bar constructor
+    pub fn new() -> EditorStatus {
+        EditorStatus {
+            counted_for: None,
+            line_starts: vec![],
+            named_for: None,
+            encoding_name: String::new()
+        }
+    }
+
+    /// Name of the encoding of 'file', looked up again once it changes,
+    /// large files are not searched for a non ASCII character
+    fn encoding_name(&mut self, file: &EditorFile) -> String {
+        if file.large.is_some() {
+            return file.format.encoding.name().to_uppercase();
+        }
+
+        let key = (file.id, file.history.changes(), file.text.len(), file.format.encoding);
+        if self.named_for != Some(key) {
+            self.encoding_name = format_encoding(file.format.encoding, &file.text);
+            self.named_for = Some(key);
+        }
+
+        self.encoding_name.clone()
+    }
+
+    /// Offsets of the cursor of 'file', from where its line starts
+    fn offsets(&mut self, file: &EditorFile) -> CursorOffsets {
+        let (x, y) = file.cursor.xy;
+        let y = y.min(file.text.len().saturating_sub(1));
+        let encoding = file.format.encoding;
+        let separator = file.format.line_ending.as_str();
+
+        match self.counted_for {
+            Some((id, changes, format)) if id == file.id && format == file.format => {
+                if let Some(line) = file.history.changed_since(changes) {
+                    self.line_starts.truncate(line + 1);
+                }
+            }
+            _ => self.line_starts.clear()
+        }
+        self.counted_for = Some((file.id, file.history.changes(), file.format));
+
+        // Empty lines are added at the end without an edit
+        self.line_starts.truncate(file.text.len());
+        if self.line_starts.is_empty() {
+            self.line_starts.push(CursorOffsets { chars: 0, bytes: encoding.bom_len() });
+        }
+        while self.line_starts.len() <= y {
+            let (start, line) = (self.line_starts[self.line_starts.len() - 1], &file.text[self.line_starts.len() - 1]);
+            self.line_starts.push(CursorOffsets {
+                chars: start.chars + line.chars().count() + separator.len(),
+                bytes: start.bytes + encoding.byte_len(line) + encoding.byte_len(separator)
+            });
+        }
+
+        let start = self.line_starts[y];
+        let before: String = file.text.get(y).map_or(String::new(), |l| l.chars().take(x).collect());
+        CursorOffsets {
+            chars: start.chars + before.chars().count(),
+            bytes: start.bytes + encoding.byte_len(&before)
+        }
+    }
+
+    pub fn draw(&mut self, console: &EditorConsole, buffers: &EditorBuffers, sidebar: &EditorSidebar, gts: &EditorGeneralTextStylizer) {
+        let y = screen_height() - STATUS_BAR_HEIGHT;
+        draw_rectangle(0.0, y, screen_width(), STATUS_BAR_HEIGHT, palette().composite_type);
+
+        let file = buffers.current();
+
+        // Right side, drawn first so the left side knows the room it has
+        let (column, line) = (file.cursor.xy.0 + 1, file.cursor.xy.1 + 1);
+        let position = match &file.large {
+            // The index knows where the line starts, not the characters before it
+            Some(large) => {
+                let before: String = file.text.get(line - 1).map_or(String::new(), |l| l.chars().take(column - 1).collect());
+                let byte = large.line_start(line - 1).unwrap_or(0) as usize + file.format.encoding.byte_len(&before);
+                format!("Ln {}, Col {} (byte {})", line, column, byte)
+            }
+            None => {
+                let offsets = self.offsets(file);
+                format!("Ln {}, Col {} (char {}, byte {})", line, column, offsets.chars, offsets.bytes)
+            }
+        };
+        let right = [
+            position,
+            format!("{} lines", file.text.len()),
+            language_of(file.path.as_deref()).to_string(),
+            self.encoding_name(file),
+            file.format.line_ending.name().to_uppercase(),
+            format!("Spaces: {}", TAB_SIZE)
+        ].join(STATUS_SEPARATOR);
+
+        let right_x = screen_width() - STATUS_PADDING - status_width(&right, gts);
+        draw_status_text(&right, right_x, screen_width(), WHITE, gts);
+
+        // Left side
+        let mut mode = String::from(if console.mode {
+            "CONSOLE"
+        } else if sidebar.focused {
+            "SIDEBAR"
+        } else {
+            "INSERT"
+        });
+
+        // The start of a key sequence waiting for the rest
+        if let Some(keys) = console.keymap.pending() {
+            mode = format!("{} {} ...", mode, keys);
+        }
+
+        let mut name = file.name();
+        if file.is_dirty() {
+            name.push('*');
+        }
+        if file.readonly {
+            name.push_str(" [RO]");
+        }
+
+        let message = console.output.first().filter(|_| get_time() - console.message_at < STATUS_MESSAGE_SECONDS);
+        let left = format!("{}{}{}{}", mode, STATUS_SEPARATOR, name, STATUS_SEPARATOR);
+        let (tail, tail_color) = match message {
+            Some(message) => (message.clone(), palette().type_qualifier),
+            None => (short_cwd(console), WHITE)
+        };
+
+        let mut x = STATUS_PADDING;
+        draw_status_text(&left, x, right_x - x, WHITE, gts);
+        x += status_width(&left, gts);
+        draw_status_text(&tail, x, right_x - STATUS_PADDING - x, tail_color, gts);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::editor_encoding::LineEnding;
+
+    /// Offsets counted from the start of the file every time
+    fn counted(file: &EditorFile) -> (usize, usize) {
+        let (x, y) = file.cursor.xy;
+        let mut text: Vec<String> = file.text[..y].to_vec();
+        text.push(file.text[y].chars().take(x).collect());
+
+        let joined = text.join(file.format.line_ending.as_str());
+        (joined.chars().count(), file.format.encoding.byte_len(&joined) + file.format.encoding.bom_len())
+    }
+
+    fn check(status: &mut EditorStatus, file: &EditorFile) {
+        let offsets = status.offsets(file);
+        assert_eq!((offsets.chars, offsets.bytes), counted(file));
+    }
+
+    #[test]
+    fn offsets_follow_moves_and_edits() {
+        let mut status = EditorStatus::new();
+        let mut file = EditorFile::new();
+        file.text = vec![String::from("héllo"), String::from("wörld"), String::new(), String::from("end")];
+        file.format.line_ending = LineEnding::CrLf;
+
+        file.cursor.xy = (2, 3);
+        check(&mut status, &file);
+        file.cursor.xy = (1, 1);
+        check(&mut status, &file);
+
+        // An edit above the lines counted so far
+        let EditorFile { text, history, .. } = &mut file;
+        history.record(text, 0, 1, 1, (0, 0), false);
+        text[0].push_str("ünïcode");
+        file.cursor.xy = (3, 3);
+        check(&mut status, &file);
+
+        file.text.push(String::from("pushed"));
+        file.cursor.xy = (4, 4);
+        check(&mut status, &file);
+
+        file.format.encoding = Encoding::Utf16Le;
+        check(&mut status, &file);
+    }
+}
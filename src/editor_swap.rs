@@ -0,0 +1,273 @@
+// Swap module, keeps a recovery copy of every buffer with unsaved changes
+// in the state directory, so a crash loses at most SWAP_INTERVAL_SECONDS of typing.
+//
+// A swap file is named after the editor's process id and the buffer,
+// its first line is the path of the file (empty for an untitled one)
+// and the rest is the text. It is removed once the buffer is saved or closed,
+// and when the editor quits, the changes then being written or discarded.
+//
+// On startup the swap files left by editors no longer running are offered
+// through the console, 'y' opens the text in a buffer with unsaved changes,
+// 'n' discards it, Escape keeps it for the next start. An editor is running
+// while '/proc/<pid>' exists on Linux, elsewhere while its '<pid>.alive' file
+// in the swap directory, rewritten every SWAP_INTERVAL_SECONDS, is younger
+// than HEARTBEAT_STALE_SECONDS.
+//
+// Large files (see editor_large.rs) get no swap file, writing one
+// would take as long as writing the file.
+//
+// 'autosave = <N>' in cal.conf writes named buffers to the disk
+// after N seconds without an edit, 0 (the default) turns it off.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use macroquad::prelude::get_time;
+
+use crate::editor_buffers::EditorBuffers;
+use crate::editor_config::{EditorConfig, state_dir};
+use crate::editor_console::{ConsolePrompt, EditorConsole};
+use crate::editor_file::EditorFile;
+use crate::editor_fs::{BackupMode, write_atomic};
+use crate::editor_info::format_time;
+
+const SWAP_DIR: &str = "swap";
+const SWAP_EXTENSION: &str = "swp";
+const SWAP_INTERVAL_SECONDS: f64 = 2.0;
+#[cfg(not(target_os = "linux"))]
+const HEARTBEAT_EXTENSION: &str = "alive";
+#[cfg(not(target_os = "linux"))]
+const HEARTBEAT_STALE_SECONDS: f64 = 30.0;
+
+/// What is known of an open buffer since the last check
+struct SwapState {
+    id: usize,
+    changes: usize,          // The buffer's history changes when last checked
+    changed_at: f64,         // When they were last seen growing
+    swapped: Option<usize>,  // The changes written to the swap file
+    autosave_failed: bool    // Reported once until the next edit
+}
+
+pub struct EditorSwap {
+    dir: PathBuf,
+    states: Vec<SwapState>,
+    leftovers: Vec<PathBuf>, // Swap files of crashed editors, not offered yet
+    autosave: f64,           // Seconds without edits, 0 for off
+    last_check: f64
+}
+
+/// Process id a swap file was written by, from its name
+fn swap_pid(path: &Path) -> Option<u32> {
+    path.file_stem()?.to_string_lossy().split('-').next()?.parse().ok()
+}
+
+#[cfg(target_os = "linux")]
+fn process_running(pid: u32, _dir: &Path) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+/// Without '/proc' an editor is running while it keeps rewriting its heartbeat file
+#[cfg(not(target_os = "linux"))]
+fn process_running(pid: u32, dir: &Path) -> bool {
+    fs::metadata(heartbeat_path(dir, pid)).and_then(|m| m.modified())
+        .is_ok_and(|t| t.elapsed().is_ok_and(|age| age.as_secs_f64() < HEARTBEAT_STALE_SECONDS))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn heartbeat_path(dir: &Path, pid: u32) -> PathBuf {
+    dir.join(format!("{}.{}", pid, HEARTBEAT_EXTENSION))
+}
+
+/// Tell the other editors this one is still running
+#[cfg(target_os = "linux")]
+fn write_heartbeat(_dir: &Path) {}
+
+#[cfg(not(target_os = "linux"))]
+fn write_heartbeat(dir: &Path) {
+    if fs::create_dir_all(dir).is_ok() {
+        let _ = fs::write(heartbeat_path(dir, process::id()), "");
+    }
+}
+
+/// Read a swap file back into a file path and its text
+fn read_swap(path: &Path) -> io::Result<(Option<PathBuf>, Vec<String>)> {
+    let content = fs::read_to_string(path)?;
+    let (file_path, text) = content.split_once('\n').unwrap_or((content.as_str(), ""));
+
+    let file_path = if file_path.is_empty() { None } else { Some(PathBuf::from(file_path)) };
+    let mut lines: Vec<String> = text.split('\n').map(|l| l.to_string()).collect();
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+
+    Ok((file_path, lines))
+}
+
+impl EditorSwap {
+    /// Swap constructor, finds the swap files left behind by crashed editors
+    pub fn new(config: &EditorConfig) -> EditorSwap {
+        let dir = state_dir().join(SWAP_DIR);
+
+        let mut leftovers: Vec<PathBuf> = fs::read_dir(&dir)
+            .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|e| e == SWAP_EXTENSION))
+                .filter(|p| swap_pid(p).is_none_or(|pid| pid != process::id() && !process_running(pid, &dir)))
+                .collect())
+            .unwrap_or_default();
+        leftovers.sort();
+
+        EditorSwap {
+            dir,
+            states: vec![],
+            leftovers,
+            autosave: config.get_usize("autosave", 0) as f64,
+            last_check: get_time()
+        }
+    }
+
+    fn swap_path(&self, id: usize) -> PathBuf {
+        self.dir.join(format!("{}-{}.{}", process::id(), id, SWAP_EXTENSION))
+    }
+
+    /// Remove the swap files of this editor, when quitting leaves nothing to recover
+    pub fn remove_all(&mut self) {
+        for state in std::mem::take(&mut self.states) {
+            if state.swapped.is_some() {
+                let _ = fs::remove_file(self.swap_path(state.id));
+            }
+        }
+    }
+
+    fn write_swap(&self, file: &EditorFile) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let mut content = file.path.as_ref().map_or_else(String::new, |p| p.display().to_string());
+        content.push('\n');
+        content.push_str(&file.text.join("\n"));
+
+        write_atomic(&self.swap_path(file.id), content.as_bytes(), BackupMode::None).map(|_| ())
+    }
+
+    /// Offer the next leftover swap file, then every SWAP_INTERVAL_SECONDS
+    /// write the swap files of the changed buffers and autosave the idle ones
+    pub fn update(&mut self, buffers: &mut EditorBuffers, console: &mut EditorConsole) {
+        if console.prompt.is_none() && let Some(path) = self.leftovers.pop() {
+            offer_recovery(console, path);
+        }
+
+        let now = get_time();
+        if now - self.last_check < SWAP_INTERVAL_SECONDS {
+            return;
+        }
+        self.last_check = now;
+        write_heartbeat(&self.dir);
+
+        // Closed buffers leave nothing behind
+        let closed: Vec<usize> = self.states.iter().map(|s| s.id).filter(|&id| buffers.index_of(id).is_none()).collect();
+        for id in closed {
+            let _ = fs::remove_file(self.swap_path(id));
+        }
+        self.states.retain(|s| buffers.index_of(s.id).is_some());
+
+        for file in buffers.files.iter_mut() {
+            let index = match self.states.iter().position(|s| s.id == file.id) {
+                Some(index) => index,
+                None => {
+                    self.states.push(SwapState { id: file.id, changes: file.history.changes(), changed_at: now, swapped: None, autosave_failed: false });
+                    self.states.len() - 1
+                }
+            };
+
+            let state = &mut self.states[index];
+            if state.changes != file.history.changes() {
+                state.changes = file.history.changes();
+                state.changed_at = now;
+                state.autosave_failed = false;
+            }
+
+            if !file.is_dirty() {
+                if state.swapped.take().is_some() {
+                    let _ = fs::remove_file(self.swap_path(file.id));
+                }
+                continue;
+            }
+
+            if self.autosave > 0.0 && file.path.is_some() && !state.autosave_failed && now - state.changed_at >= self.autosave {
+                // Never over a change made outside the editor, ':w' asks about those
+                let result = if file.changed_on_disk() {
+                    Err(io::Error::other("the file changed on the disk"))
+                } else {
+                    file.save(console.backup)
+                };
+
+                match result {
+                    Ok(warning) => {
+                        if let Some(warning) = warning {
+                            console.message(&format!("Autosaved {}, {}", file.name(), warning));
+                        }
+                        if state.swapped.take().is_some() {
+                            let _ = fs::remove_file(self.swap_path(file.id));
+                        }
+                        continue;
+                    }
+                    Err(e) => {
+                        state.autosave_failed = true;
+                        console.message(&format!("Autosave of {} failed: {}", file.name(), e));
+                    }
+                }
+            }
+
+            if state.swapped != Some(state.changes) && file.large.is_none() {
+                let changes = state.changes;
+                match self.write_swap(file) {
+                    Ok(()) => self.states[index].swapped = Some(changes),
+                    Err(e) => console.message(&format!("Cannot write the swap file of {}: {}", file.name(), e))
+                }
+            }
+        }
+    }
+}
+
+/// Ask whether to recover a leftover swap file
+fn offer_recovery(console: &mut EditorConsole, path: PathBuf) {
+    let (file_path, _) = match read_swap(&path) {
+        Ok(swap) => swap,
+        Err(_) => return
+    };
+
+    let name = file_path.map_or_else(|| String::from("an untitled file"), |p| p.display().to_string());
+    let time = fs::metadata(&path).and_then(|m| m.modified()).map(format_time).unwrap_or_default();
+
+    console.ask(ConsolePrompt::RecoverSwap(path),
+        &format!("Unsaved changes of {} from {} were left by a crash, recover them? (y/n, n discards)", name, time));
+}
+
+/// Open the text of a swap file as a buffer with unsaved changes, and remove the swap file
+pub fn recover_swap(path: &Path, buffers: &mut EditorBuffers) -> io::Result<String> {
+    let (file_path, text) = read_swap(path)?;
+
+    match &file_path {
+        Some(p) if p.exists() => buffers.open(p)?,
+        _ => {
+            let mut file = EditorFile::new();
+            file.path = file_path;
+            buffers.add(file);
+        }
+    }
+
+    // Recovered as an edit, so undoing it gives back the file on the disk
+    let EditorFile { text: current, cursor, history, .. } = buffers.current_mut();
+    history.record(current, 0, current.len(), text.len(), cursor.xy, false);
+    *current = text;
+    cursor.xy = (0, 0);
+
+    // Every buffer gets its own swap file, the recovered one is not needed anymore
+    fs::remove_file(path)?;
+    Ok(buffers.current().name())
+}
+
+pub fn discard_swap(path: &Path) -> io::Result<()> {
+    fs::remove_file(path)
+}
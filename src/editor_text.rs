@@ -4,6 +4,11 @@
 //
 // The editor's identation can be switched on/off
 // through the console.
+//
+// Large files (see editor_large.rs) are drawn in a single colour.
+//
+// Read-only buffers (':ro') refuse edits with an alert sound,
+// the cursor still moves.
 
 use macroquad::prelude::*;
 use once_cell::sync::Lazy;
@@ -11,10 +16,20 @@ use regex::Regex;
 
 use crate::editor_audio::EditorAudio;
 
+use crate::editor_commands::{Command, CursorMove, EditCommand};
+
 use crate::editor_cursor::*;
 
 use crate::editor_console::EditorConsole;
 
+use crate::editor_file::EditorFile;
+
+use crate::editor_history::EditorHistory;
+
+use crate::editor_jumps::*;
+
+use crate::editor_palette::palette;
+
 #[path = "editor_cursor.rs"]
 mod editor_cursor;
 
@@ -49,26 +64,11 @@ static TOKEN_PATTERN: Lazy<Regex> = Lazy::new(|| {
 const FILE_LINE_NUMBER_X_MARGIN: f32 = 5.0;
 const FILE_LINE_NUMBER_Y_MARGIN: f32 = 6.0;
 
-const FILE_TEXT_X_MARGIN: f32 = 50.0;
+pub const FILE_TEXT_X_MARGIN: f32 = 50.0;
 const FILE_TEXT_Y_MARGIN: f32 = 60.0;
-const TAB_SIZE: usize = 6;
+pub const TAB_SIZE: usize = 6;
 const TAB_PATTERN: &str = "      ";
-
-pub const BACKGROUND_COLOR: Color     = Color::from_rgba(8, 0, 15, 255);        // Theater dark — emotional void
-const IDENTIFIER_COLOR: Color         = Color::from_rgba(190, 140, 230, 255);   // Pale violet — fateful names
-const PUNCTUATION_COLOR: Color        = Color::from_rgba(255, 255, 255, 255);   // White — clarity in despair
-const CONTROL_FLOW_COLOR: Color       = Color::from_rgba(130, 100, 255, 255);   // Tragic blue — falling motion
-const STORAGE_CLASS_COLOR: Color      = Color::from_rgba(255, 70, 110, 255);    // Crimson sorrow — bleeding intent
-const TYPE_QUALIFIER_COLOR: Color     = Color::from_rgba(255, 210, 90, 255);    // Pale gold — faded grandeur
-pub const COMPOSITE_TYPE_COLOR: Color = Color::from_rgba(140, 0, 180, 255);     // Dark purple — structure of fate
-const MISC_COLOR: Color               = Color::from_rgba(100, 130, 200, 255);   // Twilight blue — haunting echo
-const DATA_TYPE_COLOR: Color          = Color::from_rgba(60, 190, 150, 255);    // Teal — fragile balance
-const NUMBER_LITERAL_COLOR: Color     = Color::from_rgba(255, 235, 150, 255);   // Candle gold — memory counts
-const STRING_LITERAL_COLOR: Color     = Color::from_rgba(255, 120, 170, 255);   // Mourning rose — spoken sorrow
-const COMMENT_COLOR: Color            = Color::from_rgba(100, 90, 110, 255);    // Smoke gray — whispered lament
-const CURSOR_COLOR: Color             = Color::from_rgba(255, 0, 130, 255);     // Magenta glow — pulse of pain
-const MACRO_COLOR: Color              = Color::from_rgba(255, 110, 0, 255);     // Ember orange — spark of catharsis
-const MAIN_COLOR: Color               = Color::from_rgba(180, 60, 255, 255);    // Regal violet — tragic beauty
+const PLAIN_LINE_MAX_CHARS: usize = 1000; // Drawn of a line without highlighting
 
 const C_CONTROL_FLOW_STATEMENTS: [&str ; 12] = [
     "if",
@@ -131,135 +131,216 @@ fn char_to_byte(line: &str, char_idx: usize) -> usize {
 /// Calibrate the color of a token
 fn calibrate_string_color(string: &str) -> Color {
     if C_CONTROL_FLOW_STATEMENTS.contains(&string) {
-        return CONTROL_FLOW_COLOR;
+        return palette().control_flow;
     } else if C_TYPE_QUALIFIERS.contains(&string) {
-        return TYPE_QUALIFIER_COLOR;
+        return palette().type_qualifier;
     } else if C_COMPOSITE_TYPES.contains(&string) {
-        return COMPOSITE_TYPE_COLOR;
+        return palette().composite_type;
     } else if C_STORAGE_CLASS_SPECIFIERS.contains(&string) {
-        return STORAGE_CLASS_COLOR;
+        return palette().storage_class;
     } else if C_MISC.contains(&string) {
-        return MISC_COLOR;
+        return palette().misc;
     } else if C_DATA_TYPES.contains(&string) {
-        return DATA_TYPE_COLOR;
+        return palette().data_type;
     } else if string.chars().all(|c| c.is_ascii_digit()) {
-        return NUMBER_LITERAL_COLOR;
+        return palette().number_literal;
     } else {
-        return IDENTIFIER_COLOR;
+        return palette().identifier;
     }
 }
 
-/// Record special key presses
-pub fn record_special_keys(cursor: &mut EditorCursor, text: &mut Vec<String>, audio: &EditorAudio, console: &mut EditorConsole) -> bool {
-    if is_key_pressed(KeyCode::Backspace) {
-        audio.play_delete();
+/// Run a command of the keymap on the current file (see editor_keymap.rs)
+pub fn record_special_keys(command: Command, file: &mut EditorFile, jumps: &mut EditorJumpList, audio: &EditorAudio, console: &mut EditorConsole) {
+    if file.readonly && matches!(command, Command::Edit(_)) {
+        reject_edit(file, audio, console);
+        return;
+    }
 
-        if text.is_empty() {
-            return true;
+    // The last line of a large file is decoded for the cursor to go to its end
+    if matches!(command, Command::Cursor(CursorMove::FileEnd) | Command::Select(CursorMove::FileEnd) | Command::SelectAll) {
+        let last = file.text.len() - 1;
+        file.decode_lines(last, last + 1);
+    }
+
+    let EditorFile { text, cursor, history, path, .. } = file;
+
+    // 'select.*' moves the cursor like 'cursor.*', keeping where the selection started
+    let motion = match command {
+        Command::SelectAll => {
+            cursor.anchor = Some((0, 0));
+            cursor.xy.1 = text.len() - 1;
+            cursor.xy.0 = text[cursor.xy.1].chars().count();
+            return;
         }
-    
-        // Clamp cursor_x to line length
-        let line = &mut text[cursor.xy.1];
-        let line_len = line.chars().count();
-        cursor.xy.0 = (cursor.xy.0).min(line_len);
-    
-        if cursor.xy.0 == 0 {
-            // Merge with previous line if possible
-            if cursor.xy.1 > 0 {
-                let current_line = text.remove(cursor.xy.1);
-                cursor.xy.1 -= 1;
-                cursor.xy.0 = text[cursor.xy.1].chars().count();
-                text[cursor.xy.1].push_str(&current_line);
-            }
-            return true;
+        Command::Select(motion) => {
+            cursor.anchor.get_or_insert(cursor.xy);
+            motion
         }
-    
-        let cursor_pos = cursor.xy.0;
-    
-        // Tab deletion
-        if cursor_pos >= TAB_SIZE {
-            let start_char = cursor_pos - TAB_SIZE;
-            let end_char = cursor_pos;
-            let start_byte = char_to_byte(line, start_char);
-            let end_byte = char_to_byte(line, end_char);
-    
-            if &line[start_byte..end_byte] == TAB_PATTERN {
-                line.replace_range(start_byte..end_byte, "");
-                cursor.xy.0 -= TAB_SIZE;
-                return true;
-            }
+        Command::Cursor(motion) => {
+            cursor.anchor = None;
+            motion
         }
-    
-        // Normal deletion
-        let byte_idx = char_to_byte(line, cursor_pos - 1);
-        if byte_idx < line.len() {
-            line.remove(byte_idx);
-            cursor.xy.0 -= 1;
+        Command::Edit(edit) => {
+            cursor.anchor = None;
+            edit_text(edit, text, cursor, history, audio);
+            return;
         }
-    
-        return true;
+        _ => return
+    };
+
+    match motion {
+        // Start and end of the file
+        CursorMove::FileStart => {
+            audio.play_nav();
+            jumps.push(path, cursor.xy);
+            cursor.xy = (0, 0);
+        }
+        CursorMove::FileEnd => {
+            audio.play_nav();
+            jumps.push(path, cursor.xy);
+            cursor.xy.1 = text.len() - 1;
+            cursor.xy.0 = text[cursor.xy.1].chars().count();
+        }
+        CursorMove::WordLeft | CursorMove::WordRight => file_text_special_navigation(motion, &mut cursor.xy, text, audio),
+        CursorMove::Up | CursorMove::Down | CursorMove::Left | CursorMove::Right =>
+            file_text_navigation(motion, &mut cursor.xy, text, audio)
     }
+}
 
-    if is_key_pressed(KeyCode::Tab) {
-        audio.play_space();
+/// Run one of the 'edit.*' commands at the cursor
+fn edit_text(edit: EditCommand, text: &mut Vec<String>, cursor: &mut EditorCursor, history: &mut EditorHistory, audio: &EditorAudio) {
+    match edit {
+        EditCommand::Backspace => {
+            audio.play_delete();
 
-        let line = &mut text[cursor.xy.1];
-        let byte_idx = char_to_byte(line, cursor.xy.0);
-        line.insert_str(byte_idx, TAB_PATTERN);
-        cursor.xy.0 += TAB_SIZE;
-        return true;
-    }
+            if text.is_empty() {
+                return;
+            }
 
-    if is_key_pressed(KeyCode::Enter) {
-        audio.play_return();
+            // Clamp cursor_x to line length
+            let line = &mut text[cursor.xy.1];
+            let line_len = line.chars().count();
+            cursor.xy.0 = (cursor.xy.0).min(line_len);
+
+            if cursor.xy.0 == 0 {
+                // Merge with previous line if possible
+                if cursor.xy.1 > 0 {
+                    history.record(text, cursor.xy.1 - 1, 2, 1, cursor.xy, false);
+                    let current_line = text.remove(cursor.xy.1);
+                    cursor.xy.1 -= 1;
+                    cursor.xy.0 = text[cursor.xy.1].chars().count();
+                    text[cursor.xy.1].push_str(&current_line);
+                }
+                return;
+            }
 
-        let line = &mut text[cursor.xy.1];
-        let rest = line.split_off(char_to_byte(line, cursor.xy.0));
-        cursor.xy.1 += 1;
+            let cursor_pos = cursor.xy.0;
+            history.record(text, cursor.xy.1, 1, 1, cursor.xy, true);
+            let line = &mut text[cursor.xy.1];
+
+            // Tab deletion
+            if cursor_pos >= TAB_SIZE {
+                let start_char = cursor_pos - TAB_SIZE;
+                let end_char = cursor_pos;
+                let start_byte = char_to_byte(line, start_char);
+                let end_byte = char_to_byte(line, end_char);
+
+                if &line[start_byte..end_byte] == TAB_PATTERN {
+                    line.replace_range(start_byte..end_byte, "");
+                    cursor.xy.0 -= TAB_SIZE;
+                    return;
+                }
+            }
 
-        // TODO: Smarter identation here
+            // Normal deletion
+            let byte_idx = char_to_byte(line, cursor_pos - 1);
+            if byte_idx < line.len() {
+                line.remove(byte_idx);
+                cursor.xy.0 -= 1;
+            }
+        }
+        EditCommand::Indent => {
+            audio.play_space();
+
+            history.record(text, cursor.xy.1, 1, 1, cursor.xy, false);
+            let line = &mut text[cursor.xy.1];
+            let byte_idx = char_to_byte(line, cursor.xy.0);
+            line.insert_str(byte_idx, TAB_PATTERN);
+            cursor.xy.0 += TAB_SIZE;
+        }
+        EditCommand::Newline => {
+            audio.play_return();
 
-        cursor.xy.0 = 0;
-        
-        text.insert(cursor.xy.1, rest);
-        return true;
-    }
+            history.record(text, cursor.xy.1, 1, 2, cursor.xy, false);
+            let line = &mut text[cursor.xy.1];
+            let rest = line.split_off(char_to_byte(line, cursor.xy.0));
+            cursor.xy.1 += 1;
 
-    // More special keys
-    if is_key_down(KeyCode::LeftControl) {
-        // Console switch
-        if is_key_pressed(KeyCode::GraveAccent) {
-            console.mode = !console.mode; 
-        }
+            // TODO: Smarter identation here
 
-        file_text_special_navigation(&mut cursor.xy, text, audio);
+            cursor.xy.0 = 0;
 
-        return true;
-    } else {
-        file_text_navigation(&mut cursor.xy, text, audio);
+            text.insert(cursor.xy.1, rest);
+        }
+        EditCommand::Undo => if let Some(xy) = history.undo(text, cursor.xy) {
+            audio.play_delete();
+            cursor.xy = xy;
+        },
+        EditCommand::Redo => if let Some(xy) = history.redo(text, cursor.xy) {
+            audio.play_insert();
+            cursor.xy = xy;
+        }
     }
+}
 
-    false
+/// Refuse an edit of a read-only buffer
+fn reject_edit(file: &EditorFile, audio: &EditorAudio, console: &mut EditorConsole) {
+    audio.play_alert();
+    console.message(&file.readonly_message());
+    clear_input_queue();
 }
 
-/// Standard key recording function
-pub fn record_keyboard_to_file_text(cursor: &mut EditorCursor, text: &mut Vec<String>, audio: &EditorAudio, console: &mut EditorConsole) {
+/// Standard key recording function, 'command' is what the keymap made of the keys pressed
+pub fn record_keyboard_to_file_text(file: &mut EditorFile, audio: &EditorAudio, console: &mut EditorConsole,
+    command: Option<&str>) {
     // let c = get_char_pressed().unwrap(); // Unwrap removes the Result/Option wrapper.
 
-    if text.is_empty() { // Allocate memory for a new string
-        text.push(String::new());
+    if file.text.is_empty() { // Allocate memory for a new string
+        file.text.push(String::new());
     }
 
-    if record_special_keys(cursor, text, audio, console) {
-        return; // Handle the special key and terminate the call, as to 
+    if let Some(command) = command {
+        console.commands.push(command, None);
+        return; // Run with the other commands (see editor_commands.rs), as to
         // not record any special escape character
     }
 
+    // Keys pressed with ctrl are never text
+    if is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl) {
+        clear_input_queue();
+        return;
+    }
+
+    if file.readonly {
+        if get_char_pressed().is_some() {
+            reject_edit(file, audio, console);
+        }
+        return;
+    }
+
+    let EditorFile { text, cursor, history, .. } = file;
+
     if let Some(c) = get_char_pressed() {
+        cursor.anchor = None;
+
         // We will also handle smart/smarter identation here.
         while cursor.xy.1 >= text.len() {
             text.push(String::new());
         }
+
+        if !matches!(c, '\u{8}' | '\r' | '\n' | '\t') {
+            history.record(text, cursor.xy.1, 1, 1, cursor.xy, c != ' ');
+        }
         match c {
             '\u{8}' | '\r' | '\n' | '\t' => {
                 // We also have to pre-terminate with these special characters,
@@ -382,65 +463,127 @@ pub fn record_keyboard_to_file_text(cursor: &mut EditorCursor, text: &mut Vec<St
     }
 }
 
-/// Text drawing function
-pub fn draw(text: &Vec<String>, cursor_x: usize, cursor_y: usize, gts: &mut EditorGeneralTextStylizer, console: &EditorConsole) {
-    let start_x = FILE_TEXT_X_MARGIN;
-    let start_y = FILE_TEXT_Y_MARGIN;
+/// Baseline of the first line inside 'area', panes at the top of the
+/// screen leave room for the buffer tabs
+fn text_start_y(area: Rect, gts: &EditorGeneralTextStylizer) -> f32 {
+    if area.y <= 0.0 {
+        FILE_TEXT_Y_MARGIN
+    } else {
+        area.y + gts.font_size as f32 * 1.2
+    }
+}
+
+/// Number of lines that fit in 'area'
+pub fn visible_line_count(gts: &EditorGeneralTextStylizer, area: Rect) -> usize {
+    (((area.y + area.h - text_start_y(area, gts)) / gts.font_size as f32).max(1.0)) as usize
+}
+
+/// Width of the first 'chars' characters of 'line'
+fn chars_width(line: &str, chars: usize, gts: &EditorGeneralTextStylizer) -> f32 {
+    let end = char_to_byte(line, chars.min(line.chars().count()));
+    measure_text(&line[..end], Some(&gts.font), gts.font_size, 1.0).width
+}
+
+/// Selected text behind the visible lines, line ends show as a space
+fn draw_selection(text: &[String], cursor: EditorCursor, scroll: usize, visible_lines: usize, start: (f32, f32), gts: &EditorGeneralTextStylizer) {
+    let ((from_x, from_y), (to_x, to_y)) = match cursor.selection() {
+        Some(selection) => selection,
+        None => return
+    };
     let line_spacing = gts.font_size as f32;
+    let color = Color { a: 0.3, ..palette().cursor };
+
+    for (line_index, line) in text.iter().enumerate().skip(scroll.max(from_y)).take(visible_lines) {
+        if line_index > to_y {
+            break;
+        }
+
+        let left = if line_index == from_y { chars_width(line, from_x, gts) } else { 0.0 };
+        let right = if line_index == to_y {
+            chars_width(line, to_x, gts)
+        } else {
+            chars_width(line, usize::MAX, gts) + measure_text(" ", Some(&gts.font), gts.font_size, 1.0).width
+        };
+
+        let y = start.1 + (line_index - scroll) as f32 * line_spacing - gts.font_size as f32 * 0.8;
+        draw_rectangle(start.0 + left, y, right - left, line_spacing, color);
+    }
+}
+
+/// Text drawing function, 'scroll' is the first visible line
+/// and everything is clipped to 'area'
+pub fn draw(text: &Vec<String>, cursor: EditorCursor, scroll: usize, area: Rect, focused: bool, highlight: bool, gts: &mut EditorGeneralTextStylizer) {
+    let (cursor_x, cursor_y) = cursor.xy;
+    let start_x = area.x + FILE_TEXT_X_MARGIN;
+    let start_y = text_start_y(area, gts);
+    let line_spacing = gts.font_size as f32;
+    let visible_lines = visible_line_count(gts, area);
+
+    let gl = unsafe { get_internal_gl() };
+    gl.quad_gl.scissor(Some((area.x as i32, area.y as i32, area.w as i32, area.h as i32)));
+
+    draw_selection(text, cursor, scroll, visible_lines, (start_x, start_y), gts);
     
     // Draw cursor
-    if cursor_y < text.len() {
+    if cursor_y < text.len() && cursor_y >= scroll {
         let line = &text[cursor_y];
-        let cursor_text = &line[..cursor_x.min(line.len())];
-        let text_before_cursor = measure_text(cursor_text, Some(&gts.font), gts.font_size, 1.0);
-        let cursor_x_pos = start_x + text_before_cursor.width;
-        let cursor_y_pos = start_y + cursor_y as f32 * line_spacing;
+        let cursor_x_pos = start_x + chars_width(line, cursor_x, gts);
+        let cursor_y_pos = start_y + (cursor_y - scroll) as f32 * line_spacing;
 
         // Cursor width, either of the current char size, or static 2.0px
-        let cursor_width = if CURSOR_LINE_TO_WIDTH && cursor_x < line.len() {
-            measure_text(
-                &line.chars().nth(cursor_x).unwrap().to_string(),
+        let cursor_width = match line.chars().nth(cursor_x) {
+            Some(c) if CURSOR_LINE_TO_WIDTH => measure_text(
+                &c.to_string(),
                 Some(&gts.font),
                 gts.font_size,
                 1.0,
-            ).width
-        } else {
-            2.0
+            ).width,
+            _ => 2.0
         };
 
+        // Panes without focus show a faded cursor
+        let cursor_color = if focused { palette().cursor } else { Color { a: 0.35, ..palette().cursor } };
+
         draw_rectangle(
             cursor_x_pos,
             cursor_y_pos - gts.font_size as f32 * 0.8,
             cursor_width,
             gts.font_size as f32,
-            CURSOR_COLOR,
+            cursor_color,
         );
     }
 
     let mut x;
     let mut y;    
 
-    for (line_index, line) in text.iter().enumerate() {
+    for (line_index, line) in text.iter().enumerate().skip(scroll).take(visible_lines) {
         x = start_x;
-        y = start_y + line_index as f32 * line_spacing;
+        y = start_y + (line_index - scroll) as f32 * line_spacing;
+
+        if !highlight {
+            let end = line.char_indices().nth(PLAIN_LINE_MAX_CHARS).map_or(line.len(), |(i, _)| i);
+            gts.color = palette().identifier;
+            gts.draw(&line[..end], x, y);
+            continue;
+        }
 
         for cap in TOKEN_PATTERN.find_iter(line) {
             let token = cap.as_str();
 
             let color = if token.starts_with("//") || token.starts_with("/*") {
-                COMMENT_COLOR
+                palette().comment
             } else if token.trim_start().starts_with("#") {
-                MACRO_COLOR
+                palette().macros
             } else if (token.starts_with('"') && token.ends_with('"')) || (token.starts_with('<') && token.ends_with('>')) {
-                STRING_LITERAL_COLOR
+                palette().string_literal
             } else if token.chars().all(|c| c.is_whitespace()) {
-                IDENTIFIER_COLOR
+                palette().identifier
             } else if token.chars().all(|c| !c.is_alphanumeric() && !c.is_whitespace() && c != '_') {
-                PUNCTUATION_COLOR
+                palette().punctuation
             } else if TOKEN_PATTERN.is_match(token) && token.chars().any(|c| c.is_ascii_digit()) {
-                NUMBER_LITERAL_COLOR
+                palette().number_literal
             } else if token == "main" {
-                MAIN_COLOR
+                palette().main
             } else {
                 // Normal identifiers like variable names and functions
                 let clean = token.trim_matches(|c: char| !c.is_alphanumeric() && c != '_');
@@ -466,7 +609,7 @@ pub fn draw(text: &Vec<String>, cursor_x: usize, cursor_y: usize, gts: &mut Edit
     }
 
     // Draw line numbers
-    gts.color = CURSOR_COLOR;
+    gts.color = palette().cursor;
 
     let text_len;
     if text.is_empty() {
@@ -475,13 +618,12 @@ pub fn draw(text: &Vec<String>, cursor_x: usize, cursor_y: usize, gts: &mut Edit
         text_len = text.len();
     }
 
-    for i in 0..text_len {
-        gts.draw(&i.to_string(), FILE_LINE_NUMBER_X_MARGIN,
-            1.1 * FILE_TEXT_X_MARGIN + FILE_LINE_NUMBER_Y_MARGIN + gts.font_size as f32 * i as f32
+    for i in scroll..text_len.min(scroll + visible_lines) {
+        gts.draw(&i.to_string(), area.x + FILE_LINE_NUMBER_X_MARGIN,
+            start_y - FILE_TEXT_Y_MARGIN + 1.1 * FILE_TEXT_X_MARGIN + FILE_LINE_NUMBER_Y_MARGIN + gts.font_size as f32 * (i - scroll) as f32
         );
     }
-    
-    if console.mode {
-        console.draw();
-    }
+
+    let gl = unsafe { get_internal_gl() };
+    gl.quad_gl.scissor(None);
 }
@@ -0,0 +1,185 @@
+// Directory tree module, a directory and its contents
+// with directories that can be expanded and collapsed.
+//
+// Directories are read when first expanded, directories come first
+// then files, both sorted by name. '.git' is never shown.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const TREE_SKIPPED: [&str; 1] = [".git"];
+
+struct TreeNode {
+    path: PathBuf,
+    name: String,
+    is_dir: bool,
+    expanded: bool,
+    children: Option<Vec<TreeNode>> // None until first expanded
+}
+
+/// A visible entry of the tree
+pub struct TreeRow {
+    pub path: PathBuf,
+    pub name: String,
+    pub depth: usize,
+    pub is_dir: bool,
+    pub expanded: bool
+}
+
+pub struct DirectoryTree {
+    root: TreeNode
+}
+
+impl TreeNode {
+    fn new(path: PathBuf, is_dir: bool) -> TreeNode {
+        let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().to_string());
+        TreeNode { path, name, is_dir, expanded: false, children: None }
+    }
+
+    fn read_children(&self) -> Vec<TreeNode> {
+        let mut children: Vec<TreeNode> = match fs::read_dir(&self.path) {
+            Ok(entries) => entries.filter_map(|e| e.ok())
+                .filter(|e| !TREE_SKIPPED.contains(&e.file_name().to_string_lossy().as_ref()))
+                .map(|e| {
+                    // Symlinked directories are listed but not followed
+                    let is_dir = e.file_type().is_ok_and(|t| t.is_dir());
+                    TreeNode::new(e.path(), is_dir)
+                })
+                .collect(),
+            Err(_) => vec![]
+        };
+
+        children.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+        children
+    }
+
+    fn expand(&mut self) {
+        if !self.is_dir {
+            return;
+        }
+
+        if self.children.is_none() {
+            self.children = Some(self.read_children());
+        }
+        self.expanded = true;
+    }
+
+    fn find_mut(&mut self, path: &Path) -> Option<&mut TreeNode> {
+        if self.path == path {
+            return Some(self);
+        }
+
+        if !path.starts_with(&self.path) {
+            return None;
+        }
+
+        self.children.as_mut()?.iter_mut().find_map(|c| c.find_mut(path))
+    }
+
+    fn rows(&self, depth: usize, rows: &mut Vec<TreeRow>) {
+        rows.push(TreeRow { path: self.path.clone(), name: self.name.clone(), depth, is_dir: self.is_dir, expanded: self.expanded });
+
+        if self.expanded && let Some(children) = &self.children {
+            for child in children {
+                child.rows(depth + 1, rows);
+            }
+        }
+    }
+
+    /// Expand every directory below, until 'budget' entries were read
+    fn expand_all(&mut self, budget: &mut usize) {
+        if *budget == 0 {
+            return;
+        }
+
+        self.expand();
+
+        if let Some(children) = self.children.as_mut() {
+            *budget = budget.saturating_sub(children.len());
+
+            for child in children.iter_mut().filter(|c| c.is_dir) {
+                child.expand_all(budget);
+            }
+        }
+    }
+
+    /// Read the expanded directories again, keeping what was expanded
+    fn refresh(&mut self) {
+        let mut old = match self.children.take() {
+            Some(children) => children,
+            None => return
+        };
+
+        let mut children = self.read_children();
+
+        for child in children.iter_mut() {
+            if let Some(i) = old.iter().position(|o| o.path == child.path && o.is_dir == child.is_dir) {
+                let mut previous = old.swap_remove(i);
+                previous.refresh();
+                *child = previous;
+            }
+        }
+
+        self.children = Some(children);
+    }
+}
+
+impl DirectoryTree {
+    /// Tree of 'root', with only the root expanded
+    pub fn new(root: &Path) -> DirectoryTree {
+        let mut root = TreeNode::new(root.to_path_buf(), true);
+        root.expand();
+
+        DirectoryTree { root }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root.path
+    }
+
+    /// Expand everything, up to about 'max_entries' entries
+    pub fn expand_all(&mut self, max_entries: usize) {
+        let mut budget = max_entries;
+        self.root.expand_all(&mut budget);
+    }
+
+    /// The entries not hidden inside a collapsed directory, in order
+    pub fn rows(&self) -> Vec<TreeRow> {
+        let mut rows = vec![];
+        self.root.rows(0, &mut rows);
+        rows
+    }
+
+    pub fn set_expanded(&mut self, path: &Path, expanded: bool) {
+        if let Some(node) = self.root.find_mut(path) {
+            if expanded {
+                node.expand();
+            } else {
+                node.expanded = false;
+            }
+        }
+    }
+
+    pub fn toggle(&mut self, path: &Path) {
+        let expanded = self.root.find_mut(path).is_some_and(|n| n.expanded);
+        self.set_expanded(path, !expanded);
+    }
+
+    /// Expand the directories leading to 'path'
+    pub fn reveal(&mut self, path: &Path) {
+        let mut ancestors: Vec<PathBuf> = path.ancestors().skip(1)
+            .take_while(|a| a.starts_with(&self.root.path))
+            .map(|a| a.to_path_buf())
+            .collect();
+        ancestors.reverse();
+
+        for ancestor in ancestors {
+            self.set_expanded(&ancestor, true);
+        }
+    }
+
+    /// Pick up files created or removed since the directories were read
+    pub fn refresh(&mut self) {
+        self.root.refresh();
+    }
+}
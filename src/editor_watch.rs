@@ -0,0 +1,72 @@
+// Watch module, notices open files changed outside the editor,
+// by a 'git checkout' or a code generator for example.
+//
+// Every WATCH_INTERVAL_SECONDS the files of the buffers are compared
+// with what they were when last read or written. A buffer without
+// unsaved changes is read again, for one with unsaved changes the console
+// asks whether to (r)eload it, (k)eep ours or see the (d)iff first,
+// Escape keeps ours too.
+// Writing over a file changed on the disk asks first too (see ':w').
+
+use macroquad::prelude::get_time;
+
+use crate::editor_buffers::EditorBuffers;
+use crate::editor_console::{ConsolePrompt, EditorConsole};
+use crate::editor_file::DiskStamp;
+
+const WATCH_INTERVAL_SECONDS: f64 = 1.0;
+
+pub struct EditorWatch {
+    last_check: f64
+}
+
+impl EditorWatch {
+    /// Watch constructor
+    pub fn new() -> EditorWatch {
+        EditorWatch { last_check: get_time() }
+    }
+
+    pub fn update(&mut self, buffers: &mut EditorBuffers, console: &mut EditorConsole) {
+        let now = get_time();
+        if now - self.last_check < WATCH_INTERVAL_SECONDS {
+            return;
+        }
+        self.last_check = now;
+
+        for file in buffers.files.iter_mut() {
+            let path = match &file.path {
+                Some(path) if file.disk.is_some() && file.loading.is_none() => path.clone(),
+                _ => continue
+            };
+
+            let stamp = DiskStamp::of(&path);
+            if stamp == file.disk {
+                continue;
+            }
+
+            // Removed or unreadable, nothing to read, the next ':w' writes it again
+            if stamp.is_none() {
+                console.message(&format!("{} was removed from the disk", file.name()));
+                file.disk = None;
+                continue;
+            }
+
+            if !file.is_dirty() {
+                match file.reload() {
+                    Ok(()) => console.message(&format!("{} changed on the disk and was read again", file.name())),
+                    Err(e) => console.message(&format!("Cannot read {} again: {}", file.name(), e))
+                }
+                continue;
+            }
+
+            if file.kept_over != stamp && console.prompt.is_none() {
+                ask_external_change(console, file.id, &file.name());
+            }
+        }
+    }
+}
+
+pub fn ask_external_change(console: &mut EditorConsole, id: usize, name: &str) {
+    console.ask(ConsolePrompt::ExternalChange(id),
+        &format!("{} changed on the disk and has unsaved changes: (r)eload, (k)eep ours or (d)iff?", name));
+}
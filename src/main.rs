@@ -1,3 +1,9 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+
 use macroquad::prelude::*;
 
 mod editor_console;
@@ -7,35 +13,277 @@ mod editor_audio;
 use editor_audio::*;
 
 mod editor_cursor;
-use editor_cursor::*;
 
 mod editor_text;
 use editor_text::*;
 
-#[macroquad::main("Muse")]
-async fn main() {
-    set_fullscreen(true);
-    
+mod editor_file;
+use editor_file::EditorFile;
+
+mod editor_buffers;
+use editor_buffers::*;
+
+mod editor_panes;
+use editor_panes::*;
+
+mod editor_history;
+
+mod editor_directives;
+use editor_directives::request_quit;
+
+mod editor_grep;
+use editor_grep::update_grep;
+
+mod editor_fuzzy;
+
+mod editor_jumps;
+
+mod editor_config;
+use editor_config::*;
+
+mod editor_command_history;
+
+mod editor_completion;
+use editor_completion::find_font;
+
+mod editor_fs;
+
+mod editor_tree;
+
+mod editor_info;
+
+mod editor_open;
+
+mod editor_sidebar;
+use editor_sidebar::*;
+
+mod editor_swap;
+use editor_swap::*;
+
+mod editor_diff;
+
+mod editor_watch;
+use editor_watch::*;
+
+mod editor_status;
+use editor_status::*;
+
+mod editor_encoding;
+use editor_encoding::*;
+
+mod editor_large;
+use editor_large::*;
+
+mod editor_palette;
+use editor_palette::*;
+
+mod editor_cli;
+use editor_cli::*;
+
+mod editor_session;
+use editor_session::*;
+
+mod editor_recent;
+
+mod editor_keymap;
+use editor_keymap::KeyScope;
+
+mod editor_commands;
+use editor_commands::*;
+
+fn main() {
+    let options = match parse_args(env::args().skip(1).collect()) {
+        Ok(CliCommand::Run(options)) => options,
+        Ok(CliCommand::Help) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Ok(CliCommand::Version) => {
+            println!("muse {}", VERSION);
+            return;
+        }
+        Err(e) => {
+            eprintln!("muse: {}\nTry 'muse --help' for more information.", e);
+            process::exit(2);
+        }
+    };
+
+    // Settings, a config asked for by name has to exist
+    if options.config != Path::new(CONFIG_FILE) && !options.config.is_file() {
+        eprintln!("muse: cannot read the config file '{}'", options.config.display());
+        process::exit(2);
+    }
+    let config = EditorConfig::load(&options.config);
+
+    // Palette, a bad one on the command line stops here, one from cal.conf is reported in the console
+    let mut palette_error = None;
+    match options.palette.as_deref().or(config.get_str("palette")).map(EditorPalette::load) {
+        Some(Ok(palette)) => set_palette(palette),
+        Some(Err(e)) if options.palette.is_some() => {
+            eprintln!("muse: {}", e);
+            process::exit(2);
+        }
+        Some(Err(e)) => palette_error = Some(e),
+        None => {}
+    }
+
+    let window = Conf { window_title: String::from("Muse"), fullscreen: !options.windowed, ..Default::default() };
+    macroquad::Window::from_config(window, run(options, config, palette_error));
+}
+
+async fn run(options: CliOptions, config: EditorConfig, palette_error: Option<String>) {
     // Editor audio
     let audio = EditorAudio::new().await;
     // Editor general text stylizer
     let mut gts = EditorGeneralTextStylizer::new().await;
-    // Editor Cursor
-    let mut file_cursor = EditorCursor::new(); // Cursor's x and y
+    gts.font_size = options.font_size.unwrap_or(config.get_usize("font_size", gts.font_size as usize) as u16);
+    // Settings
+    set_fallback_encoding(&config);
+    set_large_file_threshold(&config);
     // Console
-    let mut console = EditorConsole::new();
-
-    let mut file_text = vec![];
+    let mut console = EditorConsole::new(&config);
+    if let Some(e) = palette_error {
+        console.message(&e);
+    }
+    if !console.keymap.problems.is_empty() {
+        console.message(&format!("{} problems with the key bindings of {}, ':ekb' lists them",
+            console.keymap.problems.len(), options.config.display()));
+    }
+    if !console.commands.problems.is_empty() {
+        console.message(&format!("{} problems with the macros of {}, ':m' lists them",
+            console.commands.problems.len(), options.config.display()));
+    }
+    if let Some(name) = config.get_str("font") {
+        match find_font(name) {
+            Some(path) => console.font_request = Some(path),
+            None => console.message(&format!("No font named '{}', the default one is used", name))
+        }
+    }
+    if let Some(dir) = &options.directory {
+        console.cwd = fs::canonicalize(dir).unwrap_or_else(|_| dir.clone());
+    }
+    // Open files, each with its own cursor
+    let mut buffers = EditorBuffers::new();
+    open_arguments(&options, &mut buffers, &mut console);
+    // Split windows, each with its own cursor and jump list
+    let mut panes = EditorPanes::new(&buffers);
+    // File explorer to the left of the text
+    let mut sidebar = EditorSidebar::new(&console);
+    // Recovery copies of the unsaved buffers
+    let mut swap = EditorSwap::new(&config);
+    // Changes made to the open files outside the editor
+    let mut watch = EditorWatch::new();
+    // Open files and layout kept between runs
+    let mut session = EditorSession::new();
+    if options.files.is_empty() {
+        offer_last_session(&mut console);
+    }
+    // Mode, file, cursor position and format at the bottom
+    let mut status = EditorStatus::new();
+    // Closing the window asks about unsaved changes and saves the session first
+    prevent_quit();
     
     loop {
-        clear_background(BACKGROUND_COLOR);
+        clear_background(palette().background);
+
+        let screen_area = area_above_status_bar();
+        let text_area = sidebar.text_area(screen_area);
+
+        panes.enter(&mut buffers);
+
+        let scope = if console.mode {
+            KeyScope::Console
+        } else if sidebar.focused {
+            KeyScope::Sidebar
+        } else {
+            KeyScope::Text
+        };
+        let command = console.keymap.update(scope);
+
+        // The keys and directives queue commands, run once the keyboard is read
+        if console.mode {
+            record_keyboard_to_console(&mut console, &buffers, &audio, command);
+        } else if !record_keyboard_to_sidebar(command, &sidebar, &mut console) {
+            record_keyboard_to_file_text(buffers.current_mut(), &audio, &mut console, command);
+        }
+
+        run_queued_commands(&mut CommandContext {
+            console: &mut console,
+            buffers: &mut buffers,
+            panes: &mut panes,
+            sidebar: &mut sidebar,
+            audio: &audio,
+            area: text_area
+        });
+
+        buffers.current_mut().scroll_to_cursor();
+        panes.leave(&buffers);
+        console.recent.update(&buffers);
+        sidebar.update(&console, &buffers);
+        update_large_files(&mut buffers, &mut panes, &mut console);
+        update_grep(&mut console);
+        swap.update(&mut buffers, &mut console);
+        watch.update(&mut buffers, &mut console);
+        panes.shift_jumps(&mut buffers);
+        session.update(&mut console, &mut buffers, &mut panes);
 
-        record_keyboard_to_file_text(&mut file_cursor, &mut file_text, &audio, &mut console);
+        if is_quit_requested() {
+            request_quit(&mut console, &buffers);
+        }
+        if console.quit_request {
+            // Written or discarded, the changes need no recovery
+            swap.remove_all();
+            session.save_last(&console, &buffers, &panes);
+            break;
+        }
 
-        draw(&mut file_text, file_cursor.xy.0, file_cursor.xy.1, &mut gts, &console);
+        // Font of cal.conf or ':efn'
+        if let Some(path) = console.font_request.take() {
+            match load_ttf_font(&path.to_string_lossy()).await {
+                Ok(font) => gts.font = font,
+                Err(e) => console.message(&format!("Cannot load the font {}: {}", path.display(), e))
+            }
+        }
+
+        panes.draw(text_area, &buffers, &mut gts);
+        buffers.draw(text_area.x, &gts);
+        sidebar.draw(screen_area, &gts);
+        status.draw(&console, &buffers, &sidebar, &gts);
+
+        if console.mode {
+            console.draw(&gts);
+        }
 
         draw_fps();
 
         next_frame().await;
     }
 }
+
+/// Open the files given on the command line, at their line and column
+fn open_arguments(options: &CliOptions, buffers: &mut EditorBuffers, console: &mut EditorConsole) {
+    for target in &options.files {
+        let result = if target.path.exists() {
+            buffers.open(&target.path)
+        } else if target.path.parent().is_none_or(|p| p.as_os_str().is_empty() || p.is_dir()) {
+            // Created by the first ':w'
+            let mut file = EditorFile::new();
+            file.path = Some(target.path.clone());
+            buffers.add(file);
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::NotFound, "its directory does not exist"))
+        };
+
+        if let Err(e) = result {
+            console.message(&format!("Cannot open {}: {}", target.path.display(), e));
+            continue;
+        }
+
+        let file = buffers.current_mut();
+        file.readonly |= options.readonly;
+        if let Some(line) = target.line {
+            file.goto(line - 1, target.column.map_or(0, |c| c - 1));
+        }
+    }
+}